@@ -1,6 +1,8 @@
 use core::mem::zeroed;
 use core::ptr::write_volatile;
 
+use core::arch::global_asm;
+
 mod panic;
 
 use crate::kmain;
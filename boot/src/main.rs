@@ -1,15 +1,14 @@
-#![feature(asm)]
-#![feature(global_asm)]
-
 #![cfg_attr(not(test), no_std)]
 #![cfg_attr(not(test), no_main)]
 
 #[cfg(not(test))]
 mod init;
 
-use xmodem::Xmodem;
+use core::arch::asm;
 use core::time::Duration;
-use pi;
+
+use pi::uart::MiniUart;
+use xmodem::{Mode, Xmodem};
 
 /// Start address of the binary to load and of the bootloader.
 const BINARY_START_ADDR: usize = 0x80000;
@@ -21,14 +20,33 @@ const BINARY_START: *mut u8 = BINARY_START_ADDR as *mut u8;
 /// Free space between the bootloader and the loaded binary's start address.
 const MAX_BINARY_SIZE: usize = BOOTLOADER_START_ADDR - BINARY_START_ADDR;
 
+/// How long to wait for the sender before restarting the transfer.
+const READ_TIMEOUT: Duration = Duration::from_millis(750);
+
 /// Branches to the address `addr` unconditionally.
 unsafe fn jump_to(addr: *mut u8) -> ! {
-    asm!("br $0" : : "r"(addr as usize));
+    asm!("br {}", in(reg) addr as usize);
     loop {
-        asm!("wfe" :::: "volatile")
+        asm!("wfe")
     }
 }
 
 fn kmain() -> ! {
-    // FIXME: Implement the bootloader.
+    let mut uart = MiniUart::new();
+    uart.set_read_timeout(READ_TIMEOUT);
+
+    loop {
+        // The slice ends where the bootloader begins, so an oversized image
+        // fails with `WriteZero` instead of overwriting us.
+        let binary = unsafe { core::slice::from_raw_parts_mut(BINARY_START, MAX_BINARY_SIZE) };
+
+        let mut xmodem = Xmodem::new_with_mode(&mut uart, Mode::Crc);
+        xmodem.set_clock(Some(pi::timer::current_time));
+        match xmodem.receive_data(binary) {
+            Ok(_) => unsafe { jump_to(BINARY_START) },
+            // Nobody is sending yet, or the transfer failed: start over and
+            // keep asking until a transfer succeeds.
+            Err(_) => continue,
+        }
+    }
 }
@@ -0,0 +1,62 @@
+#! /bin/bash
+
+# Boots the bootloader in QEMU, chain-loads the kernel into it over the
+# emulated UART's pty with `ttywrite`, and checks that the kernel came up.
+
+TOP=$(git rev-parse --show-toplevel)
+QEMU_LOG=$(mktemp)
+
+function cleanup_and_exit() {
+  [ -n "${QEMU_PID}" ] && kill ${QEMU_PID} 2> /dev/null
+  rm -f ${QEMU_LOG}
+  exit $1
+}
+
+# Use color when outputting to the terminal.
+if [ -t 1 ]; then
+  KNRM="\x1B[0m"; KRED="\x1B[31m"; KGRN="\x1B[32m"; KBLU="\x1B[34m"
+else
+  KNRM=""; KRED=""; KGRN=""; KBLU=""
+fi
+
+echo -e "${KBLU}Building bootloader, kernel, and ttywrite...${KNRM}"
+if ! make -s build || ! make -s -C ${TOP}/kern bin \
+    || ! (cd ${TOP}/lib/ttywrite && cargo build -q); then
+  echo -e "${KRED}ERROR: compilation failed${KNRM}" >&2
+  cleanup_and_exit 1
+fi
+
+echo -e "${KBLU}Starting QEMU...${KNRM}"
+./qemu.sh build/boot.elf > ${QEMU_LOG} 2>&1 &
+QEMU_PID=$!
+
+# QEMU announces the pty it attached the UART to on startup.
+for i in {1..50}; do
+  PTY=$(grep -o '/dev/pts/[0-9]*' ${QEMU_LOG} | head -n 1)
+  [ -n "${PTY}" ] && break
+  sleep 0.1
+done
+
+if [ -z "${PTY}" ]; then
+  echo -e "${KRED}ERROR: QEMU didn't open a pty${KNRM}" >&2
+  cat ${QEMU_LOG} >&2
+  cleanup_and_exit 1
+fi
+
+stty -F ${PTY} raw -echo min 0 time 1
+
+echo -e "${KBLU}Transmitting kernel to ${PTY}...${KNRM}"
+if ! ${TOP}/lib/ttywrite/target/debug/ttywrite -i ${TOP}/kern/build/kernel.bin ${PTY}; then
+  echo -e "${KRED}ERROR: transmission failed${KNRM}" >&2
+  cleanup_and_exit 1
+fi
+
+output=$(timeout 5 cat ${PTY})
+if [[ "${output}" != *"Welcome to the Rust shell!"* ]]; then
+  echo -e "${KRED}ERROR: kernel didn't start after chain-loading${KNRM}" >&2
+  echo "${output}" >&2
+  cleanup_and_exit 1
+fi
+
+echo -e "${KGRN}SUCCESS${KNRM}"
+cleanup_and_exit 0
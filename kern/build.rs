@@ -1,3 +1,51 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Where `make -C ../user` leaves the user programs.
+const USER_BUILD: &str = "../user/build";
+
+/// Appends an entry to the cpio archive `cpio` in the "new" ASCII format.
+fn push(cpio: &mut Vec<u8>, name: &str, mode: u32, contents: &[u8]) {
+    let fields = [0, mode, 0, 0, 1, 0, contents.len() as u32, 0, 0, 0, 0, name.len() as u32 + 1, 0];
+    cpio.extend_from_slice(b"070701");
+    for field in fields {
+        cpio.extend_from_slice(format!("{:08X}", field).as_bytes());
+    }
+    cpio.extend_from_slice(name.as_bytes());
+    cpio.push(0);
+    cpio.resize(cpio.len().next_multiple_of(4), 0);
+    cpio.extend_from_slice(contents);
+    cpio.resize(cpio.len().next_multiple_of(4), 0);
+}
+
+/// Packs the user programs into the initramfs the kernel embeds, as
+/// `bin/<name>`. Without any, the archive is empty.
+fn build_initramfs() {
+    println!("cargo:rerun-if-changed={}", USER_BUILD);
+
+    let mut programs: Vec<_> = fs::read_dir(USER_BUILD)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .collect();
+    programs.sort_by_key(|entry| entry.file_name());
+
+    let mut cpio = Vec::new();
+    for program in programs {
+        let contents = fs::read(program.path()).expect("can't read a user program");
+        let name = format!("bin/{}", program.file_name().to_string_lossy());
+        push(&mut cpio, &name, 0o100755, &contents);
+    }
+    push(&mut cpio, "TRAILER!!!", 0, &[]);
+
+    let out = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out).join("initramfs.cpio"), cpio).unwrap();
+}
+
 pub fn main() {
     println!("cargo:rerun-if-changed=.cargo/layout.ld");
+    build_initramfs();
 }
@@ -0,0 +1,149 @@
+mod bin;
+#[cfg(test)]
+mod bump;
+mod linked_list;
+mod util;
+
+#[cfg(test)]
+mod tests;
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::fmt;
+
+use crate::boot_info;
+use crate::mutex::IrqMutex;
+
+/// End of the memory available to the ARM cores with the firmware's default
+/// 64 MiB GPU split on a 1 GiB board. Used if the firmware doesn't say.
+const DEFAULT_MEMORY_END: usize = 0x3C00_0000;
+
+/// `LocalAlloc` is an analogous trait to the standard library's `GlobalAlloc`,
+/// but it takes `&mut self` in `alloc()` and `dealloc()`.
+pub trait LocalAlloc {
+    /// Allocates memory for `layout`. Returns a null pointer if the request
+    /// can't be satisfied. See `GlobalAlloc::alloc()`.
+    ///
+    /// # Safety
+    ///
+    /// `layout` must have a non-zero size.
+    unsafe fn alloc(&mut self, layout: Layout) -> *mut u8;
+
+    /// Deallocates the memory at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by a call to `alloc()` with `layout` on
+    /// this allocator and must not have been deallocated since.
+    unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout);
+
+    /// Returns this allocator's current statistics.
+    fn stats(&self) -> Stats;
+}
+
+/// Heap usage statistics.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Size of the heap in bytes.
+    pub total: usize,
+    /// Bytes requested by live allocations.
+    pub requested: usize,
+    /// Bytes taken up by live allocations, i.e., `requested` plus the space
+    /// lost to rounding up to the allocator's block sizes.
+    pub allocated: usize,
+    /// The highest `allocated` has been.
+    pub peak: usize,
+    /// Number of live allocations.
+    pub allocations: usize,
+    /// Bytes never handed out so far.
+    pub unused: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "total:       {:>10} bytes", self.total)?;
+        writeln!(f, "requested:   {:>10} bytes", self.requested)?;
+        writeln!(f, "allocated:   {:>10} bytes", self.allocated)?;
+        writeln!(f, "peak:        {:>10} bytes", self.peak)?;
+        writeln!(f, "unused:      {:>10} bytes", self.unused)?;
+        write!(f, "allocations: {:>10}", self.allocations)
+    }
+}
+
+/// The allocator in use by the kernel.
+type AllocatorImpl = bin::Allocator;
+
+/// Thread-safe (locking) wrapper around a particular memory allocator.
+pub struct Allocator(IrqMutex<Option<AllocatorImpl>>);
+
+impl Allocator {
+    /// Returns an uninitialized `Allocator`.
+    ///
+    /// The allocator must be initialized by calling `initialize()` before the
+    /// first memory allocation. Failure to do will result in panics.
+    pub const fn uninitialized() -> Self {
+        Allocator(IrqMutex::new(None))
+    }
+
+    /// Initializes the memory allocator with the memory after the kernel's
+    /// binary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system's memory map could not be retrieved.
+    ///
+    /// # Safety
+    ///
+    /// The memory map must be accurate and the memory in it unused. Must be
+    /// called at most once, before any allocation.
+    pub unsafe fn initialize(&self) {
+        let (start, end) = memory_map().expect("failed to find memory map");
+        *self.0.lock() = Some(AllocatorImpl::new(start, end));
+    }
+
+    /// Returns the heap's statistics, or `None` if the allocator hasn't been
+    /// initialized yet.
+    pub fn stats(&self) -> Option<Stats> {
+        self.0.lock().as_ref().map(|allocator| allocator.stats())
+    }
+}
+
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.0
+            .lock()
+            .as_mut()
+            .expect("allocator uninitialized")
+            .alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0
+            .lock()
+            .as_mut()
+            .expect("allocator uninitialized")
+            .dealloc(ptr, layout);
+    }
+}
+
+extern "C" {
+    static __text_end: u8;
+}
+
+/// Returns the (start address, end address) of the available free memory on
+/// the system: everything from the end of the kernel's binary to the end of
+/// the first memory region the firmware reports, or `None` if there is none.
+/// A device tree in that range is kept out of it.
+pub fn memory_map() -> Option<(usize, usize)> {
+    let binary_end = unsafe { &__text_end as *const u8 as usize };
+    let mut end = boot_info::memory().map_or(DEFAULT_MEMORY_END, |(_, end)| end);
+    if let Some(dtb) = boot_info::dtb_addr() {
+        if dtb > binary_end {
+            end = core::cmp::min(end, dtb);
+        }
+    }
+
+    match binary_end < end {
+        true => Some((binary_end, end)),
+        false => None,
+    }
+}
@@ -0,0 +1,142 @@
+use core::alloc::Layout;
+use core::cmp::max;
+use core::mem::size_of;
+use core::ptr;
+
+use crate::allocator::linked_list::LinkedList;
+use crate::allocator::util::*;
+use crate::allocator::{LocalAlloc, Stats};
+
+/// Size of the smallest block, which has to fit a free list's link.
+const MIN_BLOCK_SIZE: usize = size_of::<usize>();
+/// Number of size classes: blocks of `2^3` up to `2^(3 + NUM_BINS - 1)` bytes,
+/// i.e., anything a 64-bit address space can hold.
+const NUM_BINS: usize = usize::BITS as usize - MIN_BLOCK_SIZE.trailing_zeros() as usize;
+
+/// A simple allocator that allocates based on size classes.
+///
+/// bin 0 (2^3 bytes):  handles allocations in (0, 2^3]
+/// bin 1 (2^4 bytes):  handles allocations in (2^3, 2^4]
+/// ...
+/// bin k (2^(k + 3) bytes): handles allocations in (2^(k + 2), 2^(k + 3)]
+///
+/// A block's size is also its alignment, so any request whose alignment is at
+/// most its rounded-up size is served from a bin. Freed blocks go back to
+/// their bin for reuse; bins are refilled from the untouched rest of the heap.
+#[derive(Debug)]
+pub struct Allocator {
+    bins: [LinkedList; NUM_BINS],
+    current: usize,
+    end: usize,
+    stats: Stats,
+}
+
+impl Allocator {
+    /// Creates a new bin allocator that will allocate memory from the region
+    /// starting at address `start` and ending at address `end`.
+    pub fn new(start: usize, end: usize) -> Allocator {
+        let current = align_up(start, MIN_BLOCK_SIZE);
+        let end = max(align_down(end, MIN_BLOCK_SIZE), current);
+        Allocator {
+            bins: [LinkedList::new(); NUM_BINS],
+            current,
+            end,
+            stats: Stats { total: end - current, ..Stats::default() },
+        }
+    }
+
+    /// Returns the bin serving `layout`.
+    fn bin(layout: &Layout) -> usize {
+        let size = max(max(layout.size(), layout.align()), MIN_BLOCK_SIZE);
+        match size.checked_next_power_of_two() {
+            Some(size) => Allocator::bin_of_size(size),
+            None => NUM_BINS,
+        }
+    }
+
+    /// Returns the bin holding blocks of `size` bytes, a power of two.
+    fn bin_of_size(size: usize) -> usize {
+        (size.trailing_zeros() - MIN_BLOCK_SIZE.trailing_zeros()) as usize
+    }
+
+    /// Returns the size of the blocks in `bin`.
+    fn block_size(bin: usize) -> usize {
+        MIN_BLOCK_SIZE << bin
+    }
+
+    /// Carves a new block for `bin` out of the untouched memory. The memory
+    /// skipped to align the block is split into blocks for the smaller bins.
+    fn refill(&mut self, bin: usize) -> Option<*mut usize> {
+        let size = Allocator::block_size(bin);
+        let start = self.current.checked_add(size - 1)? & !(size - 1);
+        if start.checked_add(size)? > self.end {
+            return None;
+        }
+
+        while self.current < start {
+            // The largest block that's aligned at `current` and fits.
+            let align = 1 << self.current.trailing_zeros();
+            let block = min_power_of_two(align, start - self.current);
+            unsafe { self.bins[Allocator::bin_of_size(block)].push(self.current as *mut usize) };
+            self.current += block;
+        }
+
+        self.current = start + size;
+        Some(start as *mut usize)
+    }
+}
+
+/// Returns the smaller of `align`, a power of two, and the largest power of
+/// two not above `len`.
+fn min_power_of_two(align: usize, len: usize) -> usize {
+    let below = 1 << (usize::BITS - 1 - len.leading_zeros());
+    core::cmp::min(align, below)
+}
+
+impl LocalAlloc for Allocator {
+    /// Allocates memory. Returns a pointer meeting the size and alignment
+    /// properties of `layout.size()` and `layout.align()`.
+    ///
+    /// If this method returns an `Ok(addr)`, `addr` will be non-null address
+    /// pointing to a block of storage suitable for holding an instance of
+    /// `layout`. In particular, the block will be at least `layout.size()`
+    /// bytes large and will be aligned to `layout.align()`. The returned block
+    /// of storage may or may not have its contents initialized or zeroed.
+    ///
+    /// Returns null if there isn't enough memory left.
+    unsafe fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let bin = Allocator::bin(&layout);
+        if bin >= NUM_BINS {
+            return ptr::null_mut();
+        }
+
+        let block = match self.bins[bin].pop() {
+            Some(block) => block,
+            None => match self.refill(bin) {
+                Some(block) => block,
+                None => return ptr::null_mut(),
+            },
+        };
+
+        self.stats.requested += layout.size();
+        self.stats.allocated += Allocator::block_size(bin);
+        self.stats.peak = max(self.stats.peak, self.stats.allocated);
+        self.stats.allocations += 1;
+        block as *mut u8
+    }
+
+    /// Deallocates the memory referenced by `ptr`, returning its block to its
+    /// bin.
+    unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout) {
+        let bin = Allocator::bin(&layout);
+        self.bins[bin].push(ptr as *mut usize);
+
+        self.stats.requested -= layout.size();
+        self.stats.allocated -= Allocator::block_size(bin);
+        self.stats.allocations -= 1;
+    }
+
+    fn stats(&self) -> Stats {
+        Stats { unused: self.end - self.current, ..self.stats }
+    }
+}
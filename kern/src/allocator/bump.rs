@@ -0,0 +1,68 @@
+use core::alloc::Layout;
+use core::ptr;
+
+use crate::allocator::{LocalAlloc, Stats};
+
+/// A "bump" allocator: allocates memory by bumping a pointer; never frees.
+#[derive(Debug)]
+pub struct Allocator {
+    current: usize,
+    end: usize,
+    stats: Stats,
+}
+
+impl Allocator {
+    /// Creates a new bump allocator that will allocate memory from the region
+    /// starting at address `start` and ending at address `end`.
+    pub fn new(start: usize, end: usize) -> Allocator {
+        Allocator {
+            current: start,
+            end,
+            stats: Stats { total: end - start, ..Stats::default() },
+        }
+    }
+}
+
+impl LocalAlloc for Allocator {
+    /// Allocates memory. Returns a pointer meeting the size and alignment
+    /// properties of `layout.size()` and `layout.align()`.
+    ///
+    /// If this method returns an `Ok(addr)`, `addr` will be non-null address
+    /// pointing to a block of storage suitable for holding an instance of
+    /// `layout`. In particular, the block will be at least `layout.size()`
+    /// bytes large and will be aligned to `layout.align()`. The returned block
+    /// of storage may or may not have its contents initialized or zeroed.
+    ///
+    /// Returns null if there isn't enough memory left, including when
+    /// aligning would go past the end of the address space.
+    unsafe fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let align = layout.align();
+        let start = match self.current.checked_add(align - 1) {
+            Some(addr) => addr & !(align - 1),
+            None => return ptr::null_mut(),
+        };
+        let end = match start.checked_add(layout.size()) {
+            Some(end) if end <= self.end => end,
+            _ => return ptr::null_mut(),
+        };
+
+        // Padding is lost for good, so count it as allocated.
+        self.stats.requested += layout.size();
+        self.stats.allocated += end - self.current;
+        self.stats.peak = self.stats.allocated;
+        self.stats.allocations += 1;
+        self.current = end;
+        start as *mut u8
+    }
+
+    /// Deallocates the memory referenced by `ptr`. A bump allocator never
+    /// reuses memory, so this only updates the statistics.
+    unsafe fn dealloc(&mut self, _ptr: *mut u8, layout: Layout) {
+        self.stats.requested -= layout.size();
+        self.stats.allocations -= 1;
+    }
+
+    fn stats(&self) -> Stats {
+        Stats { unused: self.end - self.current, ..self.stats }
+    }
+}
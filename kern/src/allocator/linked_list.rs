@@ -0,0 +1,45 @@
+use core::ptr;
+
+/// An intrusive, singly linked list of free memory blocks. Each block stores
+/// the address of the next one in its first word, so blocks must be at least
+/// `size_of::<usize>()` bytes long and suitably aligned.
+#[derive(Debug, Copy, Clone)]
+pub struct LinkedList {
+    head: *mut usize,
+}
+
+unsafe impl Send for LinkedList {}
+
+impl LinkedList {
+    /// Returns a new, empty linked list.
+    pub const fn new() -> LinkedList {
+        LinkedList { head: ptr::null_mut() }
+    }
+
+    /// Returns `true` if the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.is_null()
+    }
+
+    /// Pushes the block at `item` to the front of the list.
+    ///
+    /// # Safety
+    ///
+    /// `item` must point to an unused, writable block that's not in any list.
+    pub unsafe fn push(&mut self, item: *mut usize) {
+        *item = self.head as usize;
+        self.head = item;
+    }
+
+    /// Removes and returns the block at the front of the list, if any.
+    pub fn pop(&mut self) -> Option<*mut usize> {
+        match self.is_empty() {
+            true => None,
+            false => {
+                let item = self.head;
+                self.head = unsafe { *item as *mut usize };
+                Some(item)
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use core::alloc::Layout;
+
+use super::util::{align_down, align_up};
+use super::{bin, bump, LocalAlloc};
+
+/// A heap region backed by host memory, aligned to 4 KiB.
+struct Region {
+    _memory: Vec<u8>,
+    start: usize,
+    end: usize,
+}
+
+impl Region {
+    fn new(size: usize) -> Region {
+        let memory = vec![0u8; size + 4096];
+        let start = align_up(memory.as_ptr() as usize, 4096);
+        Region { start, end: start + size, _memory: memory }
+    }
+}
+
+fn layout(size: usize, align: usize) -> Layout {
+    Layout::from_size_align(size, align).unwrap()
+}
+
+#[test]
+fn test_align_util() {
+    assert_eq!(align_down(0x1234, 0x1000), 0x1000);
+    assert_eq!(align_down(0x1000, 0x1000), 0x1000);
+    assert_eq!(align_up(0x1234, 0x1000), 0x2000);
+    assert_eq!(align_up(0x1000, 0x1000), 0x1000);
+    assert_eq!(align_up(0, 8), 0);
+    assert_eq!(align_up(1, 1), 1);
+}
+
+#[test]
+#[should_panic]
+fn test_align_util_rejects_non_power_of_two() {
+    align_up(0x1234, 3);
+}
+
+#[test]
+fn test_bump_alloc() {
+    let region = Region::new(4096);
+    let mut allocator = bump::Allocator::new(region.start, region.end);
+
+    unsafe {
+        let a = allocator.alloc(layout(1, 1)) as usize;
+        let b = allocator.alloc(layout(16, 16)) as usize;
+        let c = allocator.alloc(layout(3, 4)) as usize;
+        assert_eq!(a, region.start);
+        assert_eq!(b, region.start + 16);
+        assert_eq!(c, region.start + 32);
+
+        let stats = allocator.stats();
+        assert_eq!(stats.requested, 20);
+        assert_eq!(stats.allocated, 35);
+        assert_eq!(stats.allocations, 3);
+        assert_eq!(stats.unused, 4096 - 35);
+
+        allocator.dealloc(a as *mut u8, layout(1, 1));
+        assert_eq!(allocator.stats().allocations, 2);
+    }
+}
+
+#[test]
+fn test_bump_exhaustion() {
+    let region = Region::new(4096);
+    let mut allocator = bump::Allocator::new(region.start, region.end);
+
+    unsafe {
+        assert!(!allocator.alloc(layout(4000, 8)).is_null());
+        assert!(allocator.alloc(layout(128, 8)).is_null());
+        assert!(!allocator.alloc(layout(96, 8)).is_null());
+        assert!(allocator.alloc(layout(1, 1)).is_null());
+    }
+
+    // Memory near the top of the address space, which is never touched.
+    let mut allocator = bump::Allocator::new(usize::MAX - 0x1000, usize::MAX);
+    unsafe {
+        assert!(allocator.alloc(layout(8, 0x10000)).is_null());
+        assert!(!allocator.alloc(layout(8, 8)).is_null());
+    }
+}
+
+#[test]
+fn test_bin_alignment() {
+    let region = Region::new(1 << 16);
+    let mut allocator = bin::Allocator::new(region.start + 8, region.end);
+
+    unsafe {
+        for &(size, align) in &[(1, 1), (24, 8), (100, 64), (7, 1024), (4096, 4096), (3, 2)] {
+            let ptr = allocator.alloc(layout(size, align)) as usize;
+            assert_ne!(ptr, 0);
+            assert_eq!(ptr % align, 0, "{} bytes aligned to {}", size, align);
+            assert!(ptr >= region.start + 8 && ptr + size <= region.end);
+        }
+    }
+}
+
+#[test]
+fn test_bin_reuses_freed_blocks() {
+    let region = Region::new(1 << 16);
+    let mut allocator = bin::Allocator::new(region.start, region.end);
+
+    unsafe {
+        let a = allocator.alloc(layout(100, 8));
+        let b = allocator.alloc(layout(100, 8));
+        assert_ne!(a, b);
+
+        allocator.dealloc(a, layout(100, 8));
+        assert_eq!(allocator.alloc(layout(128, 8)), a);
+        allocator.dealloc(b, layout(100, 8));
+        assert_eq!(allocator.alloc(layout(65, 1)), b);
+    }
+}
+
+#[test]
+fn test_bin_reuses_alignment_padding() {
+    let region = Region::new(1 << 16);
+    let mut allocator = bin::Allocator::new(region.start + 8, region.end);
+
+    unsafe {
+        // Skips `start + 8` to `start + 4096`; the gap goes to smaller bins.
+        let page = allocator.alloc(layout(4096, 4096)) as usize;
+        assert_eq!(page, region.start + 4096);
+        assert_eq!(allocator.alloc(layout(8, 8)) as usize, region.start + 8);
+        assert_eq!(allocator.alloc(layout(2048, 8)) as usize, region.start + 2048);
+        assert_eq!(allocator.stats().unused, (1 << 16) - 8192);
+    }
+}
+
+#[test]
+fn test_bin_exhaustion() {
+    let region = Region::new(4096);
+    let mut allocator = bin::Allocator::new(region.start, region.end);
+
+    unsafe {
+        assert!(allocator.alloc(layout(4097, 8)).is_null());
+        let all = allocator.alloc(layout(4096, 8));
+        assert!(!all.is_null());
+        assert!(allocator.alloc(layout(1, 1)).is_null());
+
+        allocator.dealloc(all, layout(4096, 8));
+        assert_eq!(allocator.alloc(layout(4096, 8)), all);
+    }
+}
+
+#[test]
+fn test_bin_stats() {
+    let region = Region::new(1 << 16);
+    let mut allocator = bin::Allocator::new(region.start, region.end);
+
+    unsafe {
+        let a = allocator.alloc(layout(100, 8));
+        let b = allocator.alloc(layout(10, 8));
+        let stats = allocator.stats();
+        assert_eq!(stats.total, 1 << 16);
+        assert_eq!(stats.requested, 110);
+        assert_eq!(stats.allocated, 128 + 16);
+        assert_eq!(stats.allocations, 2);
+
+        allocator.dealloc(a, layout(100, 8));
+        allocator.dealloc(b, layout(10, 8));
+        let stats = allocator.stats();
+        assert_eq!((stats.requested, stats.allocated, stats.allocations), (0, 0, 0));
+        assert_eq!(stats.peak, 144);
+    }
+}
+
+#[test]
+fn test_bin_vec_of_boxes() {
+    let region = Region::new(1 << 20);
+    let mut allocator = bin::Allocator::new(region.start, region.end);
+
+    unsafe {
+        let mut blocks = vec![];
+        for i in 1..200usize {
+            let layout = layout(i * 7, 8);
+            let ptr = allocator.alloc(layout);
+            assert!(!ptr.is_null());
+            core::ptr::write_bytes(ptr, i as u8, layout.size());
+            blocks.push((ptr, layout));
+        }
+
+        for (i, &(ptr, layout)) in blocks.iter().enumerate() {
+            let data = core::slice::from_raw_parts(ptr, layout.size());
+            assert!(data.iter().all(|&b| b == (i + 1) as u8), "block {} was overwritten", i);
+        }
+
+        for &(ptr, layout) in blocks.iter().rev() {
+            allocator.dealloc(ptr, layout);
+        }
+        assert_eq!(allocator.stats().allocations, 0);
+    }
+}
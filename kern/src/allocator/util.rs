@@ -0,0 +1,23 @@
+/// Align `addr` downwards to the nearest multiple of `align`.
+///
+/// The returned usize is always <= `addr.`
+///
+/// # Panics
+///
+/// Panics if `align` is not a power of 2.
+pub fn align_down(addr: usize, align: usize) -> usize {
+    assert!(align.is_power_of_two(), "alignment must be a power of 2");
+    addr & !(align - 1)
+}
+
+/// Align `addr` upwards to the nearest multiple of `align`.
+///
+/// The returned `usize` is always >= `addr.`
+///
+/// # Panics
+///
+/// Panics if `align` is not a power of 2 or aligning up overflows the address.
+pub fn align_up(addr: usize, align: usize) -> usize {
+    assert!(align.is_power_of_two(), "alignment must be a power of 2");
+    addr.checked_add(align - 1).expect("aligning up overflows") & !(align - 1)
+}
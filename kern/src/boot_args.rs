@@ -0,0 +1,235 @@
+use core::fmt;
+
+use crate::boot_info;
+use crate::console::kprintln;
+
+#[cfg(test)]
+mod tests;
+
+/// How much the kernel logs, from least to most.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn parse(s: &str) -> Option<LogLevel> {
+        match s {
+            "error" => Some(LogLevel::Error),
+            "warn" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            "trace" => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        })
+    }
+}
+
+/// What to do after a panic has been reported.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OnPanic {
+    /// Stop the core, leaving the report on the console.
+    Halt,
+    /// Reset the board through the watchdog.
+    Reboot,
+}
+
+impl fmt::Display for OnPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OnPanic::Halt => "halt",
+            OnPanic::Reboot => "reboot",
+        })
+    }
+}
+
+/// Which timer drives the kernel's tick.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimerSource {
+    /// The system timer's compare channel 1.
+    System,
+    /// The ARM generic timer's EL1 physical timer.
+    Generic,
+}
+
+impl fmt::Display for TimerSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TimerSource::System => "system",
+            TimerSource::Generic => "generic",
+        })
+    }
+}
+
+/// The kernel's options, read from the command line the firmware passes
+/// (`cmdline.txt` on the SD card, `-append` in QEMU). Options are `key=value`
+/// words; values with spaces can be quoted, e.g. `prompt="dinos> "`. Anything
+/// else on the command line is ignored.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BootArgs {
+    /// `log=error|warn|info|debug|trace`: how much to log.
+    pub log_level: LogLevel,
+    /// `prompt=...`: the shell's prompt.
+    pub prompt: &'static str,
+    /// `sched=on|off`: whether to start the scheduler.
+    pub scheduler: bool,
+    /// `baud=<rate>`: the console's baud rate, if it should change after boot.
+    pub baud_rate: Option<u32>,
+    /// `panic=halt|reboot`: what to do after a panic.
+    pub on_panic: OnPanic,
+    /// `timer=system|generic`: which timer drives the tick.
+    pub timer: TimerSource,
+    /// `demo=<n>`: how many demo processes to start alongside the shell. A
+    /// user demo process joins them if there are any.
+    pub demos: usize,
+}
+
+impl BootArgs {
+    /// The options used when the command line doesn't set them.
+    pub const DEFAULT: BootArgs = BootArgs {
+        log_level: LogLevel::Info,
+        prompt: "> ",
+        scheduler: true,
+        baud_rate: None,
+        on_panic: OnPanic::Halt,
+        timer: TimerSource::System,
+        demos: 0,
+    };
+
+    /// Parses the options in `cmdline`. Options with invalid values are
+    /// reported on the console and left at their defaults.
+    pub fn parse(cmdline: &'static str) -> BootArgs {
+        let mut args = BootArgs::DEFAULT;
+        for (key, value) in words(cmdline).filter_map(|word| word.split_once('=')) {
+            if let Err(e) = args.set(key, unquote(value)) {
+                kprintln!("boot args: ignoring {}={}: {}", key, value, e);
+            }
+        }
+
+        args
+    }
+
+    /// Sets the option `key` to `value`. Unknown options are ignored.
+    fn set(&mut self, key: &str, value: &'static str) -> Result<(), &'static str> {
+        match key {
+            "log" => self.log_level = LogLevel::parse(value).ok_or("expected error, warn, info, debug or trace")?,
+            "prompt" => self.prompt = value,
+            "sched" => {
+                self.scheduler = match value {
+                    "on" | "1" | "yes" | "true" => true,
+                    "off" | "0" | "no" | "false" => false,
+                    _ => return Err("expected on or off"),
+                }
+            }
+            "baud" => match value.parse() {
+                Ok(0) | Err(_) => return Err("expected a positive baud rate"),
+                Ok(rate) => self.baud_rate = Some(rate),
+            },
+            "panic" => {
+                self.on_panic = match value {
+                    "halt" => OnPanic::Halt,
+                    "reboot" => OnPanic::Reboot,
+                    _ => return Err("expected halt or reboot"),
+                }
+            }
+            "timer" => {
+                self.timer = match value {
+                    "system" => TimerSource::System,
+                    "generic" => TimerSource::Generic,
+                    _ => return Err("expected system or generic"),
+                }
+            }
+            "demo" => self.demos = value.parse().map_err(|_| "expected a number of processes")?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for BootArgs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "log={} prompt=\"{}\" sched={} panic={} timer={}", self.log_level, self.prompt,
+               if self.scheduler { "on" } else { "off" }, self.on_panic, self.timer)?;
+        if self.demos > 0 {
+            write!(f, " demo={}", self.demos)?;
+        }
+        match self.baud_rate {
+            Some(rate) => write!(f, " baud={}", rate),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Splits `cmdline` at spaces outside of double quotes.
+fn words(cmdline: &'static str) -> impl Iterator<Item = &'static str> {
+    let mut rest = cmdline;
+    core::iter::from_fn(move || {
+        rest = rest.trim_start_matches(' ');
+        if rest.is_empty() {
+            return None;
+        }
+
+        let mut quoted = false;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                quoted ^= c == '"';
+                c == ' ' && !quoted
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        let (word, remaining) = rest.split_at(end);
+        rest = remaining;
+        Some(word)
+    })
+}
+
+/// Strips a pair of surrounding double quotes from `value`.
+fn unquote(value: &'static str) -> &'static str {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(unquoted) => unquoted,
+        None => value,
+    }
+}
+
+/// The options the kernel booted with. Only `initialize` writes them, before
+/// any other core or process runs, so reading them needs no lock.
+static mut BOOT_ARGS: BootArgs = BootArgs::DEFAULT;
+
+/// Reads the options from the firmware's command line, if it passed one.
+///
+/// # Safety
+///
+/// Must be called at most once, on the boot core, before the other cores or
+/// the scheduler start.
+pub unsafe fn initialize() {
+    if let Some(cmdline) = boot_info::cmdline() {
+        *core::ptr::addr_of_mut!(BOOT_ARGS) = BootArgs::parse(cmdline);
+    }
+}
+
+/// Returns the options the kernel booted with.
+pub fn get() -> BootArgs {
+    unsafe { *core::ptr::addr_of!(BOOT_ARGS) }
+}
+
+/// Returns `true` if messages at `level` should be logged.
+pub fn log_enabled(level: LogLevel) -> bool {
+    level <= get().log_level
+}
@@ -0,0 +1,58 @@
+use super::*;
+
+#[test]
+fn test_defaults() {
+    assert_eq!(BootArgs::parse(""), BootArgs::DEFAULT);
+    assert_eq!(BootArgs::parse("console=ttyS0,115200 root=/dev/mmcblk0p2 rootwait"), BootArgs::DEFAULT);
+}
+
+#[test]
+fn test_options() {
+    let args = BootArgs::parse("coherent_pool=1M log=debug  sched=off baud=921600 prompt=$ panic=reboot timer=generic demo=3 rootwait");
+    assert_eq!(args, BootArgs {
+        log_level: LogLevel::Debug,
+        prompt: "$",
+        scheduler: false,
+        baud_rate: Some(921600),
+        on_panic: OnPanic::Reboot,
+        timer: TimerSource::Generic,
+        demos: 3,
+    });
+}
+
+#[test]
+fn test_quoted_values() {
+    let args = BootArgs::parse("prompt=\"dinos (debug)> \" log=trace");
+    assert_eq!(args.prompt, "dinos (debug)> ");
+    assert_eq!(args.log_level, LogLevel::Trace);
+
+    assert_eq!(BootArgs::parse("prompt=\"\"").prompt, "");
+    assert_eq!(BootArgs::parse("prompt=a\"b").prompt, "a\"b");
+}
+
+#[test]
+fn test_invalid_values_keep_defaults() {
+    let args = BootArgs::parse("log=loud sched=maybe baud=0 baud=fast panic=explode timer=fast demo=-1");
+    assert_eq!(args, BootArgs::DEFAULT);
+
+    // Later options win, valid or not.
+    let args = BootArgs::parse("log=warn log=nope sched=0 sched=1");
+    assert_eq!(args.log_level, LogLevel::Warn);
+    assert!(args.scheduler);
+}
+
+#[test]
+fn test_display_round_trips() {
+    let args = BootArgs::parse("log=error prompt=\"kern> \" sched=no panic=reboot baud=230400 demo=2");
+    let shown = format!("{}", args);
+    assert_eq!(shown, "log=error prompt=\"kern> \" sched=off panic=reboot timer=system demo=2 baud=230400");
+    assert_eq!(BootArgs::parse(Box::leak(shown.into_boxed_str())), args);
+}
+
+#[test]
+fn test_log_levels() {
+    assert!(LogLevel::Error < LogLevel::Warn);
+    assert!(LogLevel::Debug < LogLevel::Trace);
+    assert!(log_enabled(LogLevel::Info));
+    assert!(!log_enabled(LogLevel::Debug));
+}
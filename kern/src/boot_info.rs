@@ -0,0 +1,67 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use pi::atags::{self, Atag};
+use pi::fdt::{self, Fdt};
+
+/// The address of the device tree the firmware passed in `x0`, or zero.
+static DTB_ADDR: AtomicUsize = AtomicUsize::new(0);
+
+/// Records the device tree address the firmware passed in `x0`.
+pub fn set_dtb(addr: usize) {
+    DTB_ADDR.store(addr, Ordering::Relaxed);
+}
+
+/// Returns the address of the device tree the firmware passed, if it passed
+/// a valid one.
+pub fn dtb_addr() -> Option<usize> {
+    dtb().map(|_| DTB_ADDR.load(Ordering::Relaxed))
+}
+
+/// Returns the device tree the firmware passed, if it passed a valid one.
+fn dtb() -> Option<Fdt> {
+    unsafe { Fdt::from_ptr(DTB_ADDR.load(Ordering::Relaxed) as *const u8) }
+}
+
+/// An iterator over the boot information from the firmware.
+pub enum Entries {
+    Atags(atags::Atags),
+    Fdt(fdt::Atags),
+    None,
+}
+
+impl Iterator for Entries {
+    type Item = Atag;
+
+    fn next(&mut self) -> Option<Atag> {
+        match self {
+            Entries::Atags(atags) => atags.next(),
+            Entries::Fdt(atags) => atags.next(),
+            Entries::None => None,
+        }
+    }
+}
+
+/// Returns the boot information from the firmware: its ATAGS if it left any,
+/// otherwise what its device tree amounts to.
+pub fn entries() -> Entries {
+    if atags::Atags::get().next().is_some() {
+        return Entries::Atags(atags::Atags::get());
+    }
+
+    match dtb() {
+        Some(fdt) => Entries::Fdt(fdt.atags()),
+        None => Entries::None,
+    }
+}
+
+/// Returns the (start, end) addresses of the first memory region the firmware
+/// reports, if any.
+pub fn memory() -> Option<(usize, usize)> {
+    let mem = entries().find_map(Atag::mem)?;
+    Some((mem.start as usize, mem.start as usize + mem.size as usize))
+}
+
+/// Returns the kernel command line, if the firmware passed one.
+pub fn cmdline() -> Option<&'static str> {
+    entries().find_map(Atag::cmd)
+}
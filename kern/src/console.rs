@@ -2,7 +2,7 @@ use core::fmt;
 use pi::uart::MiniUart;
 use shim::io;
 
-use crate::mutex::Mutex;
+use crate::mutex::IrqMutex;
 
 /// A global singleton allowing read/write access to the console.
 pub struct Console {
@@ -34,10 +34,26 @@ impl Console {
         self.inner().read_byte()
     }
 
+    /// Returns `true` if there's a byte to read.
+    pub fn has_byte(&mut self) -> bool {
+        self.inner().has_byte()
+    }
+
     /// Writes the byte `byte` to the UART device.
     pub fn write_byte(&mut self, byte: u8) {
         self.inner().write_byte(byte);
     }
+
+    /// Returns the UART device's current baud rate.
+    pub fn baud_rate(&mut self) -> u32 {
+        self.inner().baud_rate()
+    }
+
+    /// Switches the UART device to the baud rate `baud` once pending output
+    /// has been sent. See [`MiniUart::set_baud_rate()`].
+    pub fn set_baud_rate(&mut self, baud: u32) -> io::Result<()> {
+        self.inner().set_baud_rate(baud)
+    }
 }
 
 impl io::Read for Console {
@@ -67,7 +83,7 @@ impl io::Write for Console {
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        Ok(())
+        io::Write::flush(self.inner())
     }
 }
 
@@ -79,8 +95,9 @@ impl fmt::Write for Console {
     }
 }
 
-/// Global `Console` singleton.
-pub static CONSOLE: Mutex<Console> = Mutex::new(Console::new());
+/// Global `Console` singleton. Holding it masks IRQs, so a process can't be
+/// switched out in the middle of a line.
+pub static CONSOLE: IrqMutex<Console> = IrqMutex::new(Console::new());
 
 /// Internal function called by the `kprint[ln]!` macros.
 #[doc(hidden)]
@@ -108,4 +125,12 @@ pub macro kprintln {
 /// Like `print!`, but for kernel-space.
 pub macro kprint($($arg:tt)*) {
     _print(format_args!($($arg)*))
-}
\ No newline at end of file
+}
+/// Like `kprintln!`, but tagged with a `boot_args::LogLevel` and only printed
+/// if the `log` boot argument enables that level.
+pub macro klog($level:expr, $($arg:tt)*) {
+    if crate::boot_args::log_enabled($level) {
+        kprint!("[{}] ", $level);
+        kprintln!($($arg)*)
+    }
+}
@@ -0,0 +1,257 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::BitOr;
+
+use crate::vm::{Permission, PAGE_SIZE, USER_BASE, USER_END};
+
+#[cfg(test)]
+mod tests;
+
+/// The size of an ELF64 file header.
+const HEADER_SIZE: usize = 64;
+
+/// The size of an ELF64 program header.
+const PHDR_SIZE: usize = 56;
+
+const MAGIC: &[u8; 4] = b"\x7fELF";
+const CLASS_64: u8 = 2;
+const DATA_LSB: u8 = 1;
+const VERSION_CURRENT: u8 = 1;
+const TYPE_EXEC: u16 = 2;
+const MACHINE_AARCH64: u16 = 183;
+
+/// The program header type of a segment to load into memory.
+const PT_LOAD: u32 = 1;
+
+/// Why a file can't be run.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The file ends before a header or segment it describes.
+    Truncated,
+    /// The file isn't an ELF file.
+    BadMagic,
+    /// The file isn't a 64-bit, little-endian AArch64 executable.
+    Unsupported,
+    /// A segment doesn't fit in the user part of the address space, or would
+    /// make a page both writable and executable.
+    BadSegment,
+    /// The entry point isn't in an executable segment.
+    BadEntry,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Error::Truncated => "truncated ELF file",
+            Error::BadMagic => "not an ELF file",
+            Error::Unsupported => "not an AArch64 ELF64 executable",
+            Error::BadSegment => "segment outside user memory or writable and executable",
+            Error::BadEntry => "entry point outside the code",
+        })
+    }
+}
+
+/// What a segment may be used for, as its program header's `p_flags`. Every
+/// segment is readable, whether or not it says so.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Flags(u32);
+
+impl Flags {
+    pub const EXECUTE: Flags = Flags(1 << 0);
+    pub const WRITE: Flags = Flags(1 << 1);
+
+    /// Returns `true` if all of `other`'s flags are set.
+    pub fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the permission a user page with these flags is mapped with.
+    pub fn permission(self) -> Permission {
+        if self.contains(Flags::WRITE) {
+            Permission::UserRw
+        } else if self.contains(Flags::EXECUTE) {
+            Permission::UserRx
+        } else {
+            Permission::UserRo
+        }
+    }
+
+    /// Returns `true` if a page with these flags would be writable and
+    /// executable, which isn't allowed.
+    fn is_wx(self) -> bool {
+        self.contains(Flags::WRITE | Flags::EXECUTE)
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
+
+    fn bitor(self, other: Flags) -> Flags {
+        Flags(self.0 | other.0)
+    }
+}
+
+/// A `PT_LOAD` segment: `mem_size` bytes at `vaddr`, starting with `data`
+/// and zero after it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Segment<'a> {
+    pub vaddr: usize,
+    pub mem_size: usize,
+    pub data: &'a [u8],
+    pub flags: Flags,
+}
+
+impl Segment<'_> {
+    /// Returns the addresses of the pages the segment lies in.
+    pub fn pages(&self) -> impl Iterator<Item = usize> {
+        let start = self.vaddr & !(PAGE_SIZE - 1);
+        (start..self.vaddr + self.mem_size).step_by(PAGE_SIZE)
+    }
+
+    /// Returns `true` if the segment and `other` share a page.
+    fn shares_page(&self, other: &Segment) -> bool {
+        let first = |s: &Segment| s.vaddr / PAGE_SIZE;
+        let last = |s: &Segment| (s.vaddr + s.mem_size - 1) / PAGE_SIZE;
+        first(self) <= last(other) && first(other) <= last(self)
+    }
+}
+
+/// An ELF64 executable for AArch64 whose segments all fit in the user part of
+/// the address space.
+#[derive(Debug)]
+pub struct Elf<'a> {
+    data: &'a [u8],
+    entry: usize,
+    phoff: usize,
+    phnum: usize,
+}
+
+impl<'a> Elf<'a> {
+    /// Parses and checks the ELF file in `data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if `data` isn't a well-formed 64-bit, little-endian
+    /// AArch64 executable, if a segment lies outside `USER_BASE..USER_END` or
+    /// would make a page writable and executable, or if the entry point isn't
+    /// in an executable segment.
+    pub fn parse(data: &'a [u8]) -> Result<Elf<'a>, Error> {
+        if data.len() < 4 || &data[..4] != MAGIC {
+            return Err(Error::BadMagic);
+        } else if data.len() < HEADER_SIZE {
+            return Err(Error::Truncated);
+        }
+
+        let ident_ok = data[4] == CLASS_64 && data[5] == DATA_LSB && data[6] == VERSION_CURRENT;
+        if !ident_ok
+            || read_u16(data, 16) != TYPE_EXEC
+            || read_u16(data, 18) != MACHINE_AARCH64
+            || read_u32(data, 20) != VERSION_CURRENT as u32
+        {
+            return Err(Error::Unsupported);
+        }
+
+        let phnum = read_u16(data, 56) as usize;
+        if phnum > 0 && read_u16(data, 54) as usize != PHDR_SIZE {
+            return Err(Error::Unsupported);
+        }
+
+        let elf = Elf {
+            data,
+            entry: read_u64(data, 24) as usize,
+            phoff: read_u64(data, 32) as usize,
+            phnum,
+        };
+        let phdrs_end = elf.phoff.checked_add(phnum * PHDR_SIZE);
+        if phdrs_end.is_none_or(|end| end > data.len()) {
+            return Err(Error::Truncated);
+        }
+
+        let segments = elf.load_headers()
+            .map(|phdr| elf.segment(phdr))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (i, segment) in segments.iter().enumerate().filter(|(_, s)| s.mem_size > 0) {
+            let end = segment.vaddr.checked_add(segment.mem_size);
+            if segment.vaddr < USER_BASE || end.is_none_or(|end| end > USER_END) {
+                return Err(Error::BadSegment);
+            }
+
+            // A page gets the flags of every segment in it.
+            let flags = segments[..i].iter()
+                .filter(|other| other.mem_size > 0 && segment.shares_page(other))
+                .fold(segment.flags, |flags, other| flags | other.flags);
+            if flags.is_wx() {
+                return Err(Error::BadSegment);
+            }
+        }
+
+        let in_code = |s: &Segment| {
+            s.flags.contains(Flags::EXECUTE) && (s.vaddr..s.vaddr + s.mem_size).contains(&elf.entry)
+        };
+        if !segments.iter().any(in_code) {
+            return Err(Error::BadEntry);
+        }
+
+        Ok(elf)
+    }
+
+    /// Returns the address execution starts at.
+    pub fn entry(&self) -> usize {
+        self.entry
+    }
+
+    /// Returns the `PT_LOAD` segments that take up memory, in the order of
+    /// their program headers.
+    pub fn segments(&self) -> impl Iterator<Item = Segment<'a>> + '_ {
+        // `parse` has checked every segment.
+        self.load_headers()
+            .filter_map(move |phdr| self.segment(phdr).ok())
+            .filter(|segment| segment.mem_size > 0)
+    }
+
+    /// Returns the offsets of the `PT_LOAD` program headers.
+    fn load_headers(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.phnum)
+            .map(move |i| self.phoff + i * PHDR_SIZE)
+            .filter(move |&phdr| read_u32(self.data, phdr) == PT_LOAD)
+    }
+
+    /// Reads the segment the program header at `phdr` describes.
+    fn segment(&self, phdr: usize) -> Result<Segment<'a>, Error> {
+        let offset = read_u64(self.data, phdr + 8) as usize;
+        let file_size = read_u64(self.data, phdr + 32) as usize;
+        let mem_size = read_u64(self.data, phdr + 40) as usize;
+        if file_size > mem_size {
+            return Err(Error::BadSegment);
+        }
+
+        let data = offset.checked_add(file_size)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(Error::Truncated)?;
+        Ok(Segment {
+            vaddr: read_u64(self.data, phdr + 16) as usize,
+            mem_size,
+            data,
+            flags: Flags(read_u32(self.data, phdr + 4)),
+        })
+    }
+}
+
+/// Reads the little-endian `u16` at `offset` in `data`.
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Reads the little-endian `u32` at `offset` in `data`.
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+/// Reads the little-endian `u64` at `offset` in `data`.
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
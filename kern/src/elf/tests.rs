@@ -0,0 +1,108 @@
+use alloc::vec::Vec;
+
+use super::*;
+
+const R: u32 = 1 << 2;
+const RW: u32 = R | (1 << 1);
+const RX: u32 = R | (1 << 0);
+
+/// A program header to build a test file from: its type, flags, address,
+/// number of bytes in the file and size in memory.
+type Phdr = (u32, u32, usize, usize, usize);
+
+/// Builds an AArch64 executable with `phdrs`. Each segment's bytes in the file
+/// are its index plus one, repeated.
+fn build(entry: usize, phdrs: &[Phdr]) -> Vec<u8> {
+    let mut file = Vec::new();
+    file.extend_from_slice(b"\x7fELF");
+    file.extend_from_slice(&[2, 1, 1, 0]);
+    file.resize(16, 0);
+    file.extend_from_slice(&2u16.to_le_bytes());
+    file.extend_from_slice(&183u16.to_le_bytes());
+    file.extend_from_slice(&1u32.to_le_bytes());
+    file.extend_from_slice(&(entry as u64).to_le_bytes());
+    file.extend_from_slice(&64u64.to_le_bytes());
+    file.extend_from_slice(&0u64.to_le_bytes());
+    file.extend_from_slice(&[0; 4]);
+    file.extend_from_slice(&64u16.to_le_bytes());
+    file.extend_from_slice(&56u16.to_le_bytes());
+    file.extend_from_slice(&(phdrs.len() as u16).to_le_bytes());
+    file.extend_from_slice(&[0; 6]);
+    assert_eq!(file.len(), 64);
+
+    let mut offset = 64 + phdrs.len() * 56;
+    for &(kind, flags, vaddr, file_size, mem_size) in phdrs {
+        file.extend_from_slice(&kind.to_le_bytes());
+        file.extend_from_slice(&flags.to_le_bytes());
+        for field in [offset, vaddr, vaddr, file_size, mem_size, PAGE_SIZE] {
+            file.extend_from_slice(&(field as u64).to_le_bytes());
+        }
+        offset += file_size;
+    }
+
+    for (i, &(_, _, _, file_size, _)) in phdrs.iter().enumerate() {
+        file.resize(file.len() + file_size, i as u8 + 1);
+    }
+    file
+}
+
+#[test]
+fn test_parse() {
+    let file = build(USER_BASE + 0x10, &[
+        (PT_LOAD, RX, USER_BASE, 0x100, 0x100),
+        (6, R, 0, 0x38, 0x38),
+        (PT_LOAD, R, USER_BASE + PAGE_SIZE, 0x20, 0x20),
+        (PT_LOAD, RW, USER_BASE + 2 * PAGE_SIZE + 8, 0x10, 0x2_0000),
+    ]);
+    let elf = Elf::parse(&file).unwrap();
+    assert_eq!(elf.entry(), USER_BASE + 0x10);
+
+    let segments: Vec<_> = elf.segments().collect();
+    assert_eq!(segments.len(), 3);
+    assert_eq!(segments[0].vaddr, USER_BASE);
+    assert_eq!(segments[0].data, &[1; 0x100][..]);
+    assert_eq!(segments[0].flags.permission(), Permission::UserRx);
+    assert_eq!(segments[1].data, &[3; 0x20][..]);
+    assert_eq!(segments[1].flags.permission(), Permission::UserRo);
+    assert_eq!(segments[2].mem_size, 0x2_0000);
+    assert_eq!(segments[2].flags.permission(), Permission::UserRw);
+
+    let pages: Vec<_> = segments[2].pages().collect();
+    let base = USER_BASE + 2 * PAGE_SIZE;
+    assert_eq!(pages, [base, base + PAGE_SIZE, base + 2 * PAGE_SIZE]);
+}
+
+#[test]
+fn test_bad_headers() {
+    let file = build(USER_BASE, &[(PT_LOAD, RX, USER_BASE, 4, 4)]);
+    assert!(Elf::parse(&file).is_ok());
+    assert_eq!(Elf::parse(b"\x7fEL").unwrap_err(), Error::BadMagic);
+    assert_eq!(Elf::parse(b"#!/bin/sh\n").unwrap_err(), Error::BadMagic);
+    assert_eq!(Elf::parse(&file[..40]).unwrap_err(), Error::Truncated);
+    assert_eq!(Elf::parse(&file[..100]).unwrap_err(), Error::Truncated);
+    assert_eq!(Elf::parse(&file[..file.len() - 1]).unwrap_err(), Error::Truncated);
+
+    for (offset, value) in [(4, 1), (5, 2), (16, 3), (18, 62), (54, 64)] {
+        let mut file = file.clone();
+        file[offset] = value;
+        assert_eq!(Elf::parse(&file).unwrap_err(), Error::Unsupported, "byte {}", offset);
+    }
+}
+
+#[test]
+fn test_bad_segments() {
+    let bad = |entry, phdrs: &[Phdr]| Elf::parse(&build(entry, phdrs)).unwrap_err();
+
+    assert_eq!(bad(USER_BASE, &[(PT_LOAD, RX, USER_BASE - PAGE_SIZE, 4, 4)]), Error::BadSegment);
+    assert_eq!(bad(USER_BASE, &[(PT_LOAD, RX, USER_END - 2, 4, 4)]), Error::BadSegment);
+    assert_eq!(bad(USER_BASE, &[(PT_LOAD, RX, USER_BASE, 8, 4)]), Error::BadSegment);
+    assert_eq!(bad(USER_BASE, &[(PT_LOAD, RX | RW, USER_BASE, 4, 4)]), Error::BadSegment);
+    assert_eq!(bad(USER_BASE, &[
+        (PT_LOAD, RX, USER_BASE, 4, 4),
+        (PT_LOAD, RW, USER_BASE + 0x100, 4, 4),
+    ]), Error::BadSegment);
+
+    assert_eq!(bad(USER_BASE + 4, &[(PT_LOAD, RX, USER_BASE, 4, 4)]), Error::BadEntry);
+    assert_eq!(bad(USER_BASE, &[(PT_LOAD, RW, USER_BASE, 4, 4)]), Error::BadEntry);
+    assert_eq!(bad(USER_BASE, &[]), Error::BadEntry);
+}
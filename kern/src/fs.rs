@@ -0,0 +1,70 @@
+use kernel_api::{OsError, OsResult};
+
+use crate::console::CONSOLE;
+
+#[cfg(test)]
+mod tests;
+
+/// A file a process can open. So far, there are only devices.
+#[derive(Debug)]
+pub enum File {
+    /// `/dev/console`: reads from and writes to the console.
+    Console,
+    /// `/dev/null`: reads nothing and discards writes.
+    Null,
+}
+
+impl File {
+    /// Opens the file at `path`.
+    pub fn open(path: &str) -> OsResult<File> {
+        match path {
+            "/dev/console" => Ok(File::Console),
+            "/dev/null" => Ok(File::Null),
+            _ => Err(OsError::NoEntry),
+        }
+    }
+
+    /// Returns `true` if `read` has something to return, even if it's just
+    /// the end of the file.
+    pub fn can_read(&self) -> bool {
+        match self {
+            File::Console => CONSOLE.lock().has_byte(),
+            File::Null => true,
+        }
+    }
+
+    /// Reads what's available, up to `buf.len()` bytes, into `buf` without
+    /// waiting. Returns the number of bytes read.
+    pub fn read(&mut self, buf: &mut [u8]) -> OsResult<usize> {
+        match self {
+            File::Console => {
+                let mut console = CONSOLE.lock();
+                let mut count = 0;
+                while count < buf.len() && console.has_byte() {
+                    buf[count] = console.read_byte();
+                    count += 1;
+                }
+                Ok(count)
+            }
+            File::Null => Ok(0),
+        }
+    }
+
+    /// Writes `buf`. Returns the number of bytes written.
+    pub fn write(&mut self, buf: &[u8]) -> OsResult<usize> {
+        match self {
+            File::Console => {
+                let mut console = CONSOLE.lock();
+                for &byte in buf {
+                    // Like `kprint!`, start new lines at the left margin.
+                    if byte == b'\n' {
+                        console.write_byte(b'\r');
+                    }
+                    console.write_byte(byte);
+                }
+                Ok(buf.len())
+            }
+            File::Null => Ok(buf.len()),
+        }
+    }
+}
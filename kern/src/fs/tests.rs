@@ -0,0 +1,19 @@
+use super::*;
+
+#[test]
+fn test_open() {
+    assert!(matches!(File::open("/dev/console"), Ok(File::Console)));
+    assert!(matches!(File::open("/dev/null"), Ok(File::Null)));
+    assert!(matches!(File::open("/dev/nul"), Err(OsError::NoEntry)));
+    assert!(matches!(File::open(""), Err(OsError::NoEntry)));
+}
+
+#[test]
+fn test_null() {
+    let mut null = File::open("/dev/null").unwrap();
+    let mut buf = [0xAA; 4];
+    assert!(null.can_read());
+    assert_eq!(null.read(&mut buf), Ok(0));
+    assert_eq!(buf, [0xAA; 4]);
+    assert_eq!(null.write(b"discarded"), Ok(9));
+}
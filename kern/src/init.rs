@@ -1,7 +1,7 @@
 use core::mem::zeroed;
 use core::ptr::write_volatile;
 
-use core::arch::global_asm;
+use core::arch::{asm, global_asm};
 
 mod panic;
 mod oom;
@@ -25,11 +25,26 @@ unsafe fn zeros_bss() {
     }
 }
 
+/// Entry point from `init.s` with the device tree address the firmware
+/// passed, or zero.
 #[no_mangle]
-unsafe fn kinit() -> ! {
+unsafe extern "C" fn kinit(dtb: usize) -> ! {
     zeros_bss();
+    crate::vm::initialize();
+    crate::boot_info::set_dtb(dtb);
+    crate::traps::initialize();
     kmain();
+}
 
+/// Entry point from `init.s` for cores 1-3 once `smp::initialize` has woken
+/// them, with the core's number.
+#[no_mangle]
+unsafe extern "C" fn kinit_secondary(core: usize) -> ! {
+    crate::vm::enable();
+    crate::smp::report_in(core);
 
+    loop {
+        asm!("wfe");
+    }
 }
 
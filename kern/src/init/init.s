@@ -7,20 +7,30 @@
 
 .global _start
 _start:
-    // read cpu affinity, start core 0, halt rest
+    // read cpu affinity, set up core 0 first
     mrs     x1, MPIDR_EL1
     and     x1, x1, #3
     cbz     x1, setup
 
+    // cores 1-3 get here once `smp::initialize` has put their stack in
+    // SMP_STACK_TOPS and woken them through the spin table. halt any that
+    // come without a stack
+    ldr     x2, =SMP_STACK_TOPS
+    ldr     x1, [x2, x1, lsl #3]
+    cbnz    x1, switch_el
+
 halt:
-    // core affinity != 0, halt it
     wfe
     b       halt
 
 setup:
+    // keep the device tree address the firmware may have passed in x0
+    mov     x19, x0
+
     // store the desired EL1 stack pointer in x1
     adr     x1, _start
 
+switch_el:
     // read the current exception level into x0 (ref: C5.2.1)
     mrs     x0, CurrentEL
     and     x0, x0, #0b1100
@@ -74,18 +84,22 @@ switch_to_el1:
     movk    x2, #0x30d0, lsl #16
     msr     SCTLR_EL1, x2
 
-    // set up exception handlers
-    // FIXME: load `_vectors` addr into appropriate register (guide: 10.4)
-
     // change execution level to EL1 (ref: C5.2.19)
     mov     x2, #0x3c5
     msr     SPSR_EL2, x2
-    // FIXME: Return to EL1 at `set_stack`.
+    adr     x2, set_stack
+    msr     ELR_EL2, x2
+    eret
 
 set_stack:
     // set the current stack pointer
     mov     sp, x1
 
+    // set up exception handlers. done here, in EL1, so that cores the
+    // firmware starts in EL1 get them too
+    ldr     x2, =_vectors
+    msr     VBAR_EL1, x2
+
 // zero_bss:
 //     // load the start address and number of bytes in BSS section
 //     ldr     x1, =__bss_start
@@ -99,28 +113,173 @@ set_stack:
 //     cbnz    x2, zero_bss_loop
 
 go_kmain:
-    // jump to kmain, which shouldn't return. halt if it does
+    // jump to kmain, which shouldn't return. halt if it does. a zero frame
+    // pointer ends the chain of frame records that backtraces walk
+    mov     x29, xzr
+    mrs     x0, MPIDR_EL1
+    and     x0, x0, #3
+    cbnz    x0, go_secondary
+    mov     x0, x19
     bl      kinit
     b       halt
 
+go_secondary:
+    // cores 1-3 enter with their core number
+    bl      kinit_secondary
+    b       halt
+
+// The size of a `TrapFrame` (see traps/frame.rs). Must be a multiple of 16.
+.equ TF_SIZE, 800
+
+// Saves the context of the interrupted code as a `TrapFrame` on the stack,
+// calls `handle_exception(info, esr, tf)` with the info in x0 and restores the
+// (possibly modified) context. The vector already pushed the original `lr`
+// and `x0` right above the frame; they're restored by the vector too.
 context_save:
-    // FIXME: Save the remaining context to the stack.
+    sub     sp, sp, #TF_SIZE
+
+    // general purpose registers x1-x29
+    stp     x1, x2, [sp, #8]
+    stp     x3, x4, [sp, #24]
+    stp     x5, x6, [sp, #40]
+    stp     x7, x8, [sp, #56]
+    stp     x9, x10, [sp, #72]
+    stp     x11, x12, [sp, #88]
+    stp     x13, x14, [sp, #104]
+    stp     x15, x16, [sp, #120]
+    stp     x17, x18, [sp, #136]
+    stp     x19, x20, [sp, #152]
+    stp     x21, x22, [sp, #168]
+    stp     x23, x24, [sp, #184]
+    stp     x25, x26, [sp, #200]
+    stp     x27, x28, [sp, #216]
+    str     x29, [sp, #232]
+
+    // x0 and lr, as pushed by the vector
+    ldr     x1, [sp, #TF_SIZE]
+    ldr     x2, [sp, #(TF_SIZE + 8)]
+    str     x2, [sp, #0]
+    str     x1, [sp, #240]
 
+    // special registers
+    mrs     x1, SP_EL0
+    mrs     x2, ELR_EL1
+    stp     x1, x2, [sp, #248]
+    mrs     x1, SPSR_EL1
+    mrs     x2, TPIDR_EL0
+    stp     x1, x2, [sp, #264]
+
+    // SIMD/FP registers q0-q31
+    stp     q0, q1, [sp, #288]
+    stp     q2, q3, [sp, #320]
+    stp     q4, q5, [sp, #352]
+    stp     q6, q7, [sp, #384]
+    stp     q8, q9, [sp, #416]
+    stp     q10, q11, [sp, #448]
+    stp     q12, q13, [sp, #480]
+    stp     q14, q15, [sp, #512]
+    stp     q16, q17, [sp, #544]
+    stp     q18, q19, [sp, #576]
+    stp     q20, q21, [sp, #608]
+    stp     q22, q23, [sp, #640]
+    stp     q24, q25, [sp, #672]
+    stp     q26, q27, [sp, #704]
+    stp     q28, q29, [sp, #736]
+    stp     q30, q31, [sp, #768]
+
+    // handle_exception(info, esr, tf). x19 is callee-saved and already in the
+    // frame, so it can keep our return address.
+    mov     x19, lr
+    mrs     x1, ESR_EL1
+    mov     x2, sp
+    bl      handle_exception
+    mov     lr, x19
+
+// Restores the context saved in the `TrapFrame` at `sp`, pops the frame and
+// returns. `x0` and `lr` are written back above the frame for the vector to
+// restore.
 .global context_restore
 context_restore:
-    // FIXME: Restore the context from the stack.
+    ldp     q0, q1, [sp, #288]
+    ldp     q2, q3, [sp, #320]
+    ldp     q4, q5, [sp, #352]
+    ldp     q6, q7, [sp, #384]
+    ldp     q8, q9, [sp, #416]
+    ldp     q10, q11, [sp, #448]
+    ldp     q12, q13, [sp, #480]
+    ldp     q14, q15, [sp, #512]
+    ldp     q16, q17, [sp, #544]
+    ldp     q18, q19, [sp, #576]
+    ldp     q20, q21, [sp, #608]
+    ldp     q22, q23, [sp, #640]
+    ldp     q24, q25, [sp, #672]
+    ldp     q26, q27, [sp, #704]
+    ldp     q28, q29, [sp, #736]
+    ldp     q30, q31, [sp, #768]
+
+    ldp     x1, x2, [sp, #248]
+    msr     SP_EL0, x1
+    msr     ELR_EL1, x2
+    ldp     x1, x2, [sp, #264]
+    msr     SPSR_EL1, x1
+    msr     TPIDR_EL0, x2
+
+    ldr     x1, [sp, #240]
+    ldr     x2, [sp, #0]
+    str     x1, [sp, #TF_SIZE]
+    str     x2, [sp, #(TF_SIZE + 8)]
 
+    ldp     x1, x2, [sp, #8]
+    ldp     x3, x4, [sp, #24]
+    ldp     x5, x6, [sp, #40]
+    ldp     x7, x8, [sp, #56]
+    ldp     x9, x10, [sp, #72]
+    ldp     x11, x12, [sp, #88]
+    ldp     x13, x14, [sp, #104]
+    ldp     x15, x16, [sp, #120]
+    ldp     x17, x18, [sp, #136]
+    ldp     x19, x20, [sp, #152]
+    ldp     x21, x22, [sp, #168]
+    ldp     x23, x24, [sp, #184]
+    ldp     x25, x26, [sp, #200]
+    ldp     x27, x28, [sp, #216]
+    ldr     x29, [sp, #232]
+
+    add     sp, sp, #TF_SIZE
     ret
 
-// #define HANDLER(source, kind) \
-//     .align 7; \
-//     stp     lr, x0, [SP, #-16]!; \
-//     mov     x0, ##source; \
-//     movk    x0, ##kind, LSL #16; \
-//     bl      context_save; \
-//     ldp     lr, x0, [SP], #16; \
-//     eret
+// One vector table entry: saves `lr` and `x0`, passes the `Info` for
+// `source` and `kind` in x0 and returns from the exception once handled.
+.macro HANDLER source, kind
+    .align 7
+    stp     lr, x0, [sp, #-16]!
+    mov     x0, #\source
+    movk    x0, #\kind, lsl #16
+    bl      context_save
+    ldp     lr, x0, [sp], #16
+    eret
+.endm
 
+// Sources: 0 current EL with SP_EL0, 1 current EL with SP_ELx, 2 lower EL in
+// AArch64, 3 lower EL in AArch32. Kinds: 0 synchronous, 1 IRQ, 2 FIQ, 3 SError.
 .align 11
 _vectors:
-    // FIXME: Setup the 16 exception vectors.
+    HANDLER 0, 0
+    HANDLER 0, 1
+    HANDLER 0, 2
+    HANDLER 0, 3
+
+    HANDLER 1, 0
+    HANDLER 1, 1
+    HANDLER 1, 2
+    HANDLER 1, 3
+
+    HANDLER 2, 0
+    HANDLER 2, 1
+    HANDLER 2, 2
+    HANDLER 2, 3
+
+    HANDLER 3, 0
+    HANDLER 3, 1
+    HANDLER 3, 2
+    HANDLER 3, 3
@@ -1,6 +1,82 @@
+use core::arch::asm;
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use pi::common::IO_BASE;
+use shim::io;
+
+use crate::boot_args::{self, OnPanic};
+use crate::console::{kprintln, CONSOLE};
+use crate::smp::NUM_CORES;
+
+/// The most frames a backtrace prints before giving up.
+const MAX_FRAMES: usize = 32;
+
+/// Which cores are panicking. A core that panics again while reporting a
+/// panic, e.g. because it held the console when it first panicked, halts.
+static PANICKING: [AtomicBool; NUM_CORES] = [const { AtomicBool::new(false) }; NUM_CORES];
+
+/// Prints the return addresses of the frames on the stack by following the
+/// chain of frame records, each of which holds the caller's frame pointer and
+/// the return address. The chain ends with a zero frame pointer (see
+/// `init.s`); the checks stop early on a corrupted one instead of faulting.
+///
+/// The addresses can be resolved with `addr2line -e` on the kernel's ELF.
+fn backtrace() {
+    let mut fp: usize;
+    unsafe { asm!("mov {}, x29", out(reg) fp) };
+
+    kprintln!("backtrace:");
+    for i in 0..MAX_FRAMES {
+        if fp == 0 || fp % 16 != 0 || fp >= IO_BASE {
+            return;
+        }
+
+        let record = fp as *const usize;
+        let (next, lr) = unsafe { (*record, *record.add(1)) };
+        if lr == 0 {
+            return;
+        }
+
+        // `lr` points after the `bl`; report the call itself.
+        kprintln!("  {:>2}: {:#x}", i, lr - 4);
+
+        // Callers' frames are always further up the stack.
+        if next <= fp {
+            return;
+        }
+        fp = next;
+    }
+
+    kprintln!("  ...");
+}
 
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    loop {}
+fn panic(info: &PanicInfo) -> ! {
+    if PANICKING[crate::traps::core()].swap(true, Ordering::Relaxed) {
+        halt();
+    }
+
+    kprintln!("\n---------- PANIC ----------");
+    match info.location() {
+        Some(location) => kprintln!("{}:{}:{}", location.file(), location.line(), location.column()),
+        None => kprintln!("<unknown location>"),
+    }
+    kprintln!("{}\n", info.message());
+    backtrace();
+
+    let on_panic = boot_args::get().on_panic;
+    kprintln!("---------- {} ----------", on_panic);
+    let _ = io::Write::flush(&mut *CONSOLE.lock());
+
+    match on_panic {
+        OnPanic::Reboot => pi::pm::reset(),
+        OnPanic::Halt => halt(),
+    }
+}
+
+fn halt() -> ! {
+    loop {
+        unsafe { asm!("wfe") };
+    }
 }
@@ -0,0 +1,95 @@
+use core::str;
+
+#[cfg(test)]
+mod tests;
+
+/// The archive built into the kernel. `build.rs` packs the user programs in
+/// `user/build` into it as `bin/<name>`.
+static INITRAMFS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/initramfs.cpio"));
+
+/// The magic number that starts an entry in the "new" ASCII cpio format.
+const MAGIC: &[u8] = b"070701";
+
+/// The size of an entry's header: the magic number and thirteen 8-digit hex
+/// fields.
+const HEADER_SIZE: usize = 110;
+
+/// The name of the entry that ends an archive.
+const TRAILER: &str = "TRAILER!!!";
+
+/// The file type bits of an entry's mode, and their value for regular files.
+const S_IFMT: u32 = 0o170000;
+const S_IFREG: u32 = 0o100000;
+
+/// Returns the archive built into the kernel.
+pub fn get() -> Archive<'static> {
+    Archive::new(INITRAMFS)
+}
+
+/// A cpio archive in the "new" ASCII format, as `cpio -H newc` writes it.
+#[derive(Debug, Copy, Clone)]
+pub struct Archive<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Archive<'a> {
+    /// Returns the archive in `data`.
+    pub fn new(data: &'a [u8]) -> Archive<'a> {
+        Archive { data }
+    }
+
+    /// Returns the regular files in the archive, as their paths and contents.
+    /// Paths don't start with `/` or `./`. The files end early if the archive
+    /// is malformed.
+    pub fn files(&self) -> Files<'a> {
+        Files { data: self.data }
+    }
+
+    /// Returns the contents of the regular file at `path`, which may start
+    /// with `/`.
+    pub fn find(&self, path: &str) -> Option<&'a [u8]> {
+        let path = path.trim_start_matches('/');
+        self.files().find(|&(name, _)| name == path).map(|(_, contents)| contents)
+    }
+}
+
+/// An iterator over the regular files in an `Archive`.
+#[derive(Debug)]
+pub struct Files<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Files<'a> {
+    /// Reads the next entry, returning its name, mode and contents, and moves
+    /// past it. Returns `None` at the trailer or if the entry is malformed.
+    fn entry(&mut self) -> Option<(&'a str, u32, &'a [u8])> {
+        let header = self.data.get(..HEADER_SIZE).filter(|h| h.starts_with(MAGIC))?;
+        let field = |i: usize| {
+            let digits = str::from_utf8(&header[6 + i * 8..6 + (i + 1) * 8]).ok()?;
+            u32::from_str_radix(digits, 16).ok().map(|n| n as usize)
+        };
+        let (mode, size, name_size) = (field(1)? as u32, field(6)?, field(11)?);
+
+        // The name ends with a NUL; it and the contents are padded to 4 bytes.
+        let name_end = HEADER_SIZE + name_size;
+        let name = self.data.get(HEADER_SIZE..name_end.checked_sub(1)?)?;
+        let name = str::from_utf8(name).ok().filter(|&name| name != TRAILER)?;
+        let start = name_end.next_multiple_of(4);
+        let contents = self.data.get(start..start + size)?;
+        self.data = self.data.get((start + size).next_multiple_of(4)..).unwrap_or(&[]);
+        Some((name.trim_start_matches("./").trim_start_matches('/'), mode, contents))
+    }
+}
+
+impl<'a> Iterator for Files<'a> {
+    type Item = (&'a str, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (name, mode, contents) = self.entry()?;
+            if mode & S_IFMT == S_IFREG {
+                return Some((name, contents));
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use alloc::format;
+use alloc::vec::Vec;
+
+use super::*;
+
+/// Appends an entry to the archive `cpio`, as `cpio -H newc` writes it.
+fn push(cpio: &mut Vec<u8>, name: &str, mode: u32, contents: &[u8]) {
+    let fields = [0, mode, 0, 0, 1, 0, contents.len() as u32, 0, 0, 0, 0, name.len() as u32 + 1, 0];
+    cpio.extend_from_slice(MAGIC);
+    for field in fields {
+        cpio.extend_from_slice(format!("{:08X}", field).as_bytes());
+    }
+    cpio.extend_from_slice(name.as_bytes());
+    cpio.push(0);
+    cpio.resize(cpio.len().next_multiple_of(4), 0);
+    cpio.extend_from_slice(contents);
+    cpio.resize(cpio.len().next_multiple_of(4), 0);
+}
+
+fn archive() -> Vec<u8> {
+    let mut cpio = Vec::new();
+    push(&mut cpio, ".", 0o040755, b"");
+    push(&mut cpio, "bin", 0o040755, b"");
+    push(&mut cpio, "bin/hello", 0o100755, b"\x7fELF hello");
+    push(&mut cpio, "./etc/motd", 0o100644, b"hi\n");
+    push(&mut cpio, "bin/sh", 0o120777, b"hello");
+    push(&mut cpio, TRAILER, 0, b"");
+    push(&mut cpio, "after", 0o100644, b"ignored");
+    cpio
+}
+
+#[test]
+fn test_files() {
+    let cpio = archive();
+    let archive = Archive::new(&cpio);
+    let files: Vec<_> = archive.files().collect();
+    assert_eq!(files, [("bin/hello", &b"\x7fELF hello"[..]), ("etc/motd", &b"hi\n"[..])]);
+
+    assert_eq!(archive.find("/bin/hello"), Some(&b"\x7fELF hello"[..]));
+    assert_eq!(archive.find("etc/motd"), Some(&b"hi\n"[..]));
+    assert_eq!(archive.find("/bin"), None);
+    assert_eq!(archive.find("/bin/sh"), None);
+    assert_eq!(archive.find("/after"), None);
+}
+
+#[test]
+fn test_malformed() {
+    assert_eq!(Archive::new(&[]).files().count(), 0);
+
+    let cpio = archive();
+    let start = cpio.windows(9).position(|w| w == b"bin/hello").unwrap() - HEADER_SIZE;
+    assert_eq!(Archive::new(&cpio[..start + 20]).files().count(), 0);
+
+    let mut bad = cpio.clone();
+    bad[start + 6 + 6 * 8] = b'x';
+    assert_eq!(Archive::new(&bad).files().count(), 0);
+
+    let truncated = &cpio[..start + HEADER_SIZE + 12 + 4];
+    assert_eq!(Archive::new(truncated).files().count(), 0);
+
+    // The built-in archive is well-formed, even with no programs in it.
+    assert!(get().files().all(|(name, _)| name.starts_with("bin/")));
+}
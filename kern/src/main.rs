@@ -6,22 +6,77 @@
 #![cfg_attr(not(test), no_std)]
 #![cfg_attr(not(test), no_main)]
 #![feature(negative_impls)]
+
+extern crate alloc;
+
 #[cfg(not(test))]
 mod init;
+#[cfg(not(test))]
+pub mod process;
+#[cfg(not(test))]
+pub mod traps;
 
+pub mod allocator;
+pub mod boot_args;
+pub mod boot_info;
 pub mod console;
+pub mod elf;
+pub mod fs;
+pub mod initramfs;
 pub mod mutex;
 pub mod shell;
+pub mod smp;
+pub mod timer;
+pub mod vm;
 
-use console::kprintln;
+use allocator::Allocator;
+use boot_args::LogLevel;
+use console::{klog, kprintln, CONSOLE};
 use shell::shell;
 
+#[cfg_attr(not(test), global_allocator)]
+pub static ALLOCATOR: Allocator = Allocator::uninitialized();
+
+#[cfg(not(test))]
+pub static SCHEDULER: process::GlobalScheduler = process::GlobalScheduler::uninitialized();
+
+/// The first process: runs the shell with the prompt from the boot args.
+#[cfg(not(test))]
+fn run_shell() {
+    shell(boot_args::get().prompt);
+}
+
 /// The kernel entry point.
 #[no_mangle]
 pub extern "C" fn kmain() -> ! {
+    unsafe { ALLOCATOR.initialize() };
+    unsafe { boot_args::initialize() };
+    #[cfg(not(test))]
+    timer::initialize();
+    #[cfg(not(test))]
+    unsafe { smp::initialize() };
+
+    let args = boot_args::get();
+    if let Some(rate) = args.baud_rate {
+        if let Err(e) = CONSOLE.lock().set_baud_rate(rate) {
+            klog!(LogLevel::Warn, "can't switch to {} baud: {:?}", rate, e);
+        }
+    }
+    klog!(LogLevel::Info, "boot args: {}", args);
+
     // Print a welcome message.
     kprintln!("Welcome to the Rust shell!");
 
-    // Start the shell with the prompt "> ".
-    shell("> ");
-}
\ No newline at end of file
+    // Run the shell as the first process, next to any demo processes, unless
+    // the scheduler is off.
+    #[cfg(not(test))]
+    if args.scheduler {
+        SCHEDULER.initialize();
+        SCHEDULER.add(process::Process::kernel(run_shell).expect("out of memory for the shell"));
+        process::spawn_demos(args.demos);
+        SCHEDULER.start();
+    }
+
+    // Start the shell with the prompt from the boot args.
+    shell(args.prompt);
+}
@@ -1,13 +1,29 @@
 use core::fmt;
-use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicUsize, Ordering};
 use core::cell::UnsafeCell;
+use core::mem::ManuallyDrop;
 use core::ops::{DerefMut, Deref, Drop};
 
+#[cfg(test)]
+mod tests;
+
+/// The `owner` of a lock no core holds.
+const NO_OWNER: usize = usize::MAX;
+
+/// A spinlock. The core holding it can't lock it again: that would hand out
+/// a second mutable reference to the data, so `try_lock` fails and `lock`
+/// panics instead of spinning forever. Data that interrupt handlers use should
+/// be in an `IrqMutex`, so that they can't interrupt its users.
+///
+/// The lock belongs to the core, not to the process running on it. It must
+/// never be held where the holder can be preempted: the next process on the
+/// same core would find it held and panic. Data that processes use once the
+/// scheduler has started must be in an `IrqMutex` too, which masks the timer
+/// IRQ that preempts them.
 #[repr(align(32))]
 pub struct Mutex<T> {
     data: UnsafeCell<T>,
-    lock: AtomicBool,
-    owner: AtomicUsize
+    owner: AtomicUsize,
 }
 
 unsafe impl<T: Send> Send for Mutex<T> { }
@@ -23,42 +39,69 @@ unsafe impl<'a, T: Sync> Sync for MutexGuard<'a, T> { }
 impl<T> Mutex<T> {
     pub const fn new(val: T) -> Mutex<T> {
         Mutex {
-            lock: AtomicBool::new(false),
-            owner: AtomicUsize::new(usize::max_value()),
+            owner: AtomicUsize::new(NO_OWNER),
             data: UnsafeCell::new(val)
         }
     }
 }
 
 impl<T> Mutex<T> {
-    // Once MMU/cache is enabled, do the right thing here. For now, we don't
-    // need any real synchronization.
+    /// Acquires the lock if it's free. Otherwise, including when this core
+    /// already holds it, returns `None`.
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
-        let this = 0;
-        if !self.lock.load(Ordering::Relaxed) || self.owner.load(Ordering::Relaxed) == this {
-            self.lock.store(true, Ordering::Relaxed);
-            self.owner.store(this, Ordering::Relaxed);
-            Some(MutexGuard { lock: &self })
+        if self.acquire(core_id()) {
+            Some(MutexGuard { lock: self })
         } else {
             None
         }
     }
 
-    // Once MMU/cache is enabled, do the right thing here. For now, we don't
-    // need any real synchronization.
+    /// Returns `true` if this core holds the lock. Only this core can have
+    /// made itself the owner, so the answer can't change under it.
+    fn is_held_here(&self) -> bool {
+        self.owner.load(Ordering::Relaxed) == core_id()
+    }
+
+    /// Makes `this` the owner if no core is.
+    fn acquire(&self, this: usize) -> bool {
+        if crate::vm::is_enabled() {
+            self.owner
+                .compare_exchange(NO_OWNER, this, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        } else if self.owner.load(Ordering::Acquire) == NO_OWNER {
+            // Exclusive loads and stores don't work until this core's MMU is
+            // on. Only the first core takes locks before then, while it's
+            // the only one running, so a plain store will do.
+            self.owner.store(this, Ordering::Relaxed);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Spins until the lock is acquired.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this core already holds the lock, which would never be
+    /// released.
     #[inline(never)]
     pub fn lock(&self) -> MutexGuard<T> {
-        // Wait until we can "aquire" the lock, then "acquire" it.
+        debug_assert!(!preemptible(), "Mutex locked where its holder can be preempted; use an IrqMutex");
+        if self.is_held_here() {
+            panic!("lock recursion");
+        }
+
         loop {
             match self.try_lock() {
                 Some(guard) => return guard,
-                None => continue
+                None => core::hint::spin_loop(),
             }
         }
     }
 
     fn unlock(&self) {
-        self.lock.store(false, Ordering::Relaxed);
+        self.owner.store(NO_OWNER, Ordering::Release);
     }
 }
 
@@ -90,3 +133,135 @@ impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
         }
     }
 }
+
+/// A `Mutex` that masks IRQs on the core holding it, for data that interrupt
+/// handlers share with the rest of the kernel.
+pub struct IrqMutex<T>(Mutex<T>);
+
+pub struct IrqMutexGuard<'a, T: 'a> {
+    guard: ManuallyDrop<MutexGuard<'a, T>>,
+    daif: u64,
+}
+
+impl<T> IrqMutex<T> {
+    pub const fn new(val: T) -> IrqMutex<T> {
+        IrqMutex(Mutex::new(val))
+    }
+
+    /// Masks IRQs and acquires the lock if it's free. Otherwise, restores
+    /// IRQs and returns `None`.
+    pub fn try_lock(&self) -> Option<IrqMutexGuard<T>> {
+        let daif = irq_save();
+        match self.0.try_lock() {
+            Some(guard) => Some(IrqMutexGuard { guard: ManuallyDrop::new(guard), daif }),
+            None => {
+                irq_restore(daif);
+                None
+            }
+        }
+    }
+
+    /// Spins until the lock is acquired. IRQs stay enabled while spinning.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this core already holds the lock.
+    pub fn lock(&self) -> IrqMutexGuard<T> {
+        if self.0.is_held_here() {
+            panic!("lock recursion");
+        }
+
+        loop {
+            match self.try_lock() {
+                Some(guard) => return guard,
+                None => core::hint::spin_loop(),
+            }
+        }
+    }
+}
+
+impl<'a, T: 'a> Deref for IrqMutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<'a, T: 'a> DerefMut for IrqMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<'a, T: 'a> Drop for IrqMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        // Release the lock before an IRQ can come in and want it.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        irq_restore(self.daif);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for IrqMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => f.debug_struct("IrqMutex").field("data", &&*guard).finish(),
+            None => f.debug_struct("IrqMutex").field("data", &"<locked>").finish()
+        }
+    }
+}
+
+/// Returns the number of the core this runs on, which owns the locks it holds.
+#[cfg(not(test))]
+fn core_id() -> usize {
+    crate::traps::core()
+}
+
+/// Host threads stand in for cores in tests.
+#[cfg(test)]
+fn core_id() -> usize {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    std::thread_local!(static ID: usize = NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    ID.with(|&id| id)
+}
+
+/// Returns `true` if the code running on this core can be preempted: the
+/// scheduler has started and IRQs aren't masked.
+#[cfg(not(test))]
+fn preemptible() -> bool {
+    let daif: u64;
+    unsafe { core::arch::asm!("mrs {}, DAIF", out(reg) daif) };
+    crate::SCHEDULER.is_started() && daif & DAIF_I == 0
+}
+
+/// Host tests have no scheduler.
+#[cfg(test)]
+fn preemptible() -> bool {
+    false
+}
+
+/// The `DAIF` bit that masks IRQs.
+#[cfg(not(test))]
+const DAIF_I: u64 = 1 << 7;
+
+/// Masks IRQs on this core and returns the previous mask (`DAIF`).
+#[cfg(not(test))]
+fn irq_save() -> u64 {
+    let daif: u64;
+    unsafe { core::arch::asm!("mrs {}, DAIF", "msr DAIFSet, #0b0010", out(reg) daif) };
+    daif
+}
+
+/// Restores the IRQ mask that `irq_save` returned.
+#[cfg(not(test))]
+fn irq_restore(daif: u64) {
+    unsafe { core::arch::asm!("msr DAIF, {}", in(reg) daif) };
+}
+
+#[cfg(test)]
+fn irq_save() -> u64 {
+    0
+}
+
+#[cfg(test)]
+fn irq_restore(_daif: u64) {}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::thread;
+
+use super::*;
+
+#[test]
+fn test_mutual_exclusion() {
+    let counter = Arc::new(Mutex::new(0usize));
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let counter = counter.clone();
+            thread::spawn(move || {
+                for _ in 0..1_000 {
+                    let mut count = counter.lock();
+                    // A non-atomic read-modify-write that loses updates
+                    // unless the lock excludes the other threads.
+                    let value = *count;
+                    thread::yield_now();
+                    *count = value + 1;
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*counter.lock(), 4_000);
+}
+
+#[test]
+fn test_no_recursion() {
+    let mutex = Mutex::new(1);
+    let guard = mutex.lock();
+    assert!(mutex.try_lock().is_none(), "the owner can't lock again");
+    assert_eq!(format!("{:?}", mutex), "Mutex { data: \"<locked>\" }");
+
+    let mutex = &mutex;
+    thread::scope(|s| {
+        s.spawn(|| assert!(mutex.try_lock().is_none()));
+    });
+
+    drop(guard);
+    thread::scope(|s| {
+        s.spawn(|| assert_eq!(*mutex.try_lock().expect("released"), 1));
+    });
+    assert_eq!(*mutex.try_lock().expect("released"), 1);
+}
+
+#[test]
+#[should_panic(expected = "lock recursion")]
+fn test_lock_recursion_panics() {
+    let mutex = IrqMutex::new(1);
+    let _guard = mutex.lock();
+    let _ = mutex.lock();
+}
+
+#[test]
+fn test_irq_mutex() {
+    let mutex = IrqMutex::new(vec![1]);
+    mutex.lock().push(2);
+    {
+        let guard = mutex.lock();
+        let mutex = &mutex;
+        thread::scope(|s| {
+            s.spawn(|| assert!(mutex.try_lock().is_none()));
+        });
+        assert_eq!(*guard, [1, 2]);
+    }
+
+    assert_eq!(format!("{:?}", mutex), "IrqMutex { data: [1, 2] }");
+}
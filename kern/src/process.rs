@@ -0,0 +1,359 @@
+mod demo;
+mod scheduler;
+mod stack;
+mod state;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::{mem, slice};
+
+use kernel_api::{OsError, OsResult};
+
+use crate::elf::{Elf, Flags};
+use crate::fs::File;
+use crate::traps::TrapFrame;
+use crate::vm::{self, Permission, UserPageTable, PAGE_SIZE, USER_BASE, USER_END};
+
+pub use self::demo::spawn_demos;
+pub use self::scheduler::GlobalScheduler;
+pub use self::stack::Stack;
+pub use self::state::{EventPollFn, State};
+
+/// Type alias for the type of a process ID.
+pub type Id = u64;
+
+/// The ID of the idle process, which runs when no other process is ready.
+pub const IDLE_ID: Id = Id::MAX;
+
+/// `SPSR_EL1` for kernel processes: EL1 using `SP_EL0`, so that exceptions
+/// run on the core's own stack, with IRQs unmasked and debug, SError and FIQ
+/// exceptions masked.
+const SPSR_EL1T: u64 = (0b1101 << 6) | 0b0100;
+
+/// `SPSR_EL1` for user processes: EL0, with the same exceptions masked.
+const SPSR_EL0T: u64 = 0b1101 << 6;
+
+/// How far a user process's stack may grow down from `USER_END`. Its heap
+/// can't grow into that.
+const USER_STACK_SIZE: usize = 16 * 1024 * 1024;
+
+/// The most files a process can have open at once.
+const MAX_FILES: usize = 16;
+
+/// A structure that represents the complete state of a process.
+#[derive(Debug)]
+pub struct Process {
+    /// The saved trap frame of a process.
+    pub context: Box<TrapFrame>,
+    /// The memory allocation used for the process's stack.
+    pub stack: Stack,
+    /// The scheduling state of the process.
+    pub state: State,
+    /// The page table of a user process's address space. Kernel processes
+    /// use the kernel's.
+    pub vmap: Option<Box<UserPageTable>>,
+    /// The process's open files, indexed by file descriptor.
+    pub files: Vec<Option<File>>,
+    /// Where a user process's heap starts.
+    pub heap_start: usize,
+    /// Where a user process's heap ends, as moved by `sbrk`.
+    pub brk: usize,
+    /// The process that started this one, which can wait for it to exit.
+    pub parent: Option<Id>,
+    /// The children of this process that have exited but haven't been waited
+    /// for.
+    pub exited: Vec<Id>,
+}
+
+impl Process {
+    /// Creates a new process with a zeroed `TrapFrame` (the default), a zeroed
+    /// stack of the default size, and a state of `Ready`.
+    ///
+    /// If enough memory could not be allocated to start the process, returns
+    /// `None`. Otherwise returns `Some` of the new `Process`.
+    pub fn new() -> Option<Process> {
+        Some(Process {
+            context: Box::new(TrapFrame::default()),
+            stack: Stack::new()?,
+            state: State::Ready,
+            vmap: None,
+            files: Vec::new(),
+            heap_start: 0,
+            brk: 0,
+            parent: None,
+            exited: Vec::new(),
+        })
+    }
+
+    /// Creates a process that runs `entry` in the kernel, at EL1 on its own
+    /// stack, and exits when it returns. It can make system calls like a user
+    /// process, starting with the console open as `STDIN`, `STDOUT` and
+    /// `STDERR`.
+    pub fn kernel(entry: fn()) -> Option<Process> {
+        let mut process = Process::new()?;
+        process.context.elr = run_kernel_process as usize as u64;
+        process.context.x[0] = entry as usize as u64;
+        process.context.sp = process.stack.top() as u64;
+        process.context.spsr = SPSR_EL1T;
+        process.files = vec![Some(File::Console), Some(File::Console), Some(File::Console)];
+        Some(process)
+    }
+
+    /// Creates a process that runs `code`, copied to `USER_BASE`, at EL0 in an
+    /// address space of its own. Its stack grows down from `USER_END`, with
+    /// pages mapped as it faults on them, and its heap starts at the page
+    /// after the code. It starts with the console open as `STDIN`, `STDOUT`
+    /// and `STDERR`.
+    pub fn user(code: &[u8]) -> Option<Process> {
+        let mut process = Process::new()?;
+        let mut vmap = UserPageTable::new(vm::kern_page_table())?;
+        for (i, chunk) in code.chunks(PAGE_SIZE).enumerate() {
+            let page = vmap.alloc(USER_BASE + i * PAGE_SIZE, Permission::UserRx)?;
+            page[..chunk.len()].copy_from_slice(chunk);
+            vm::sync_icache(page.as_ptr() as usize, chunk.len());
+        }
+
+        let heap_start = USER_BASE + code.len().div_ceil(PAGE_SIZE) * PAGE_SIZE;
+        process.enter_user(vmap, USER_BASE, heap_start);
+        Some(process)
+    }
+
+    /// Creates a process that runs the program `elf` at EL0 in an address
+    /// space of its own, with each page of its segments mapped with the
+    /// permissions the segments ask for. Its stack and files are set up as
+    /// for `user`, and its heap starts at the page after the last segment.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NoMemory` if there's no memory for the process and
+    /// `InvalidArgument` if its segments reach into the stack.
+    pub fn load(elf: &Elf) -> OsResult<Process> {
+        let mut process = Process::new().ok_or(OsError::NoMemory)?;
+        let mut vmap = UserPageTable::new(vm::kern_page_table()).ok_or(OsError::NoMemory)?;
+
+        // A page shared by several segments gets the flags of all of them.
+        let mut pages: BTreeMap<usize, Flags> = BTreeMap::new();
+        for segment in elf.segments() {
+            for page in segment.pages() {
+                let flags = pages.entry(page).or_default();
+                *flags = *flags | segment.flags;
+            }
+        }
+
+        let heap_start = pages.keys().next_back().map_or(USER_BASE, |&page| page + PAGE_SIZE);
+        if heap_start > USER_END - USER_STACK_SIZE {
+            return Err(OsError::InvalidArgument);
+        }
+
+        for (&page, &flags) in &pages {
+            vmap.alloc(page, flags.permission()).ok_or(OsError::NoMemory)?;
+        }
+
+        // The pages are zeroed, which takes care of the parts of segments
+        // that aren't in the file. The rest is copied a page at a time, as
+        // the pages aren't next to each other in the kernel's view.
+        for segment in elf.segments() {
+            let (mut va, mut data) = (segment.vaddr, segment.data);
+            while !data.is_empty() {
+                let len = data.len().min(PAGE_SIZE - va % PAGE_SIZE);
+                let pa = vmap.translate(va).expect("segment page unmapped");
+                unsafe { slice::from_raw_parts_mut(pa as *mut u8, len) }.copy_from_slice(&data[..len]);
+                (va, data) = (va + len, &data[len..]);
+            }
+        }
+
+        for (&page, _) in pages.iter().filter(|(_, flags)| flags.contains(Flags::EXECUTE)) {
+            vm::sync_icache(vmap.translate(page).expect("segment page unmapped"), PAGE_SIZE);
+        }
+
+        process.enter_user(vmap, elf.entry(), heap_start);
+        Ok(process)
+    }
+
+    /// Makes this process start at `entry` at EL0, in the address space
+    /// `vmap`, with its stack at `USER_END`, its heap at `heap_start`, and
+    /// the console open as `STDIN`, `STDOUT` and `STDERR`.
+    fn enter_user(&mut self, vmap: Box<UserPageTable>, entry: usize, heap_start: usize) {
+        self.context.elr = entry as u64;
+        self.context.sp = USER_END as u64;
+        self.context.spsr = SPSR_EL0T;
+        self.vmap = Some(vmap);
+        self.files = vec![Some(File::Console), Some(File::Console), Some(File::Console)];
+        self.heap_start = heap_start;
+        self.brk = heap_start;
+    }
+
+    /// Returns the address of this process's page table, for `TTBR0_EL1`.
+    pub fn ttbr0(&self) -> usize {
+        match &self.vmap {
+            Some(vmap) => vmap.baddr(),
+            None => vm::kern_page_table().baddr(),
+        }
+    }
+
+    /// Handles a translation fault at `va` by mapping a new page there, if
+    /// `va` is in this process's part of the address space. Returns `false`
+    /// if it isn't, or if there's no memory left.
+    pub fn page_fault(&mut self, va: usize) -> bool {
+        let vmap = match &mut self.vmap {
+            Some(vmap) if (USER_BASE..USER_END).contains(&va) => vmap,
+            _ => return false,
+        };
+
+        if vmap.alloc(va & !(PAGE_SIZE - 1), Permission::UserRw).is_none() {
+            return false;
+        }
+
+        // Make the new entry visible to the table walk before the access is
+        // retried. Invalid entries are never cached, so there's nothing to
+        // invalidate.
+        unsafe { asm!("dsb ishst") };
+        true
+    }
+
+    /// Returns the `len` bytes of the process's memory at `va`, for a system
+    /// call. Kernel processes share the kernel's memory; a user process's
+    /// pages that aren't mapped yet are mapped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BadAddress` if the range isn't in a user process's part of
+    /// the address space and `NoMemory` if its pages can't be mapped.
+    ///
+    /// # Safety
+    ///
+    /// The process's page table must be the one in use, and the slice mustn't
+    /// outlive the process or alias another borrow of the memory.
+    pub unsafe fn buffer<'a>(&mut self, va: u64, len: u64) -> OsResult<&'a mut [u8]> {
+        if len == 0 {
+            return Ok(&mut []);
+        }
+
+        let (va, len) = (va as usize, len as usize);
+        if self.vmap.is_none() {
+            return Ok(slice::from_raw_parts_mut(va as *mut u8, len));
+        }
+
+        let end = va.checked_add(len)
+            .filter(|&end| va >= USER_BASE && end <= USER_END)
+            .ok_or(OsError::BadAddress)?;
+
+        for page in (va & !(PAGE_SIZE - 1)..end).step_by(PAGE_SIZE) {
+            let mapped = self.vmap.as_ref().is_some_and(|vmap| vmap.translate(page).is_some());
+            if !mapped && !self.page_fault(page) {
+                return Err(OsError::NoMemory);
+            }
+        }
+
+        Ok(slice::from_raw_parts_mut(va as *mut u8, len))
+    }
+
+    /// Moves the end of a user process's heap by `increment` bytes and returns
+    /// where it was. The pages are mapped as the process faults on them; the
+    /// ones it gives back stay mapped.
+    pub fn sbrk(&mut self, increment: isize) -> OsResult<usize> {
+        if self.vmap.is_none() {
+            return Err(OsError::Unsupported);
+        }
+
+        let brk = self.brk.checked_add_signed(increment).ok_or(OsError::InvalidArgument)?;
+        if brk < self.heap_start {
+            return Err(OsError::InvalidArgument);
+        } else if brk > USER_END - USER_STACK_SIZE {
+            return Err(OsError::NoMemory);
+        }
+
+        Ok(mem::replace(&mut self.brk, brk))
+    }
+
+    /// Adds `file` to the process's open files and returns its descriptor.
+    pub fn add_file(&mut self, file: File) -> OsResult<u64> {
+        let fd = match self.files.iter().position(Option::is_none) {
+            Some(fd) => fd,
+            None if self.files.len() < MAX_FILES => {
+                self.files.push(None);
+                self.files.len() - 1
+            }
+            None => return Err(OsError::TooManyFiles),
+        };
+
+        self.files[fd] = Some(file);
+        Ok(fd as u64)
+    }
+
+    /// Returns the open file `fd`.
+    pub fn file(&mut self, fd: u64) -> OsResult<&mut File> {
+        self.files.get_mut(fd as usize).and_then(Option::as_mut).ok_or(OsError::BadFd)
+    }
+
+    /// Closes the open file `fd`.
+    pub fn close_file(&mut self, fd: u64) -> OsResult<()> {
+        self.files.get_mut(fd as usize).and_then(Option::take).map(drop).ok_or(OsError::BadFd)
+    }
+
+    /// Forgets that the child `child` exited. Returns `false` if it hasn't.
+    pub fn reap(&mut self, child: Id) -> bool {
+        match self.exited.iter().position(|&id| id == child) {
+            Some(i) => {
+                self.exited.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns this process's ID, which the scheduler keeps in `TPIDR_EL0`.
+    pub fn id(&self) -> Id {
+        self.context.tpidr
+    }
+
+    /// Returns `true` if this process is ready to be scheduled.
+    ///
+    /// This functions returns `true` only if one of the following holds:
+    ///
+    ///   * The state is currently `Ready`.
+    ///
+    ///   * An event being waited for has arrived.
+    ///
+    ///     If the process is currently waiting, the corresponding event
+    ///     function is polled to determine if the event being waiting for has
+    ///     occured. If it has, the state is switched to `Ready` and this
+    ///     function returns `true`.
+    ///
+    /// Returns `false` in all other cases.
+    pub fn is_ready(&mut self) -> bool {
+        match mem::replace(&mut self.state, State::Ready) {
+            State::Ready => true,
+            State::Waiting(mut poll) => {
+                if poll(self) {
+                    true
+                } else {
+                    self.state = State::Waiting(poll);
+                    false
+                }
+            }
+            state => {
+                self.state = state;
+                false
+            }
+        }
+    }
+}
+
+/// Returns the ID of the process running on this core.
+pub fn current_id() -> Id {
+    let id: u64;
+    unsafe { asm!("mrs {}, TPIDR_EL0", out(reg) id) };
+    id
+}
+
+/// Where kernel processes start, with the address of their `fn()` in `x0`:
+/// runs it, then exits.
+extern "C" fn run_kernel_process(entry: usize) -> ! {
+    let entry: fn() = unsafe { mem::transmute(entry) };
+    entry();
+    kernel_api::syscall::exit();
+}
@@ -0,0 +1,47 @@
+use core::arch::global_asm;
+use core::ptr::addr_of;
+use core::slice;
+use core::time::Duration;
+
+use crate::console::kprintln;
+use crate::process::{current_id, Process};
+use crate::SCHEDULER;
+
+global_asm!(include_str!("demo.s"));
+
+/// Returns the code of the user demo program in `demo.s`.
+fn user_demo() -> &'static [u8] {
+    extern "C" {
+        static user_demo_start: u8;
+        static user_demo_end: u8;
+    }
+
+    unsafe {
+        let start = addr_of!(user_demo_start);
+        slice::from_raw_parts(start, addr_of!(user_demo_end) as usize - start as usize)
+    }
+}
+
+/// A demo process: prints a few lines, sleeping in between, so that several
+/// of them show how the scheduler interleaves processes and idles while they
+/// all sleep.
+fn kernel_demo() {
+    for i in 0..5 {
+        kprintln!("[process {}] line {} of 5", current_id(), i + 1);
+        let _ = kernel_api::syscall::sleep(Duration::from_millis(300));
+    }
+}
+
+/// Starts `demos` kernel demo processes and, if there are any, one user
+/// process that makes a few system calls and is then killed for a fault.
+pub fn spawn_demos(demos: usize) {
+    for _ in 0..demos {
+        let process = Process::kernel(kernel_demo).expect("out of memory for a demo process");
+        SCHEDULER.add(process);
+    }
+
+    if demos > 0 {
+        let process = Process::user(user_demo()).expect("out of memory for a demo process");
+        SCHEDULER.add(process);
+    }
+}
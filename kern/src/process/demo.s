@@ -0,0 +1,34 @@
+// A user program for the demo processes. It's copied to `USER_BASE` and run
+// at EL0: it grows its stack by a few pages, which get mapped as it faults on
+// them, says hello a few times through system calls, then reads kernel
+// memory, which EL0 can't, and gets killed for it.
+.pushsection .rodata.user_demo
+.balign 4
+.global user_demo_start
+.global user_demo_end
+user_demo_start:
+    mov     x19, #4
+1:  sub     sp, sp, #0x10000
+    str     x19, [sp]
+    subs    x19, x19, #1
+    b.ne    1b
+
+    mov     x19, #3
+2:  mov     x0, #1                      // STDOUT
+    adr     x1, hello
+    mov     x2, #(hello_end - hello)
+    svc     #4                          // NR_WRITE
+    mov     x0, #500
+    svc     #1                          // NR_SLEEP
+    subs    x19, x19, #1
+    b.ne    2b
+
+    mov     x0, #0x80000
+    ldr     x0, [x0]
+
+hello:
+    .ascii  "hello from user space\n"
+hello_end:
+.balign 4
+user_demo_end:
+.popsection
@@ -0,0 +1,244 @@
+use alloc::collections::vec_deque::VecDeque;
+use core::arch::asm;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::mutex::IrqMutex;
+use crate::process::{Id, Process, State, IDLE_ID};
+use crate::traps::TrapFrame;
+use crate::vm;
+
+/// Process scheduler for the entire machine, and whether it has started.
+pub struct GlobalScheduler(IrqMutex<Option<Scheduler>>, AtomicBool);
+
+impl GlobalScheduler {
+    /// Returns an uninitialized wrapper around a local scheduler.
+    pub const fn uninitialized() -> GlobalScheduler {
+        GlobalScheduler(IrqMutex::new(None), AtomicBool::new(false))
+    }
+
+    /// Enters a critical region and executes the provided closure with a
+    /// mutable reference to the inner scheduler.
+    fn critical<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Scheduler) -> R,
+    {
+        let mut guard = self.0.lock();
+        f(guard.as_mut().expect("scheduler uninitialized"))
+    }
+
+    /// Initializes the scheduler. Processes can be added from then on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there's no memory for the idle process.
+    pub fn initialize(&self) {
+        *self.0.lock() = Some(Scheduler::new());
+    }
+
+    /// Adds a process to the scheduler's queue and returns that process's ID.
+    /// For more details, see the documentation on `Scheduler::add()`.
+    pub fn add(&self, process: Process) -> Option<Id> {
+        self.critical(move |scheduler| scheduler.add(process))
+    }
+
+    /// Performs a context switch using `tf` by setting the state of the
+    /// current process to `new_state`, saving `tf` into it, and restoring the
+    /// next process which is ready to run, or the idle process, into `tf`.
+    ///
+    /// Returns the ID of the process that's now running.
+    pub fn switch(&self, new_state: State, tf: &mut TrapFrame) -> Id {
+        self.critical(|scheduler| {
+            scheduler.schedule_out(new_state, tf);
+            scheduler.switch_to(tf)
+        })
+    }
+
+    /// Preempts the current process, if one is running, in favour of the next
+    /// one which is ready. Waiting processes whose event has happened are
+    /// ready again. Called on every timer tick.
+    pub fn tick(&self, tf: &mut TrapFrame) {
+        if let Some(scheduler) = self.0.lock().as_mut() {
+            if scheduler.schedule_out(State::Ready, tf) {
+                scheduler.switch_to(tf);
+            }
+        }
+    }
+
+    /// Kills the process `tf` belongs to and restores the next process which
+    /// is ready to run into `tf`. Returns the ID of the killed process.
+    pub fn kill(&self, tf: &mut TrapFrame) -> Id {
+        let id = tf.tpidr;
+        self.switch(State::Dead, tf);
+        id
+    }
+
+    /// Calls `f` with the process with the ID `id`, if there's one.
+    pub fn with_process<F, R>(&self, id: Id, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Process) -> R,
+    {
+        self.critical(|scheduler| scheduler.processes.iter_mut().find(|process| process.id() == id).map(f))
+    }
+
+    /// Returns `true` once `start` has been called: code that runs with IRQs
+    /// unmasked can be preempted from then on.
+    pub fn is_started(&self) -> bool {
+        self.1.load(Ordering::Relaxed)
+    }
+
+    /// Starts running the processes that were added, switching between them
+    /// on every timer tick. Never returns.
+    pub fn start(&self) -> ! {
+        extern "C" {
+            fn _start();
+        }
+
+        self.1.store(true, Ordering::Relaxed);
+
+        // Nothing that ran before needs its stack anymore: start over at the
+        // top of it, where exceptions will be taken from now on.
+        unsafe {
+            asm!(
+                "msr DAIFSet, #0b0010",
+                "mov sp, {stack}",
+                "mov x29, xzr",
+                "bl {run_first}",
+                stack = in(reg) _start as usize,
+                run_first = sym run_first,
+                in("x0") self,
+                options(noreturn),
+            )
+        }
+    }
+}
+
+/// A trap frame followed by room for the `lr` and `x0` that `context_restore`
+/// leaves above it, as for an exception vector.
+#[repr(C, align(16))]
+struct ExceptionReturn {
+    tf: TrapFrame,
+    lr_x0: [u64; 2],
+}
+
+/// Returns from a fake exception into the first process that's ready.
+extern "C" fn run_first(scheduler: &GlobalScheduler) -> ! {
+    let mut frame = ExceptionReturn { tf: TrapFrame::default(), lr_x0: [0; 2] };
+    scheduler.critical(|scheduler| scheduler.switch_to(&mut frame.tf));
+
+    unsafe {
+        asm!(
+            "mov sp, {frame}",
+            "bl context_restore",
+            "ldp lr, x0, [sp], #16",
+            "eret",
+            frame = in(reg) &frame,
+            options(noreturn),
+        )
+    }
+}
+
+/// What the idle process runs: it waits for interrupts, so that the core
+/// sleeps until there's something to do.
+fn run_idle() {
+    loop {
+        unsafe { asm!("wfi") };
+    }
+}
+
+/// Round-robin scheduler of processes.
+struct Scheduler {
+    processes: VecDeque<Process>,
+    last_id: Option<Id>,
+    /// The process that runs when no other is ready. It isn't queued, and it
+    /// starts over each time.
+    idle: Process,
+}
+
+impl Scheduler {
+    /// Returns a new `Scheduler` with an empty queue.
+    fn new() -> Scheduler {
+        let mut idle = Process::kernel(run_idle).expect("out of memory for the idle process");
+        idle.context.tpidr = IDLE_ID;
+        Scheduler { processes: VecDeque::new(), last_id: None, idle }
+    }
+
+    /// Adds a process to the scheduler's queue and returns that process's ID if
+    /// a new process can be scheduled. The process ID is newly allocated for
+    /// the process and saved in its trap frame's `tpidr`. If no further processes can
+    /// be scheduled, returns `None`.
+    fn add(&mut self, mut process: Process) -> Option<Id> {
+        let id = match self.last_id {
+            Some(last_id) => last_id.checked_add(1).filter(|&id| id != IDLE_ID)?,
+            None => 0,
+        };
+
+        process.context.tpidr = id;
+        self.processes.push_back(process);
+        self.last_id = Some(id);
+        Some(id)
+    }
+
+    /// Finds the currently running process, sets the current process's state
+    /// to `new_state`, prepares the context switch on `tf` by saving `tf`
+    /// into the current process, and pushes the current process to the back
+    /// of `processes` queue. A process that has died, or is to die, is
+    /// dropped instead, along with its address space, and its parent is told.
+    ///
+    /// If the `processes` queue is empty or there is no current process,
+    /// returns `false`. Otherwise, returns `true`.
+    fn schedule_out(&mut self, new_state: State, tf: &mut TrapFrame) -> bool {
+        if tf.tpidr == IDLE_ID {
+            return true;
+        }
+
+        let index = self.processes.iter().position(|process| {
+            process.id() == tf.tpidr && matches!(process.state, State::Running | State::Dead)
+        });
+
+        let mut process = match index.and_then(|i| self.processes.remove(i)) {
+            Some(process) => process,
+            None => return false,
+        };
+
+        if let (State::Dead, _) | (_, State::Dead) = (&process.state, &new_state) {
+            // Stop using its page table before freeing it.
+            unsafe { vm::set_ttbr0(vm::kern_page_table().baddr()) };
+            let parent = process.parent.and_then(|id| self.processes.iter_mut().find(|p| p.id() == id));
+            if let Some(parent) = parent {
+                parent.exited.push(process.id());
+            }
+            return true;
+        }
+
+        *process.context = *tf;
+        process.state = new_state;
+        self.processes.push_back(process);
+        true
+    }
+
+    /// Finds the next process to switch to, brings the next process to the
+    /// front of the `processes` queue, changes the next process's state to
+    /// `Running`, and performs context switch by restoring the next process's
+    /// trap frame into `tf` and switching to its page table.
+    ///
+    /// If there is no process to switch to, switches to the idle process.
+    /// Returns the ID of the process switched to.
+    fn switch_to(&mut self, tf: &mut TrapFrame) -> Id {
+        let index = self.processes.iter_mut().position(|process| process.is_ready());
+        let mut process = match index.and_then(|i| self.processes.remove(i)) {
+            Some(process) => process,
+            None => {
+                *tf = *self.idle.context;
+                unsafe { vm::set_ttbr0(self.idle.ttbr0()) };
+                return IDLE_ID;
+            }
+        };
+
+        process.state = State::Running;
+        *tf = *process.context;
+        unsafe { vm::set_ttbr0(process.ttbr0()) };
+        let id = process.id();
+        self.processes.push_front(process);
+        id
+    }
+}
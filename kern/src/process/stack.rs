@@ -0,0 +1,58 @@
+use alloc::alloc::{alloc_zeroed, dealloc, Layout};
+use core::fmt;
+use core::ptr::NonNull;
+
+/// A process's stack, allocated from the kernel heap.
+pub struct Stack {
+    ptr: NonNull<u8>,
+}
+
+/// A stack is only ever used by the process that owns it.
+unsafe impl Send for Stack {}
+
+impl Stack {
+    /// The size of a stack in bytes.
+    pub const SIZE: usize = 64 * 1024;
+
+    /// The stack's alignment, which AArch64 requires of the stack pointer.
+    pub const ALIGN: usize = 16;
+
+    fn layout() -> Layout {
+        Layout::from_size_align(Self::SIZE, Self::ALIGN).unwrap()
+    }
+
+    /// Returns a newly allocated, zeroed stack, or `None` if the heap is
+    /// exhausted. Zeroing it keeps whatever the memory held before out of the
+    /// new process's reach.
+    pub fn new() -> Option<Stack> {
+        let ptr = unsafe { alloc_zeroed(Self::layout()) };
+        NonNull::new(ptr).map(|ptr| Stack { ptr })
+    }
+
+    /// Returns the lowest address in the stack.
+    pub fn bottom(&self) -> usize {
+        self.ptr.as_ptr() as usize
+    }
+
+    /// Returns the address just past the stack, where a new stack pointer
+    /// starts.
+    pub fn top(&self) -> usize {
+        self.bottom() + Self::SIZE
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ptr.as_ptr(), Self::layout()) }
+    }
+}
+
+impl fmt::Debug for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Stack")
+            .field("top", &(self.top() as *const u8))
+            .field("bottom", &(self.bottom() as *const u8))
+            .field("size", &Self::SIZE)
+            .finish()
+    }
+}
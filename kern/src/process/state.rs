@@ -0,0 +1,32 @@
+use alloc::boxed::Box;
+use core::fmt;
+
+use crate::process::Process;
+
+/// A function that's polled while a process waits for an event. It returns
+/// `true`, possibly after updating the process's context, once the event has
+/// happened.
+pub type EventPollFn = Box<dyn FnMut(&mut Process) -> bool + Send>;
+
+/// The scheduling state of a process.
+pub enum State {
+    /// Ready to be scheduled.
+    Ready,
+    /// Waiting for an event to occur before it can be scheduled.
+    Waiting(EventPollFn),
+    /// Currently running.
+    Running,
+    /// Finished; it's dropped once it's no longer running.
+    Dead,
+}
+
+impl fmt::Debug for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            State::Ready => write!(f, "State::Ready"),
+            State::Running => write!(f, "State::Running"),
+            State::Waiting(_) => write!(f, "State::Waiting"),
+            State::Dead => write!(f, "State::Dead"),
+        }
+    }
+}
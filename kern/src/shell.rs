@@ -1,6 +1,11 @@
 use stack_vec::StackVec;
 
 use crate::console::{kprint, kprintln, CONSOLE};
+use crate::boot_args;
+use crate::boot_info;
+use crate::smp;
+use crate::timer;
+use crate::ALLOCATOR;
 use core::arch::asm;
 
 /// Error type for `Command` parse failures.
@@ -42,6 +47,110 @@ impl<'a> Command<'a> {
     }
 }
 
+/// Shows the console's baud rate or, given one, switches to it. The terminal
+/// on the other end has to follow, e.g. `ttywrite -T -b <rate> <tty>`.
+fn baud(args: &[&str]) {
+    match args {
+        [] => {
+            // Printing needs the console, so let go of it first.
+            let rate = CONSOLE.lock().baud_rate();
+            kprintln!("{}", rate);
+        }
+        [rate] => match rate.parse() {
+            Ok(rate) => {
+                kprintln!("switching to {} baud", rate);
+                if let Err(e) = CONSOLE.lock().set_baud_rate(rate) {
+                    kprintln!("error: {:?}", e);
+                }
+            }
+            Err(_) => kprintln!("error: invalid baud rate: {}", rate),
+        },
+        _ => kprintln!("usage: baud [rate]"),
+    }
+}
+
+/// Runs the program at `path` in the initramfs as a child of the shell's
+/// process and waits for it to exit. Without a path, lists the programs.
+#[cfg(not(test))]
+fn exec(args: &[&str]) {
+    let path = match args {
+        [path] => path,
+        _ => {
+            kprintln!("usage: exec <path>");
+            for (name, _) in crate::initramfs::get().files() {
+                kprintln!("  /{}", name);
+            }
+            return;
+        }
+    };
+
+    if !boot_args::get().scheduler {
+        kprintln!("exec: programs run as processes, but the scheduler is off");
+        return;
+    }
+
+    match spawn(path) {
+        Ok(id) => {
+            if let Err(e) = kernel_api::syscall::wait(id) {
+                kprintln!("exec: {}: {}", path, e);
+            }
+        }
+        Err(e) => kprintln!("exec: {}: {}", path, e),
+    }
+}
+
+/// Starts the program at `path` in the initramfs as a child of the shell's
+/// process. Returns the child's ID.
+#[cfg(not(test))]
+fn spawn(path: &str) -> Result<crate::process::Id, alloc::string::String> {
+    use alloc::string::{String, ToString};
+
+    use crate::elf::Elf;
+    use crate::initramfs;
+    use crate::process::{current_id, Process};
+    use crate::SCHEDULER;
+
+    let program = initramfs::get().find(path).ok_or_else(|| String::from("no such file"))?;
+    let elf = Elf::parse(program).map_err(|e| e.to_string())?;
+    let mut process = Process::load(&elf).map_err(|e| e.to_string())?;
+    process.parent = Some(current_id());
+    SCHEDULER.add(process).ok_or_else(|| String::from("out of process IDs"))
+}
+
+/// Set once reading standard input has failed, after which the shell polls
+/// the UART instead.
+#[cfg(not(test))]
+static STDIN_FAILED: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+/// Waits for a byte of input. Run by a process, the shell blocks in the
+/// `read` system call, letting the others run; otherwise, or if that fails,
+/// it polls the UART.
+fn read_byte() -> u8 {
+    #[cfg(not(test))]
+    {
+        use core::sync::atomic::Ordering;
+
+        if boot_args::get().scheduler && !STDIN_FAILED.load(Ordering::Relaxed) {
+            let mut byte = [0; 1];
+            match kernel_api::syscall::read(kernel_api::STDIN, &mut byte) {
+                Ok(1) => return byte[0],
+                Ok(_) => kprintln!("error: stdin has ended; polling the console instead"),
+                Err(e) => kprintln!("error: can't read stdin: {:?}; polling the console instead", e),
+            }
+            STDIN_FAILED.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Don't wait while holding the console: it masks IRQs, which would keep
+    // other processes from running.
+    loop {
+        let mut console = CONSOLE.lock();
+        if console.has_byte() {
+            return console.read_byte();
+        }
+    }
+}
+
 /// Starts a shell using `prefix` as the prefix for each line. This function
 /// returns if the `exit` command is called.
 pub fn shell(prefix: &str) -> !{
@@ -55,7 +164,7 @@ pub fn shell(prefix: &str) -> !{
 
         // Read input one byte at a time.
         loop {
-            let byte = CONSOLE.lock().read_byte();
+            let byte = read_byte();
             match byte {
                 // Accept both '\r' and '\n' as Enter.
                 b'\r' | b'\n' => {
@@ -121,6 +230,35 @@ pub fn shell(prefix: &str) -> !{
                         kprint!("{}", arg);
                     }
                     kprintln!("");
+                } else if cmd_name == "baud" {
+                    baud(&cmd.args[1..]);
+                } else if cmd_name == "heap" {
+                    match ALLOCATOR.stats() {
+                        Some(stats) => kprintln!("{}", stats),
+                        None => kprintln!("error: allocator uninitialized"),
+                    }
+                } else if cmd_name == "atags" {
+                    for atag in boot_info::entries() {
+                        kprintln!("{:#x?}", atag);
+                    }
+                } else if cmd_name == "bootargs" {
+                    kprintln!("{}", boot_args::get());
+                } else if cmd_name == "uptime" {
+                    let ticks = timer::ticks();
+                    let idle = timer::idle_ticks();
+                    kprintln!("{} ticks ({:?}), {} idle ({}%)", ticks, timer::TICK * ticks as u32,
+                              idle, idle * 100 / ticks.max(1));
+                } else if cmd_name == "cores" {
+                    for core in 0..smp::NUM_CORES {
+                        match smp::stack(core) {
+                            Some((base, size)) => kprintln!("core {}: {} (stack {:#x}-{:#x})",
+                                                            core, smp::status(core), base, base + size),
+                            None => kprintln!("core {}: {}", core, smp::status(core)),
+                        }
+                    }
+                } else if cmd_name == "exec" {
+                    #[cfg(not(test))]
+                    exec(&cmd.args[1..]);
                 } else if cmd_name == "exit" {
                     // Built-in exit: leave the shell.
                     kprintln!("exited.");
@@ -0,0 +1,161 @@
+use core::fmt;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+/// The number of cores.
+pub const NUM_CORES: usize = 4;
+
+/// The size of the stack each of cores 1-3 gets.
+pub const STACK_SIZE: usize = 64 * 1024;
+
+/// Where each core's stack starts. `init.s` loads cores 1-3's stack pointers
+/// from here, with the MMU and caches still off.
+#[no_mangle]
+static SMP_STACK_TOPS: [AtomicUsize; NUM_CORES] = [const { AtomicUsize::new(0) }; NUM_CORES];
+
+/// What a core is up to.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// Still held by the firmware.
+    Parked = 0,
+    /// Released from the spin table, but not reported in yet.
+    Waking = 1,
+    /// Running the kernel.
+    Running = 2,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Status::Parked => "parked",
+            Status::Waking => "waking",
+            Status::Running => "running",
+        })
+    }
+}
+
+/// Each core's status. Core 0 is the one that boots the kernel.
+static STATUS: [AtomicU8; NUM_CORES] = [
+    AtomicU8::new(Status::Running as u8),
+    AtomicU8::new(Status::Parked as u8),
+    AtomicU8::new(Status::Parked as u8),
+    AtomicU8::new(Status::Parked as u8),
+];
+
+/// Returns the status of `core`.
+///
+/// # Panics
+///
+/// Panics if `core` isn't less than `NUM_CORES`.
+pub fn status(core: usize) -> Status {
+    match STATUS[core].load(Ordering::Acquire) {
+        0 => Status::Parked,
+        1 => Status::Waking,
+        _ => Status::Running,
+    }
+}
+
+/// Returns the stack `core` got, as its lowest address and size, if it was
+/// woken.
+pub fn stack(core: usize) -> Option<(usize, usize)> {
+    match SMP_STACK_TOPS[core].load(Ordering::Relaxed) {
+        0 => None,
+        top => Some((top - STACK_SIZE, STACK_SIZE)),
+    }
+}
+
+#[cfg(not(test))]
+pub use self::wake::{initialize, report_in};
+
+#[cfg(not(test))]
+mod wake {
+    use alloc::alloc::{alloc, Layout};
+    use core::arch::asm;
+    use core::sync::atomic::Ordering;
+    use core::time::Duration;
+
+    use super::{status, Status, NUM_CORES, SMP_STACK_TOPS, STACK_SIZE, STATUS};
+    use crate::boot_args::LogLevel;
+    use crate::console::klog;
+
+    /// Where the firmware's spin table starts: core `n` waits for an entry
+    /// address at `SPIN_TABLE_BASE + 8 * n`. Core 0's entry is unused.
+    const SPIN_TABLE_BASE: usize = 0xd8;
+
+    /// How long to wait for the woken cores to report in.
+    const WAKE_TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// The size of a cache line on the Cortex-A53.
+    const CACHE_LINE_SIZE: usize = 64;
+
+    /// Writes the cache line holding `addr` back to memory, where a core with
+    /// its caches still off can see it, and evicts it.
+    fn clean(addr: usize) {
+        unsafe { asm!("dc civac, {}", in(reg) addr) };
+    }
+
+    /// Cleans and evicts every cache line in `start..start + len`.
+    fn clean_range(start: usize, len: usize) {
+        let start = start & !(CACHE_LINE_SIZE - 1);
+        for line in (start..start + len).step_by(CACHE_LINE_SIZE) {
+            clean(line);
+        }
+    }
+
+    /// Gives cores 1-3 a stack each and releases them from the firmware's spin
+    /// table into `_start`, which sets them up like core 0 and calls
+    /// `kinit_secondary`. Waits up to `WAKE_TIMEOUT` for them to report in.
+    ///
+    /// # Safety
+    ///
+    /// Must be called once, by core 0, once the allocator is initialized.
+    pub unsafe fn initialize() {
+        extern "C" {
+            fn _start();
+        }
+
+        for (core, stack_top) in SMP_STACK_TOPS.iter().enumerate().skip(1) {
+            let layout = Layout::from_size_align(STACK_SIZE, 16).unwrap();
+            let stack = alloc(layout);
+            if stack.is_null() {
+                klog!(LogLevel::Warn, "no memory for core {}'s stack", core);
+                continue;
+            }
+
+            // The core uses its stack before turning on its caches. Make sure
+            // no stale line of ours gets written back over it later.
+            clean_range(stack as usize, STACK_SIZE);
+            stack_top.store(stack as usize + STACK_SIZE, Ordering::Relaxed);
+            clean(stack_top as *const _ as usize);
+            set_status(core, Status::Waking);
+
+            let entry = (SPIN_TABLE_BASE as *mut u64).add(core);
+            entry.write_volatile(_start as usize as u64);
+            clean(entry as usize);
+        }
+
+        asm!("dsb sy", "sev");
+
+        let deadline = pi::timer::current_time() + WAKE_TIMEOUT;
+        while (1..NUM_CORES).any(|core| status(core) == Status::Waking)
+            && pi::timer::current_time() < deadline
+        {
+            core::hint::spin_loop();
+        }
+
+        for core in (1..NUM_CORES).filter(|&core| status(core) != Status::Running) {
+            klog!(LogLevel::Warn, "core {} didn't report in", core);
+        }
+    }
+
+    fn set_status(core: usize, status: Status) {
+        STATUS[core].store(status as u8, Ordering::Release);
+    }
+
+    /// Marks this core, one of 1-3, as running. Called by `kinit_secondary`
+    /// once the core's MMU is on.
+    pub fn report_in(core: usize) {
+        set_status(core, Status::Running);
+        klog!(LogLevel::Info, "core {} is up", core);
+    }
+}
@@ -0,0 +1,69 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+/// The time between two timer interrupts.
+pub const TICK: Duration = Duration::from_millis(10);
+
+/// The number of timer interrupts since the tick started.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// The number of timer interrupts that found the core idle.
+static IDLE_TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the number of ticks since the tick started.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Returns the number of ticks that found the core running the idle process,
+/// which waits for interrupts when no other process is ready.
+pub fn idle_ticks() -> u64 {
+    IDLE_TICKS.load(Ordering::Relaxed)
+}
+
+/// Counts a tick, as idle if it interrupted the idle process.
+#[cfg(not(test))]
+fn count_tick(tf: &crate::traps::TrapFrame) {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    if tf.tpidr == crate::process::IDLE_ID {
+        IDLE_TICKS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Starts the periodic timer interrupt on this core, using the timer chosen by
+/// the `timer` boot argument. Each interrupt advances `ticks()` by one and
+/// gives the scheduler a chance to switch processes.
+#[cfg(not(test))]
+pub fn initialize() {
+    use alloc::boxed::Box;
+    use pi::interrupt::{Controller, Interrupt, LocalController, LocalInterrupt};
+    use pi::timer::{self, generic, Channel, Timer};
+
+    use crate::boot_args::{self, TimerSource};
+    use crate::traps::{self, IRQ};
+    use crate::SCHEDULER;
+
+    match boot_args::get().timer {
+        TimerSource::System => {
+            IRQ.register(Interrupt::Timer1, Box::new(|tf| {
+                let mut timer = Timer::new();
+                timer.clear_match(Channel::One);
+                timer.tick_in(Channel::One, TICK);
+                count_tick(tf);
+                SCHEDULER.tick(tf);
+            }));
+            Controller::new().enable(Interrupt::Timer1);
+            timer::tick_in(TICK);
+        }
+        TimerSource::Generic => {
+            IRQ.register_local(LocalInterrupt::CntPns, Box::new(|tf| {
+                // Setting up the next match deasserts this one.
+                generic::tick_in(TICK);
+                count_tick(tf);
+                SCHEDULER.tick(tf);
+            }));
+            LocalController::new(traps::core()).enable_timer(LocalInterrupt::CntPns);
+            generic::tick_in(TICK);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+mod frame;
+pub mod irq;
+mod syndrome;
+mod syscall;
+
+use core::arch::asm;
+
+use crate::boot_args::LogLevel;
+use crate::console::{klog, kprintln};
+use crate::SCHEDULER;
+
+pub use self::frame::TrapFrame;
+pub use self::irq::IRQ;
+pub use self::syndrome::{Fault, Syndrome};
+
+/// The kind of an exception, i.e., which of a source's four vectors was taken.
+#[repr(u16)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Kind {
+    Synchronous = 0,
+    Irq = 1,
+    Fiq = 2,
+    SError = 3,
+}
+
+/// Where an exception was taken from.
+#[repr(u16)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Source {
+    CurrentSpEl0 = 0,
+    CurrentSpElx = 1,
+    LowerAArch64 = 2,
+    LowerAArch32 = 3,
+}
+
+/// Information about an exception, as passed in `x0` by the vector.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Info {
+    pub source: Source,
+    pub kind: Kind,
+}
+
+/// Returns the faulting virtual address of the last abort.
+fn far() -> u64 {
+    let far: u64;
+    unsafe { asm!("mrs {}, FAR_EL1", out(reg) far) };
+    far
+}
+
+/// Returns the number of the core this runs on.
+pub fn core() -> usize {
+    let mpidr: u64;
+    unsafe { asm!("mrs {}, MPIDR_EL1", out(reg) mpidr) };
+    (mpidr & 0b11) as usize
+}
+
+/// Resets the interrupt controllers, routing their interrupts to this core,
+/// and unmasks IRQs. Handlers can then be registered with `IRQ`.
+pub fn initialize() {
+    irq::initialize(core());
+    unsafe { asm!("msr DAIFClr, #0b0010") };
+}
+
+/// Stops this core for good.
+fn halt() -> ! {
+    loop {
+        unsafe { asm!("wfe") };
+    }
+}
+
+/// This function is called when an exception occurs. The `info` parameter
+/// specifies the source and kind of exception that has occurred. The `esr` is
+/// the value of the exception syndrome register. Finally, `tf` is a pointer to
+/// the trap frame for the exception; changes to it take effect on return.
+#[no_mangle]
+pub extern "C" fn handle_exception(info: Info, esr: u32, tf: &mut TrapFrame) {
+    if info.kind == Kind::Irq {
+        irq::dispatch(core(), tf);
+        return;
+    }
+
+    if info.kind != Kind::Synchronous {
+        kprintln!("{:?} exception from {:?} at {:#x}", info.kind, info.source, tf.elr);
+        return;
+    }
+
+    let syndrome = Syndrome::from(esr);
+    if info.source == Source::LowerAArch64 {
+        handle_user_exception(syndrome, tf);
+        return;
+    }
+
+    // Kernel processes, which run on `SP_EL0`, make system calls too.
+    if let (Source::CurrentSpEl0, Syndrome::Svc(num)) = (info.source, syndrome) {
+        syscall::handle(num, tf);
+        return;
+    }
+
+    kprintln!("{:?} from {:?} at {:#x}", syndrome, info.source, tf.elr);
+    match syndrome {
+        // `svc` already advanced `ELR`, so there's nothing to do yet.
+        Syndrome::Svc(_) => {}
+        // `brk` doesn't: skip it to continue after the breakpoint.
+        Syndrome::Brk(_) => tf.elr += 4,
+        Syndrome::InstructionAbort { .. } | Syndrome::DataAbort { .. } => {
+            kprintln!("faulting address: {:#x}", far());
+            kprintln!("{:#x?}", tf);
+            halt();
+        }
+        _ => {
+            kprintln!("{:#x?}", tf);
+            halt();
+        }
+    }
+}
+
+/// Handles a synchronous exception from a user process: a system call, or a
+/// translation fault in its part of the address space, which gets it a new
+/// page. Anything else kills it.
+fn handle_user_exception(syndrome: Syndrome, tf: &mut TrapFrame) {
+    let far = far() as usize;
+    match syndrome {
+        Syndrome::Svc(num) => syscall::handle(num, tf),
+        Syndrome::InstructionAbort { kind: Fault::Translation, .. }
+        | Syndrome::DataAbort { kind: Fault::Translation, .. }
+            if SCHEDULER.with_process(tf.tpidr, |process| process.page_fault(far)) == Some(true) => {}
+        _ => {
+            let elr = tf.elr;
+            let id = SCHEDULER.kill(tf);
+            klog!(LogLevel::Warn, "process {} killed: {:?} at {:#x}, faulting address {:#x}",
+                  id, syndrome, elr, far);
+        }
+    }
+}
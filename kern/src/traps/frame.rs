@@ -0,0 +1,22 @@
+/// The state of the code that was running when an exception was taken, saved
+/// on the stack by `context_save` in `init.s` and restored from it by
+/// `context_restore`. The field offsets are hard-coded there.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug)]
+pub struct TrapFrame {
+    /// General purpose registers `x0` to `x30` (`lr`).
+    pub x: [u64; 31],
+    /// The stack pointer of EL0, `SP_EL0`.
+    pub sp: u64,
+    /// The address to return to, `ELR_EL1`.
+    pub elr: u64,
+    /// The saved program status, `SPSR_EL1`.
+    pub spsr: u64,
+    /// The EL0 thread ID register, `TPIDR_EL0`.
+    pub tpidr: u64,
+    _reserved: u64,
+    /// SIMD/FP registers `q0` to `q31`.
+    pub q: [u128; 32],
+}
+
+const _: () = assert!(core::mem::size_of::<TrapFrame>() == 800);
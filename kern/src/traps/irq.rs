@@ -0,0 +1,107 @@
+use alloc::boxed::Box;
+
+use pi::interrupt::{Controller, Interrupt, LocalController, LocalInterrupt};
+
+use crate::console::kprintln;
+use crate::mutex::IrqMutex;
+use crate::traps::TrapFrame;
+
+/// A function called with the interrupted context's trap frame whenever the
+/// interrupt it's registered for is pending.
+pub type IrqHandler = Box<dyn FnMut(&mut TrapFrame) + Send>;
+
+/// The handlers registered for each interrupt.
+pub struct Irq {
+    handlers: IrqMutex<[Option<IrqHandler>; Interrupt::MAX]>,
+    local_handlers: IrqMutex<[Option<IrqHandler>; LocalInterrupt::MAX]>,
+}
+
+impl Irq {
+    /// Returns a registry without any handlers.
+    pub const fn empty() -> Irq {
+        Irq {
+            handlers: IrqMutex::new([const { None }; Interrupt::MAX]),
+            local_handlers: IrqMutex::new([const { None }; LocalInterrupt::MAX]),
+        }
+    }
+
+    /// Registers `handler` for the interrupt `int`, replacing any previous
+    /// handler. The interrupt still has to be enabled in the `Controller`.
+    pub fn register(&self, int: Interrupt, handler: IrqHandler) {
+        self.handlers.lock()[int.to_index()] = Some(handler);
+    }
+
+    /// Registers `handler` for the local interrupt `int`, replacing any
+    /// previous handler.
+    pub fn register_local(&self, int: LocalInterrupt, handler: IrqHandler) {
+        self.local_handlers.lock()[int.to_index()] = Some(handler);
+    }
+
+    /// Calls the handler registered for `int`. Returns `false` if there is
+    /// none.
+    pub fn invoke(&self, int: Interrupt, tf: &mut TrapFrame) -> bool {
+        match self.handlers.lock()[int.to_index()] {
+            Some(ref mut handler) => {
+                handler(tf);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Calls the handler registered for the local interrupt `int`. Returns
+    /// `false` if there is none.
+    pub fn invoke_local(&self, int: LocalInterrupt, tf: &mut TrapFrame) -> bool {
+        match self.local_handlers.lock()[int.to_index()] {
+            Some(ref mut handler) => {
+                handler(tf);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The kernel's interrupt handlers.
+pub static IRQ: Irq = Irq::empty();
+
+/// Calls the handlers of every interrupt pending on `core`. Interrupts without
+/// a handler are disabled so that they don't fire again.
+pub fn dispatch(core: usize, tf: &mut TrapFrame) {
+    let mut local = LocalController::new(core);
+    for int in LocalInterrupt::iter() {
+        if !local.is_pending(int) {
+            continue;
+        }
+
+        if int == LocalInterrupt::Gpu {
+            dispatch_gpu(tf);
+        } else if !IRQ.invoke_local(int, tf) {
+            kprintln!("unhandled local interrupt {:?} on core {}", int, core);
+            local.disable(int);
+        }
+    }
+}
+
+/// Calls the handlers of the ARM interrupt controller's pending interrupts.
+fn dispatch_gpu(tf: &mut TrapFrame) {
+    let mut controller = Controller::new();
+    for int in Interrupt::iter() {
+        if controller.is_pending(int) && !IRQ.invoke(int, tf) {
+            kprintln!("unhandled interrupt {:?}", int);
+            controller.disable(int);
+        }
+    }
+}
+
+/// Disables every interrupt in the interrupt controllers and routes the ARM
+/// interrupt controller's interrupts to `core`.
+pub fn initialize(core: usize) {
+    Controller::new().disable_all();
+
+    let mut local = LocalController::new(core);
+    for int in LocalInterrupt::iter() {
+        local.disable(int);
+    }
+    local.route_gpu_irqs();
+}
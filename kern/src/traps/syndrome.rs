@@ -0,0 +1,65 @@
+/// The cause of an instruction or data abort, from the fault status code.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Fault {
+    AddressSize,
+    Translation,
+    AccessFlag,
+    Permission,
+    Alignment,
+    TlbConflict,
+    Other(u8),
+}
+
+impl From<u32> for Fault {
+    /// Decodes the fault status code in the low 6 bits of `val`, an abort's
+    /// ISS (ref: D12.2.36).
+    fn from(val: u32) -> Fault {
+        use self::Fault::*;
+
+        match (val & 0b111111) as u8 {
+            0b000000..=0b000011 => AddressSize,
+            0b000100..=0b000111 => Translation,
+            0b001000..=0b001011 => AccessFlag,
+            0b001100..=0b001111 => Permission,
+            0b100001 => Alignment,
+            0b110000 => TlbConflict,
+            code => Other(code),
+        }
+    }
+}
+
+/// The reason for a synchronous exception, decoded from `ESR_EL1`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Syndrome {
+    /// An exception with no other class, e.g. an undefined instruction.
+    Unknown,
+    /// An instruction fetch failed at translation table `level`.
+    InstructionAbort { kind: Fault, level: u8 },
+    /// A load or store failed at translation table `level`.
+    DataAbort { kind: Fault, level: u8 },
+    /// An `svc #imm` instruction.
+    Svc(u16),
+    /// A `brk #imm` instruction.
+    Brk(u16),
+    /// Any other exception class, not decoded further.
+    Other(u32),
+}
+
+impl From<u32> for Syndrome {
+    /// Decodes the exception syndrome register value `esr` (ref: D12.2.36).
+    fn from(esr: u32) -> Syndrome {
+        use self::Syndrome::*;
+
+        let class = esr >> 26;
+        let iss = esr & 0x1FF_FFFF;
+        let level = (iss & 0b11) as u8;
+        match class {
+            0b000000 => Unknown,
+            0b100000 | 0b100001 => InstructionAbort { kind: Fault::from(iss), level },
+            0b100100 | 0b100101 => DataAbort { kind: Fault::from(iss), level },
+            0b010101 => Svc(iss as u16),
+            0b111100 => Brk(iss as u16),
+            _ => Other(esr),
+        }
+    }
+}
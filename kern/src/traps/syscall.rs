@@ -0,0 +1,196 @@
+use alloc::boxed::Box;
+use core::str;
+use core::time::Duration;
+
+use kernel_api::*;
+use pi::timer::current_time;
+
+use crate::fs::File;
+use crate::process::{Process, State};
+use crate::traps::TrapFrame;
+use crate::SCHEDULER;
+
+/// A system call handler. It gets the caller's trap frame, with the arguments
+/// in `x0` to `x5`.
+type Handler = fn(&mut TrapFrame) -> Status;
+
+/// What a system call handler did.
+enum Status {
+    /// Finished, with this result for the caller.
+    Done(OsResult<u64>),
+    /// Switched to another process. The caller gets its result, if any, when
+    /// it's woken.
+    Switched,
+}
+
+impl From<OsResult<u64>> for Status {
+    fn from(result: OsResult<u64>) -> Status {
+        Status::Done(result)
+    }
+}
+
+/// The system calls, by number.
+const SYSCALLS: [(u16, Handler); 10] = [
+    (NR_SLEEP, sys_sleep),
+    (NR_TIME, sys_time),
+    (NR_EXIT, sys_exit),
+    (NR_WRITE, sys_write),
+    (NR_GETPID, sys_getpid),
+    (NR_OPEN, sys_open),
+    (NR_READ, sys_read),
+    (NR_CLOSE, sys_close),
+    (NR_SBRK, sys_sbrk),
+    (NR_WAIT, sys_wait),
+];
+
+/// Handles the system call `num` made by the process `tf` belongs to.
+pub fn handle(num: u16, tf: &mut TrapFrame) {
+    let status = match SYSCALLS.iter().find(|&&(nr, _)| nr == num) {
+        Some(&(_, handler)) => handler(tf),
+        None => Status::Done(Err(OsError::NoSyscall)),
+    };
+
+    if let Status::Done(result) = status {
+        set_result(tf, result);
+    }
+}
+
+/// Sets the registers a system call returns `result` in.
+fn set_result(tf: &mut TrapFrame, result: OsResult<u64>) {
+    match result {
+        Ok(value) => {
+            tf.x[0] = value;
+            tf.x[7] = 0;
+        }
+        Err(e) => tf.x[7] = e.code(),
+    }
+}
+
+/// Calls `f` with the process `tf` belongs to.
+fn with_caller<F, R>(tf: &TrapFrame, f: F) -> OsResult<R>
+where
+    F: FnOnce(&mut Process) -> OsResult<R>,
+{
+    SCHEDULER.with_process(tf.tpidr, f).unwrap_or(Err(OsError::Unknown))
+}
+
+/// `sleep(ms) -> elapsed ms`: switches away from the caller until `ms`
+/// milliseconds have passed.
+fn sys_sleep(tf: &mut TrapFrame) -> Status {
+    let ms = match u32::try_from(tf.x[0]) {
+        Ok(ms) => ms,
+        Err(_) => return Status::Done(Err(OsError::InvalidArgument)),
+    };
+
+    let start = current_time();
+    let deadline = start + Duration::from_millis(ms.into());
+    SCHEDULER.switch(State::Waiting(Box::new(move |process| {
+        let now = current_time();
+        if now < deadline {
+            return false;
+        }
+
+        set_result(&mut process.context, Ok((now - start).as_millis() as u64));
+        true
+    })), tf);
+    Status::Switched
+}
+
+/// `time() -> µs`: the time since boot.
+fn sys_time(_tf: &mut TrapFrame) -> Status {
+    Status::Done(Ok(current_time().as_micros() as u64))
+}
+
+/// `exit() -> !`: kills the caller.
+fn sys_exit(tf: &mut TrapFrame) -> Status {
+    SCHEDULER.kill(tf);
+    Status::Switched
+}
+
+/// `write(fd, buf, len) -> written`.
+fn sys_write(tf: &mut TrapFrame) -> Status {
+    let (fd, buf, len) = (tf.x[0], tf.x[1], tf.x[2]);
+    with_caller(tf, |process| {
+        // The caller's page table is in use while it makes the call.
+        let buf = unsafe { process.buffer(buf, len)? };
+        process.file(fd)?.write(buf).map(|written| written as u64)
+    }).into()
+}
+
+/// `getpid() -> pid`.
+fn sys_getpid(tf: &mut TrapFrame) -> Status {
+    Status::Done(Ok(tf.tpidr))
+}
+
+/// `open(path, len) -> fd`.
+fn sys_open(tf: &mut TrapFrame) -> Status {
+    let (path, len) = (tf.x[0], tf.x[1]);
+    with_caller(tf, |process| {
+        let path = unsafe { process.buffer(path, len)? };
+        let path = str::from_utf8(path).map_err(|_| OsError::InvalidArgument)?;
+        process.add_file(File::open(path)?)
+    }).into()
+}
+
+/// `read(fd, buf, len) -> read`. If there's no input yet, the caller waits
+/// for some and then makes the call again.
+fn sys_read(tf: &mut TrapFrame) -> Status {
+    let (fd, buf, len) = (tf.x[0], tf.x[1], tf.x[2]);
+    let read = with_caller(tf, |process| {
+        let buf = unsafe { process.buffer(buf, len)? };
+        let file = process.file(fd)?;
+        if !file.can_read() {
+            return Ok(None);
+        }
+
+        file.read(buf).map(|read| Some(read as u64))
+    });
+
+    match read {
+        Ok(Some(read)) => Status::Done(Ok(read)),
+        Ok(None) => {
+            // Back up to the `svc`, to make the call again once woken.
+            tf.elr -= 4;
+            SCHEDULER.switch(State::Waiting(Box::new(move |process| {
+                process.file(fd).map_or(true, |file| file.can_read())
+            })), tf);
+            Status::Switched
+        }
+        Err(e) => Status::Done(Err(e)),
+    }
+}
+
+/// `close(fd)`.
+fn sys_close(tf: &mut TrapFrame) -> Status {
+    let fd = tf.x[0];
+    with_caller(tf, |process| process.close_file(fd).map(|_| 0)).into()
+}
+
+/// `sbrk(increment) -> previous break`.
+fn sys_sbrk(tf: &mut TrapFrame) -> Status {
+    let increment = tf.x[0] as isize;
+    with_caller(tf, |process| process.sbrk(increment).map(|brk| brk as u64)).into()
+}
+
+/// `wait(pid) -> pid`: switches away from the caller until its child `pid`
+/// has exited, unless it already has.
+fn sys_wait(tf: &mut TrapFrame) -> Status {
+    let (child, caller) = (tf.x[0], tf.tpidr);
+    let running = SCHEDULER.with_process(child, |process| process.parent == Some(caller)) == Some(true);
+    match with_caller(tf, |process| Ok(process.reap(child))) {
+        Ok(true) => return Status::Done(Ok(child)),
+        Ok(false) if running => {}
+        Ok(false) => return Status::Done(Err(OsError::NoProcess)),
+        Err(e) => return Status::Done(Err(e)),
+    }
+
+    SCHEDULER.switch(State::Waiting(Box::new(move |process| {
+        if !process.reap(child) {
+            return false;
+        }
+
+        set_result(&mut process.context, Ok(child));
+        true
+    })), tf);
+    Status::Switched
+}
@@ -0,0 +1,57 @@
+#[cfg(not(test))]
+mod mmu;
+mod pagetable;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::pagetable::*;
+
+#[cfg(not(test))]
+pub use self::mmu::{is_enabled, set_ttbr0, sync_icache};
+
+/// The kernel's identity-mapped page table. It lives in the BSS, so it's only
+/// usable once that's zeroed.
+#[cfg(not(test))]
+static mut KERN_PAGE_TABLE: KernPageTable = KernPageTable {
+    l2: L2Table { entries: [RawEntry::INVALID; ENTRIES] },
+    l3: L3Table { entries: [RawEntry::INVALID; ENTRIES] },
+};
+
+/// Builds the kernel's page table and turns on the MMU and caches on this
+/// core with it.
+///
+/// # Safety
+///
+/// Must be called once, by one core, after the BSS is zeroed and before
+/// anything relies on caches or exclusive loads and stores.
+#[cfg(not(test))]
+pub unsafe fn initialize() {
+    let table = &mut *core::ptr::addr_of_mut!(KERN_PAGE_TABLE);
+    table.fill();
+    mmu::enable(table);
+}
+
+/// Turns on the MMU and caches on this core with the kernel's page table, for
+/// the cores `smp::initialize` wakes.
+///
+/// # Safety
+///
+/// `initialize` must have been called.
+#[cfg(not(test))]
+pub unsafe fn enable() {
+    mmu::enable(kern_page_table());
+}
+
+/// Returns the kernel's page table, whose mappings user page tables share.
+/// It's only filled in once `initialize` has been called.
+#[cfg(not(test))]
+pub fn kern_page_table() -> &'static KernPageTable {
+    unsafe { &*core::ptr::addr_of!(KERN_PAGE_TABLE) }
+}
+
+/// Host tests run as if the MMU were on.
+#[cfg(test)]
+pub fn is_enabled() -> bool {
+    true
+}
@@ -0,0 +1,83 @@
+use core::arch::asm;
+
+use super::{KernPageTable, MemoryAttr, VA_BITS};
+
+/// `MAIR_EL1`, with each `MemoryAttr`'s attributes at its index.
+const MAIR: u64 = (0xFF << (8 * MemoryAttr::Normal as u64))
+    | (0x04 << (8 * MemoryAttr::Device as u64))
+    | (0x44 << (8 * MemoryAttr::NonCacheable as u64));
+
+/// `TCR_EL1` without the physical address size: a `VA_BITS` address space
+/// through `TTBR0_EL1` with the 64 KiB granule, walked through inner
+/// shareable, write-back cacheable memory. Walks through `TTBR1_EL1` are off.
+const TCR: u64 = (64 - VA_BITS as u64) // T0SZ
+    | (0b01 << 8) // IRGN0: write-back, write-allocate
+    | (0b01 << 10) // ORGN0: write-back, write-allocate
+    | (0b11 << 12) // SH0: inner shareable
+    | (0b01 << 14) // TG0: 64 KiB
+    | (1 << 23) // EPD1
+    | (0b11 << 30); // TG1: 64 KiB
+
+/// Where the physical address size goes in `TCR_EL1`.
+const TCR_IPS_SHIFT: u64 = 32;
+
+/// `SCTLR_EL1` bits: the MMU, and the data and instruction caches.
+const SCTLR_M: u64 = 1 << 0;
+const SCTLR_C: u64 = 1 << 2;
+const SCTLR_I: u64 = 1 << 12;
+
+/// Loads `table` into this core's translation registers and turns on the MMU
+/// and caches.
+///
+/// # Safety
+///
+/// `table` must identity-map the code and data in use.
+pub unsafe fn enable(table: &KernPageTable) {
+    let mmfr0: u64;
+    asm!("mrs {}, ID_AA64MMFR0_EL1", out(reg) mmfr0);
+    let tcr = TCR | ((mmfr0 & 0b111) << TCR_IPS_SHIFT);
+
+    // Make the table's writes visible to the walker before it starts.
+    asm!("dsb ish", "isb");
+    asm!("msr MAIR_EL1, {}", in(reg) MAIR);
+    asm!("msr TCR_EL1, {}", in(reg) tcr);
+    asm!("msr TTBR0_EL1, {}", in(reg) table.baddr());
+    asm!("isb", "tlbi vmalle1", "dsb ish", "isb");
+
+    let mut sctlr: u64;
+    asm!("mrs {}, SCTLR_EL1", out(reg) sctlr);
+    sctlr |= SCTLR_M | SCTLR_C | SCTLR_I;
+    asm!("msr SCTLR_EL1, {}", "isb", in(reg) sctlr);
+}
+
+/// Switches this core's `TTBR0_EL1` to the table at `baddr` and discards the
+/// translations cached for the previous one.
+///
+/// # Safety
+///
+/// The table must map the kernel like `KERN_PAGE_TABLE` does.
+pub unsafe fn set_ttbr0(baddr: usize) {
+    asm!("dsb ish", "msr TTBR0_EL1, {}", "isb", "tlbi vmalle1", "dsb ish", "isb", in(reg) baddr);
+}
+
+/// Makes code written to `start..start + len` through the data cache visible
+/// to this core's instruction fetches.
+pub fn sync_icache(start: usize, len: usize) {
+    let ctr: u64;
+    unsafe { asm!("mrs {}, CTR_EL0", out(reg) ctr) };
+    // The smallest data cache line, in words.
+    let line = 4usize << ((ctr >> 16) & 0xF);
+
+    for addr in (start & !(line - 1)..start + len).step_by(line) {
+        unsafe { asm!("dc cvau, {}", in(reg) addr) };
+    }
+    unsafe { asm!("dsb ish", "ic iallu", "dsb ish", "isb") };
+}
+
+/// Returns `true` once the MMU and caches are on on this core, and with them
+/// exclusive loads and stores.
+pub fn is_enabled() -> bool {
+    let sctlr: u64;
+    unsafe { asm!("mrs {}, SCTLR_EL1", out(reg) sctlr) };
+    sctlr & SCTLR_M != 0
+}
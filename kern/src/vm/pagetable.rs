@@ -0,0 +1,343 @@
+use alloc::alloc::{alloc_zeroed, dealloc, Layout};
+use alloc::boxed::Box;
+use core::fmt;
+use core::slice;
+
+use pi::common::IO_BASE;
+
+/// The translation granule: the size of a page and of a page table.
+pub const PAGE_SIZE: usize = 64 * 1024;
+
+/// The size of the region an L2 entry maps: 8192 pages.
+pub const BLOCK_SIZE: usize = ENTRIES * PAGE_SIZE;
+
+/// The number of entries in a page table.
+pub const ENTRIES: usize = PAGE_SIZE / 8;
+
+/// The number of bits of virtual address space. Two gigabytes cover RAM, the
+/// peripherals at `IO_BASE` and the local peripherals at 0x4000_0000.
+pub const VA_BITS: usize = 31;
+
+/// The end of the local peripherals, which start where the peripherals at
+/// `IO_BASE` end.
+pub const LOCAL_IO_END: usize = 0x4004_0000;
+
+/// Where user processes' part of the address space starts: the last L2
+/// block, which the kernel doesn't use.
+pub const USER_BASE: usize = (1 << VA_BITS) - BLOCK_SIZE;
+
+/// Where user processes' part of the address space ends.
+pub const USER_END: usize = 1 << VA_BITS;
+
+const _: () = assert!(USER_BASE >= LOCAL_IO_END);
+
+/// The index of each memory type's attributes in `MAIR_EL1`. `vm::enable`
+/// sets up `MAIR_EL1` to match.
+#[repr(u64)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MemoryAttr {
+    /// Normal memory, write-back cacheable.
+    Normal = 0,
+    /// Device-nGnRE memory, for memory-mapped I/O.
+    Device = 1,
+    /// Normal memory, not cacheable.
+    NonCacheable = 2,
+}
+
+/// Who may access a page and how. Only the kernel executes kernel memory and
+/// only user processes execute `UserRx` memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Permission {
+    KernelRw,
+    UserRw,
+    KernelRo,
+    UserRo,
+    UserRx,
+}
+
+impl Permission {
+    /// Returns the `AP[2:1]` bits for this permission.
+    fn ap(self) -> u64 {
+        match self {
+            Permission::KernelRw => 0b00,
+            Permission::UserRw => 0b01,
+            Permission::KernelRo => 0b10,
+            Permission::UserRo | Permission::UserRx => 0b11,
+        }
+    }
+}
+
+/// A stage 1 translation table descriptor for the 64 KiB granule.
+#[repr(transparent)]
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+pub struct RawEntry(u64);
+
+impl RawEntry {
+    const VALID: u64 = 1 << 0;
+    /// Set for table and page descriptors, clear for block descriptors.
+    const TABLE_OR_PAGE: u64 = 1 << 1;
+    const ATTR_SHIFT: u64 = 2;
+    const AP_SHIFT: u64 = 6;
+    const SH_INNER: u64 = 0b11 << 8;
+    const AF: u64 = 1 << 10;
+    const PXN: u64 = 1 << 53;
+    const UXN: u64 = 1 << 54;
+    const ADDR_MASK: u64 = 0x0000_FFFF_FFFF_0000;
+
+    /// An entry that maps nothing.
+    pub const INVALID: RawEntry = RawEntry(0);
+
+    /// Returns an L2 entry pointing to the L3 table at `addr`.
+    pub fn table(addr: usize) -> RawEntry {
+        RawEntry(Self::address_bits(addr) | Self::TABLE_OR_PAGE | Self::VALID)
+    }
+
+    /// Returns an L3 entry mapping the page at `addr`.
+    pub fn page(addr: usize, attr: MemoryAttr, perm: Permission) -> RawEntry {
+        RawEntry(Self::leaf(addr, attr, perm) | Self::TABLE_OR_PAGE)
+    }
+
+    /// Returns an L2 entry mapping the `BLOCK_SIZE` block at `addr`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `addr` isn't `BLOCK_SIZE` aligned.
+    pub fn block(addr: usize, attr: MemoryAttr, perm: Permission) -> RawEntry {
+        assert!(addr % BLOCK_SIZE == 0, "unaligned block: {:#x}", addr);
+        RawEntry(Self::leaf(addr, attr, perm))
+    }
+
+    /// The bits page and block descriptors share. Device memory is never
+    /// executable; its shareability is ignored. Kernel memory isn't executable
+    /// at EL0, user memory isn't at EL1, and only `UserRx` memory is at EL0.
+    fn leaf(addr: usize, attr: MemoryAttr, perm: Permission) -> u64 {
+        let mut bits = Self::address_bits(addr)
+            | ((attr as u64) << Self::ATTR_SHIFT)
+            | (perm.ap() << Self::AP_SHIFT)
+            | Self::AF
+            | Self::VALID;
+        match attr {
+            MemoryAttr::Device => bits |= Self::PXN | Self::UXN,
+            _ => bits |= Self::SH_INNER,
+        }
+        match perm {
+            Permission::KernelRw | Permission::KernelRo => bits |= Self::UXN,
+            Permission::UserRw | Permission::UserRo => bits |= Self::PXN | Self::UXN,
+            Permission::UserRx => bits |= Self::PXN,
+        }
+        bits
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `addr` isn't `PAGE_SIZE` aligned.
+    fn address_bits(addr: usize) -> u64 {
+        assert!(addr % PAGE_SIZE == 0, "unaligned address: {:#x}", addr);
+        addr as u64 & Self::ADDR_MASK
+    }
+
+    /// Returns `true` if this entry maps something.
+    pub fn is_valid(&self) -> bool {
+        self.0 & Self::VALID != 0
+    }
+
+    /// Returns `true` if this is an L2 block entry.
+    pub fn is_block(&self) -> bool {
+        self.is_valid() && self.0 & Self::TABLE_OR_PAGE == 0
+    }
+
+    /// Returns the physical address this entry maps or points to.
+    pub fn address(&self) -> usize {
+        (self.0 & Self::ADDR_MASK) as usize
+    }
+
+    /// Returns the memory type of a page or block entry.
+    pub fn attr(&self) -> MemoryAttr {
+        match (self.0 >> Self::ATTR_SHIFT) & 0b111 {
+            0 => MemoryAttr::Normal,
+            1 => MemoryAttr::Device,
+            _ => MemoryAttr::NonCacheable,
+        }
+    }
+
+    /// Returns the descriptor's bits.
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Debug for RawEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.is_valid() {
+            return f.write_str("RawEntry(invalid)");
+        }
+
+        f.debug_struct("RawEntry")
+            .field("address", &(self.address() as *const u8))
+            .field("block", &self.is_block())
+            .field("attr", &self.attr())
+            .finish()
+    }
+}
+
+/// An L2 page table. Its entries map `BLOCK_SIZE` blocks or point to L3
+/// tables.
+#[repr(C, align(65536))]
+pub struct L2Table {
+    pub entries: [RawEntry; ENTRIES],
+}
+
+impl L2Table {
+    /// Returns the physical address `va` maps to through this table, if it's
+    /// mapped. L3 tables are read at the addresses the entries hold, so they
+    /// must be identity-mapped.
+    pub fn translate(&self, va: usize) -> Option<usize> {
+        let l2 = self.entries.get(va / BLOCK_SIZE).filter(|e| e.is_valid())?;
+        if l2.is_block() {
+            return Some(l2.address() + va % BLOCK_SIZE);
+        }
+
+        let l3 = unsafe { &*(l2.address() as *const L3Table) };
+        let l3 = l3.entries[(va % BLOCK_SIZE) / PAGE_SIZE];
+        Some(l3.address() + va % PAGE_SIZE).filter(|_| l3.is_valid())
+    }
+}
+
+/// An L3 page table. Its entries map `PAGE_SIZE` pages.
+#[repr(C, align(65536))]
+pub struct L3Table {
+    pub entries: [RawEntry; ENTRIES],
+}
+
+/// The kernel's page table. It identity-maps RAM as normal memory and the
+/// peripherals from `IO_BASE` up to `LOCAL_IO_END` as device memory. With a
+/// 31-bit address space, translation starts at L2 and the L2 table only uses
+/// its first four entries: blocks for RAM below `BLOCK_SIZE` and for the
+/// local peripherals, and an L3 table for the block `IO_BASE` lies in.
+#[repr(C)]
+pub struct KernPageTable {
+    pub l2: L2Table,
+    pub l3: L3Table,
+}
+
+impl KernPageTable {
+    /// Fills in the identity map. Everything above `LOCAL_IO_END`'s block is
+    /// left unmapped.
+    pub fn fill(&mut self) {
+        self.l2.entries.fill(RawEntry::INVALID);
+
+        let io_block = IO_BASE / BLOCK_SIZE;
+        for (i, entry) in self.l2.entries.iter_mut().enumerate() {
+            let addr = i * BLOCK_SIZE;
+            if addr >= LOCAL_IO_END {
+                break;
+            }
+
+            *entry = if i == io_block {
+                RawEntry::table(&self.l3 as *const L3Table as usize)
+            } else if addr < IO_BASE {
+                RawEntry::block(addr, MemoryAttr::Normal, Permission::KernelRw)
+            } else {
+                RawEntry::block(addr, MemoryAttr::Device, Permission::KernelRw)
+            };
+        }
+
+        let base = io_block * BLOCK_SIZE;
+        for (i, entry) in self.l3.entries.iter_mut().enumerate() {
+            let addr = base + i * PAGE_SIZE;
+            let attr = if addr < IO_BASE { MemoryAttr::Normal } else { MemoryAttr::Device };
+            *entry = RawEntry::page(addr, attr, Permission::KernelRw);
+        }
+    }
+
+    /// Returns the address to load into `TTBR0_EL1`.
+    pub fn baddr(&self) -> usize {
+        &self.l2 as *const L2Table as usize
+    }
+
+    /// Returns the physical address `va` maps to, if it's mapped.
+    pub fn translate(&self, va: usize) -> Option<usize> {
+        self.l2.translate(va)
+    }
+}
+
+/// A user process's page table. It shares the kernel's mappings, which EL0
+/// can't access, and maps the process's own pages from `USER_BASE` to
+/// `USER_END` through its L3 table. The pages are freed with the table.
+#[repr(C)]
+pub struct UserPageTable {
+    pub l2: L2Table,
+    pub l3: L3Table,
+}
+
+impl UserPageTable {
+    /// Returns a table with the kernel's mappings from `kern` and no pages of
+    /// its own, or `None` if there's no memory for it.
+    pub fn new(kern: &KernPageTable) -> Option<Box<UserPageTable>> {
+        // Too big to build on the stack, and all zeroes is all invalid.
+        let table = unsafe { alloc_zeroed(Layout::new::<UserPageTable>()) as *mut UserPageTable };
+        if table.is_null() {
+            return None;
+        }
+
+        let mut table = unsafe { Box::from_raw(table) };
+        let user_block = USER_BASE / BLOCK_SIZE;
+        table.l2.entries[..user_block].copy_from_slice(&kern.l2.entries[..user_block]);
+        table.l2.entries[user_block] = RawEntry::table(&table.l3 as *const L3Table as usize);
+        Some(table)
+    }
+
+    fn page_layout() -> Layout {
+        Layout::from_size_align(PAGE_SIZE, PAGE_SIZE).unwrap()
+    }
+
+    /// Maps a new zeroed page at `va` with `perm` and returns its memory, or
+    /// `None` if there's no memory left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `va` isn't a page in `USER_BASE..USER_END` or is already
+    /// mapped.
+    pub fn alloc(&mut self, va: usize, perm: Permission) -> Option<&mut [u8]> {
+        assert!((USER_BASE..USER_END).contains(&va), "not a user address: {:#x}", va);
+        assert!(va % PAGE_SIZE == 0, "unaligned page: {:#x}", va);
+
+        let entry = &mut self.l3.entries[(va - USER_BASE) / PAGE_SIZE];
+        assert!(!entry.is_valid(), "page already mapped: {:#x}", va);
+
+        let page = unsafe { alloc_zeroed(Self::page_layout()) };
+        if page.is_null() {
+            return None;
+        }
+
+        *entry = RawEntry::page(page as usize, MemoryAttr::Normal, perm);
+        Some(unsafe { slice::from_raw_parts_mut(page, PAGE_SIZE) })
+    }
+
+    /// Returns the address to load into `TTBR0_EL1`.
+    pub fn baddr(&self) -> usize {
+        &self.l2 as *const L2Table as usize
+    }
+
+    /// Returns the physical address `va` maps to, if it's mapped.
+    pub fn translate(&self, va: usize) -> Option<usize> {
+        self.l2.translate(va)
+    }
+}
+
+impl fmt::Debug for UserPageTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UserPageTable")
+            .field("baddr", &(self.baddr() as *const u8))
+            .field("pages", &self.l3.entries.iter().filter(|e| e.is_valid()).count())
+            .finish()
+    }
+}
+
+impl Drop for UserPageTable {
+    fn drop(&mut self) {
+        for entry in self.l3.entries.iter().filter(|e| e.is_valid()) {
+            unsafe { dealloc(entry.address() as *mut u8, Self::page_layout()) };
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use alloc::alloc::{alloc_zeroed, Layout};
+use alloc::boxed::Box;
+
+use pi::common::IO_BASE;
+
+use super::*;
+
+fn kern_page_table() -> Box<KernPageTable> {
+    // Too big for the test thread's stack, and all zeroes is all invalid.
+    let layout = Layout::new::<KernPageTable>();
+    let mut table = unsafe { Box::from_raw(alloc_zeroed(layout) as *mut KernPageTable) };
+    table.fill();
+    table
+}
+
+#[test]
+fn test_entry_encoding() {
+    let page = RawEntry::page(0x8_0000, MemoryAttr::Normal, Permission::KernelRw);
+    assert_eq!(page.bits(), 0x8_0000 | (1 << 54) | (0b11 << 8) | (1 << 10) | 0b11);
+    assert_eq!(page.address(), 0x8_0000);
+    assert!(page.is_valid() && !page.is_block());
+
+    let block = RawEntry::block(2 * BLOCK_SIZE, MemoryAttr::Device, Permission::KernelRw);
+    assert_eq!(block.bits(), 0x4000_0000 | (1 << 54) | (1 << 53) | (1 << 10) | (1 << 2) | 0b01);
+    assert!(block.is_block());
+    assert_eq!(block.attr(), MemoryAttr::Device);
+
+    let user = RawEntry::page(0, MemoryAttr::NonCacheable, Permission::UserRo);
+    assert_eq!(user.bits() & (0b11 << 6), 0b11 << 6);
+    assert_eq!(user.bits() & (0b11 << 53), 0b11 << 53);
+    assert_eq!(user.attr(), MemoryAttr::NonCacheable);
+
+    let code = RawEntry::page(0, MemoryAttr::Normal, Permission::UserRx);
+    assert_eq!(code.bits() & (0b11 << 6), 0b11 << 6);
+    assert_eq!(code.bits() & (0b11 << 53), 1 << 53);
+
+    assert!(!RawEntry::INVALID.is_valid());
+    assert_eq!(RawEntry::table(0x10_0000).bits(), 0x10_0000 | 0b11);
+}
+
+#[test]
+#[should_panic]
+fn test_unaligned_page() {
+    RawEntry::page(0x8_1000, MemoryAttr::Normal, Permission::KernelRw);
+}
+
+#[test]
+#[should_panic]
+fn test_unaligned_block() {
+    RawEntry::block(PAGE_SIZE, MemoryAttr::Normal, Permission::KernelRw);
+}
+
+#[test]
+fn test_identity_map() {
+    let table = kern_page_table();
+    for &va in &[0, 0x8_0000, BLOCK_SIZE, IO_BASE - 1, IO_BASE, IO_BASE + 0x21_5040, 0x4000_0000, 0x4000_0060] {
+        assert_eq!(table.translate(va), Some(va), "{:#x}", va);
+    }
+
+    assert_eq!(table.translate(0x6000_0000), None);
+    assert_eq!(table.translate(1 << VA_BITS), None);
+    assert!(table.l2.entries[3..].iter().all(|e| !e.is_valid()));
+}
+
+#[test]
+fn test_memory_types() {
+    let table = kern_page_table();
+    assert!(table.l2.entries[0].is_block());
+    assert_eq!(table.l2.entries[0].attr(), MemoryAttr::Normal);
+    assert!(!table.l2.entries[1].is_block());
+    assert_eq!(table.l2.entries[1].address(), &table.l3 as *const L3Table as usize);
+    assert_eq!(table.l2.entries[2].attr(), MemoryAttr::Device);
+
+    let io_page = (IO_BASE - BLOCK_SIZE) / PAGE_SIZE;
+    assert_eq!(table.l3.entries[io_page - 1].attr(), MemoryAttr::Normal);
+    assert_eq!(table.l3.entries[io_page].attr(), MemoryAttr::Device);
+    assert_eq!(table.l3.entries[ENTRIES - 1].attr(), MemoryAttr::Device);
+}
+
+#[test]
+fn test_user_page_table() {
+    let kern = kern_page_table();
+    let mut table = UserPageTable::new(&kern).unwrap();
+    assert_eq!(&table.l2.entries[..3], &kern.l2.entries[..3]);
+    for &va in &[0x8_0000, IO_BASE + 0x21_5040, 0x4000_0060] {
+        assert_eq!(table.translate(va), Some(va), "{:#x}", va);
+    }
+    assert_eq!(table.translate(USER_BASE), None);
+
+    let text = table.alloc(USER_BASE, Permission::UserRo).unwrap();
+    assert!(text.iter().all(|&b| b == 0));
+    text[0x10] = 0xAA;
+    let text = text.as_ptr() as usize;
+    let stack = table.alloc(USER_END - PAGE_SIZE, Permission::UserRw).unwrap().as_ptr() as usize;
+
+    assert_eq!(table.translate(USER_BASE + 0x10), Some(text + 0x10));
+    assert_eq!(unsafe { *((text + 0x10) as *const u8) }, 0xAA);
+    assert_eq!(table.translate(USER_END - 8), Some(stack + PAGE_SIZE - 8));
+    assert_eq!(table.translate(USER_BASE + PAGE_SIZE), None);
+    assert_eq!(table.translate(USER_END), None);
+}
+
+#[test]
+#[should_panic]
+fn test_user_alloc_outside_user_region() {
+    let mut table = UserPageTable::new(&kern_page_table()).unwrap();
+    table.alloc(USER_BASE - PAGE_SIZE, Permission::UserRw);
+}
+
+#[test]
+#[should_panic]
+fn test_user_alloc_twice() {
+    let mut table = UserPageTable::new(&kern_page_table()).unwrap();
+    table.alloc(USER_BASE, Permission::UserRw);
+    table.alloc(USER_BASE, Permission::UserRw);
+}
@@ -0,0 +1,131 @@
+//! The interface between the kernel and user programs: system call numbers,
+//! the errors system calls return and, on AArch64, safe wrappers that make the
+//! calls.
+//!
+//! A system call is made with `svc #n`, where `n` is one of the `NR_*`
+//! numbers. Arguments go in `x0` to `x5`. The kernel returns the result in
+//! `x0` and `0`, or an `OsError` code on failure, in `x7`.
+//!
+//! With the `rt` feature, this is also the runtime of user programs: see
+//! `rt`.
+
+#![no_std]
+
+#[cfg(test)]
+mod tests;
+
+#[cfg(target_arch = "aarch64")]
+pub mod syscall;
+
+#[cfg(all(feature = "rt", target_arch = "aarch64"))]
+pub mod rt;
+
+use core::fmt;
+
+/// `sleep(ms) -> elapsed ms`: waits at least `ms` milliseconds.
+pub const NR_SLEEP: u16 = 1;
+/// `time() -> µs`: the time since boot.
+pub const NR_TIME: u16 = 2;
+/// `exit() -> !`: ends the calling process.
+pub const NR_EXIT: u16 = 3;
+/// `write(fd, buf, len) -> written`: writes to an open file.
+pub const NR_WRITE: u16 = 4;
+/// `getpid() -> pid`: the calling process's ID.
+pub const NR_GETPID: u16 = 5;
+/// `open(path, len) -> fd`: opens the file at the UTF-8 path.
+pub const NR_OPEN: u16 = 6;
+/// `read(fd, buf, len) -> read`: reads from an open file, waiting for input
+/// if there's none yet.
+pub const NR_READ: u16 = 7;
+/// `close(fd)`: closes an open file.
+pub const NR_CLOSE: u16 = 8;
+/// `sbrk(increment) -> previous break`: grows or shrinks the heap.
+pub const NR_SBRK: u16 = 9;
+/// `wait(pid) -> pid`: waits for the child process `pid` to exit.
+pub const NR_WAIT: u16 = 10;
+
+/// The file descriptors every process starts with, all for the console.
+pub const STDIN: u64 = 0;
+pub const STDOUT: u64 = 1;
+pub const STDERR: u64 = 2;
+
+/// Why a system call failed.
+#[repr(u64)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OsError {
+    /// A code this version doesn't know.
+    Unknown = 1,
+    /// There's no system call with that number.
+    NoSyscall = 2,
+    /// An argument was out of range or malformed.
+    InvalidArgument = 3,
+    /// A buffer wasn't in the caller's memory.
+    BadAddress = 4,
+    /// There's no file at that path.
+    NoEntry = 5,
+    /// The file descriptor isn't open.
+    BadFd = 6,
+    /// The process can't have any more files open.
+    TooManyFiles = 7,
+    /// The kernel ran out of memory.
+    NoMemory = 8,
+    /// The operation isn't supported by the file.
+    Unsupported = 9,
+    /// There's no such process, or it isn't the caller's child.
+    NoProcess = 10,
+}
+
+impl OsError {
+    /// Returns the error for the code in `x7`, or `None` for `0`, success.
+    pub fn from_code(code: u64) -> Option<OsError> {
+        use self::OsError::*;
+
+        match code {
+            0 => None,
+            2 => Some(NoSyscall),
+            3 => Some(InvalidArgument),
+            4 => Some(BadAddress),
+            5 => Some(NoEntry),
+            6 => Some(BadFd),
+            7 => Some(TooManyFiles),
+            8 => Some(NoMemory),
+            9 => Some(Unsupported),
+            10 => Some(NoProcess),
+            _ => Some(Unknown),
+        }
+    }
+
+    /// Returns the code for `x7`.
+    pub fn code(self) -> u64 {
+        self as u64
+    }
+}
+
+impl fmt::Display for OsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OsError::Unknown => "unknown error",
+            OsError::NoSyscall => "no such system call",
+            OsError::InvalidArgument => "invalid argument",
+            OsError::BadAddress => "bad address",
+            OsError::NoEntry => "no such file",
+            OsError::BadFd => "bad file descriptor",
+            OsError::TooManyFiles => "too many open files",
+            OsError::NoMemory => "out of memory",
+            OsError::Unsupported => "operation not supported",
+            OsError::NoProcess => "no such child process",
+        })
+    }
+}
+
+/// The result of a system call.
+pub type OsResult<T> = Result<T, OsError>;
+
+/// Returns the result of a system call from the values it left in `x0` and
+/// `x7`.
+pub fn result(x0: u64, x7: u64) -> OsResult<u64> {
+    match OsError::from_code(x7) {
+        None => Ok(x0),
+        Some(e) => Err(e),
+    }
+}
@@ -0,0 +1,56 @@
+//! The runtime of user programs. A program built with the `rt` feature is
+//! `#![no_std]` and `#![no_main]`, and defines its entry point as
+//! `#[no_mangle] fn main()`. It exits when `main` returns or panics.
+
+use core::fmt;
+use core::panic::PanicInfo;
+
+use crate::syscall::{self, Fd};
+use crate::{STDERR, STDOUT};
+
+extern "Rust" {
+    fn main();
+}
+
+/// Where the kernel starts a user program, with the stack pointer at the top
+/// of its address space.
+#[no_mangle]
+#[link_section = ".text._start"]
+extern "C" fn _start() -> ! {
+    unsafe { main() };
+    syscall::exit()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    let _ = fmt::Write::write_fmt(&mut Writer(STDERR), format_args!("panicked: {}\n", info.message()));
+    syscall::exit()
+}
+
+/// Formats to an open file.
+pub struct Writer(pub Fd);
+
+impl fmt::Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        syscall::write(self.0, s.as_bytes()).map(drop).map_err(|_| fmt::Error)
+    }
+}
+
+/// Prints to `STDOUT`.
+#[doc(hidden)]
+pub fn print(args: fmt::Arguments) {
+    let _ = fmt::Write::write_fmt(&mut Writer(STDOUT), args);
+}
+
+/// Prints to `STDOUT`, like `std::print!`.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::rt::print(format_args!($($arg)*)));
+}
+
+/// Prints to `STDOUT` with a newline, like `std::println!`.
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::rt::print(format_args!("{}\n", format_args!($($arg)*))));
+}
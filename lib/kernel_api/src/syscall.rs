@@ -0,0 +1,85 @@
+//! Safe wrappers around the kernel's system calls.
+
+use core::arch::asm;
+use core::time::Duration;
+
+use crate::*;
+
+/// A file descriptor, as returned by `open`.
+pub type Fd = u64;
+
+/// Makes the system call `$nr` with up to three arguments and returns its
+/// result.
+macro_rules! syscall {
+    ($nr:expr $(, $x0:expr $(, $x1:expr $(, $x2:expr)?)?)?) => {{
+        let x0: u64;
+        let x7: u64;
+        #[allow(unused_unsafe)]
+        unsafe {
+            asm!(
+                "svc {nr}",
+                nr = const $nr,
+                inlateout("x0") 0u64 $(| $x0 as u64)? => x0,
+                $($(in("x1") $x1 as u64, $(in("x2") $x2 as u64,)?)?)?
+                lateout("x7") x7,
+                clobber_abi("C"),
+            );
+        }
+        result(x0, x7)
+    }};
+}
+
+/// Sleeps for at least `duration`, to millisecond precision. Returns how long
+/// the process actually slept.
+pub fn sleep(duration: Duration) -> OsResult<Duration> {
+    let ms = u32::try_from(duration.as_millis()).map_err(|_| OsError::InvalidArgument)?;
+    syscall!(NR_SLEEP, ms).map(Duration::from_millis)
+}
+
+/// Returns the time since boot.
+pub fn time() -> Duration {
+    Duration::from_micros(syscall!(NR_TIME).unwrap_or(0))
+}
+
+/// Ends the calling process.
+pub fn exit() -> ! {
+    let _ = syscall!(NR_EXIT);
+    unreachable!("exit returned")
+}
+
+/// Writes `buf` to the open file `fd`. Returns the number of bytes written.
+pub fn write(fd: Fd, buf: &[u8]) -> OsResult<usize> {
+    syscall!(NR_WRITE, fd, buf.as_ptr(), buf.len()).map(|n| n as usize)
+}
+
+/// Returns the calling process's ID.
+pub fn getpid() -> u64 {
+    syscall!(NR_GETPID).unwrap_or(0)
+}
+
+/// Opens the file at `path`.
+pub fn open(path: &str) -> OsResult<Fd> {
+    syscall!(NR_OPEN, path.as_ptr(), path.len())
+}
+
+/// Reads from the open file `fd` into `buf`, waiting for input if there's
+/// none yet. Returns the number of bytes read, `0` at the end of the file.
+pub fn read(fd: Fd, buf: &mut [u8]) -> OsResult<usize> {
+    syscall!(NR_READ, fd, buf.as_mut_ptr(), buf.len()).map(|n| n as usize)
+}
+
+/// Closes the open file `fd`.
+pub fn close(fd: Fd) -> OsResult<()> {
+    syscall!(NR_CLOSE, fd).map(|_| ())
+}
+
+/// Moves the end of the heap by `increment` bytes. Returns where it was, i.e.
+/// the start of the new memory when growing it.
+pub fn sbrk(increment: isize) -> OsResult<*mut u8> {
+    syscall!(NR_SBRK, increment).map(|brk| brk as *mut u8)
+}
+
+/// Waits for the child process `pid` to exit. Returns `pid`.
+pub fn wait(pid: u64) -> OsResult<u64> {
+    syscall!(NR_WAIT, pid)
+}
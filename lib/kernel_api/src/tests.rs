@@ -0,0 +1,18 @@
+use super::*;
+
+#[test]
+fn test_codes_round_trip() {
+    use OsError::*;
+
+    for &e in &[Unknown, NoSyscall, InvalidArgument, BadAddress, NoEntry, BadFd, TooManyFiles, NoMemory,
+                Unsupported, NoProcess] {
+        assert_eq!(OsError::from_code(e.code()), Some(e));
+    }
+}
+
+#[test]
+fn test_result() {
+    assert_eq!(result(42, 0), Ok(42));
+    assert_eq!(result(42, OsError::BadFd.code()), Err(OsError::BadFd));
+    assert_eq!(result(0, 0xFFFF), Err(OsError::Unknown));
+}
@@ -0,0 +1,67 @@
+use core::{slice, str};
+
+use crate::atags::raw;
+
+pub use crate::atags::raw::{Core, Mem};
+
+/// An ATAG.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Atag {
+    Core(raw::Core),
+    Mem(raw::Mem),
+    Cmd(&'static str),
+    Unknown(u32),
+    None,
+}
+
+impl Atag {
+    /// Returns `Some` if this is a `Core` ATAG. Otherwise returns `None`.
+    pub fn core(self) -> Option<Core> {
+        match self {
+            Atag::Core(core) => Some(core),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some` if this is a `Mem` ATAG. Otherwise returns `None`.
+    pub fn mem(self) -> Option<Mem> {
+        match self {
+            Atag::Mem(mem) => Some(mem),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some` with the command line string if this is a `Cmd` ATAG.
+    /// Otherwise returns `None`.
+    pub fn cmd(self) -> Option<&'static str> {
+        match self {
+            Atag::Cmd(cmd) => Some(cmd),
+            _ => None,
+        }
+    }
+}
+
+impl From<&'static raw::Atag> for Atag {
+    fn from(atag: &'static raw::Atag) -> Atag {
+        unsafe {
+            match atag.tag {
+                // A `CORE` ATAG without a payload is allowed.
+                raw::Atag::CORE if atag.payload_len() < 12 => Atag::Core(Core { flags: 0, page_size: 0, root_dev: 0 }),
+                raw::Atag::CORE => Atag::Core(atag.kind.core),
+                raw::Atag::MEM if atag.payload_len() >= 8 => Atag::Mem(atag.kind.mem),
+                raw::Atag::CMDLINE => {
+                    // The string ends at its `NUL` or, failing that, with the
+                    // ATAG. Invalid UTF-8 cuts it short.
+                    let bytes = slice::from_raw_parts(&atag.kind.cmd.cmd as *const u8, atag.payload_len());
+                    let bytes = &bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())];
+                    Atag::Cmd(match str::from_utf8(bytes) {
+                        Ok(cmd) => cmd,
+                        Err(e) => str::from_utf8_unchecked(&bytes[..e.valid_up_to()]),
+                    })
+                }
+                raw::Atag::NONE => Atag::None,
+                id => Atag::Unknown(id),
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+mod atag;
+mod raw;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::atag::*;
+
+/// The address at which the firmware loads the ATAGS.
+const ATAG_BASE: usize = 0x100;
+
+/// An iterator over the ATAGS on this system. The terminating `NONE` ATAG
+/// isn't returned.
+pub struct Atags {
+    ptr: Option<&'static raw::Atag>,
+}
+
+impl Atags {
+    /// Returns an instance of `Atags`, an iterator over ATAGS on this system.
+    /// The iterator is empty if the firmware didn't pass any, e.g. because it
+    /// passed a device tree instead.
+    pub fn get() -> Atags {
+        unsafe { Atags::from_ptr(ATAG_BASE as *const u32) }
+    }
+
+    /// Returns an iterator over the ATAGS at `ptr`. The iterator is empty if
+    /// the list doesn't start with a `CORE` ATAG.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be 4-byte aligned and readable for the first ATAG's header.
+    /// If it starts with a `CORE` ATAG, the whole list up to and including
+    /// its `NONE` ATAG must be readable and live forever.
+    pub unsafe fn from_ptr(ptr: *const u32) -> Atags {
+        let first = &*(ptr as *const raw::Atag);
+        match first.tag == raw::Atag::CORE && first.dwords >= 2 {
+            true => Atags { ptr: Some(first) },
+            false => Atags { ptr: None },
+        }
+    }
+}
+
+impl Iterator for Atags {
+    type Item = Atag;
+
+    fn next(&mut self) -> Option<Atag> {
+        let current = self.ptr?;
+        if current.tag == raw::Atag::NONE {
+            self.ptr = None;
+            return None;
+        }
+
+        self.ptr = current.next();
+        Some(Atag::from(current))
+    }
+}
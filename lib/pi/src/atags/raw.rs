@@ -0,0 +1,69 @@
+/// A raw `ATAG` as laid out in memory: its size in 32-bit words, header
+/// included, its tag and its payload.
+#[repr(C)]
+pub struct Atag {
+    pub dwords: u32,
+    pub tag: u32,
+    pub kind: Kind,
+}
+
+impl Atag {
+    pub const NONE: u32 = 0x00000000;
+    pub const CORE: u32 = 0x54410001;
+    pub const MEM: u32 = 0x54410002;
+    pub const VIDEOTEXT: u32 = 0x54410003;
+    pub const RAMDISK: u32 = 0x54410004;
+    pub const INITRD2: u32 = 0x54420005;
+    pub const SERIAL: u32 = 0x54410006;
+    pub const REVISION: u32 = 0x54410007;
+    pub const VIDEOLFB: u32 = 0x54410008;
+    pub const CMDLINE: u32 = 0x54410009;
+
+    /// Returns the ATAG following `self`, or `None` if `self` ends the list or
+    /// is too short to be an ATAG at all.
+    pub fn next(&self) -> Option<&Atag> {
+        if self.tag == Atag::NONE || self.dwords < 2 {
+            return None;
+        }
+
+        let next = unsafe { (self as *const Atag as *const u32).add(self.dwords as usize) };
+        Some(unsafe { &*(next as *const Atag) })
+    }
+
+    /// Returns the size of the payload in bytes.
+    pub fn payload_len(&self) -> usize {
+        (self.dwords.saturating_sub(2) as usize) * 4
+    }
+}
+
+/// The payload of an ATAG.
+#[repr(C)]
+pub union Kind {
+    pub core: Core,
+    pub mem: Mem,
+    pub cmd: Cmd,
+}
+
+/// A `CORE` ATAG: the first one in a list.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Core {
+    pub flags: u32,
+    pub page_size: u32,
+    pub root_dev: u32,
+}
+
+/// A `MEM` ATAG: a region of physical memory.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Mem {
+    pub size: u32,
+    pub start: u32,
+}
+
+/// A `CMDLINE` ATAG: the first byte of a `NUL`-terminated command line.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Cmd {
+    pub cmd: u8,
+}
@@ -0,0 +1,135 @@
+use super::*;
+use crate::fdt::Fdt;
+
+/// Forces the alignment the firmware guarantees onto a captured blob.
+#[repr(C, align(8))]
+struct Aligned<T: ?Sized>(T);
+
+/// ATAGS as left by the Raspberry Pi 3 firmware.
+static PI3_ATAGS: &Aligned<[u8]> = &Aligned(*include_bytes!("testdata/pi3.atags"));
+
+/// A device tree with the nodes the firmware fills in on a Raspberry Pi 3.
+static PI3_DTB: &Aligned<[u8]> = &Aligned(*include_bytes!("testdata/pi3.dtb"));
+
+fn pi3_atags() -> Atags {
+    unsafe { Atags::from_ptr(PI3_ATAGS.0.as_ptr() as *const u32) }
+}
+
+#[test]
+fn test_atags() {
+    let atags: Vec<Atag> = pi3_atags().collect();
+    assert_eq!(atags.len(), 3);
+    assert_eq!(atags[0], Atag::Core(Core { flags: 0, page_size: 0, root_dev: 0 }));
+    assert_eq!(atags[1], Atag::Mem(Mem { size: 0x3b000000, start: 0 }));
+
+    let cmd = atags[2].cmd().expect("command line");
+    assert!(cmd.starts_with("bcm2708_fb.fbwidth=656 "));
+    assert!(cmd.ends_with(" rootwait"));
+}
+
+#[test]
+fn test_atag_accessors() {
+    let mut atags = pi3_atags();
+    assert!(atags.next().unwrap().core().is_some());
+    let mem = atags.next().unwrap();
+    assert_eq!(mem.mem().map(|m| m.size), Some(0x3b000000));
+    assert_eq!(mem.core(), None);
+    assert_eq!(mem.cmd(), None);
+    assert_eq!(atags.next().unwrap().mem(), None);
+    assert!(atags.next().is_none());
+    assert!(atags.next().is_none());
+}
+
+#[test]
+fn test_atags_must_start_with_core() {
+    static ZEROES: Aligned<[u32; 4]> = Aligned([0; 4]);
+    static MEM_FIRST: Aligned<[u32; 6]> = Aligned([4, 0x54410002, 0x1000, 0, 0, 0]);
+
+    assert_eq!(unsafe { Atags::from_ptr(ZEROES.0.as_ptr()) }.count(), 0);
+    assert_eq!(unsafe { Atags::from_ptr(MEM_FIRST.0.as_ptr()) }.count(), 0);
+}
+
+#[test]
+fn test_atags_unknown_and_short_tags() {
+    static TAGS: Aligned<[u32; 14]> = Aligned([
+        2, 0x54410001,                 // CORE without a payload
+        3, 0x54410007, 0xa02082,       // REVISION
+        3, 0x54410002, 0x1000,         // MEM, too short
+        4, 0x54410009, 0x00006968, 0,  // CMDLINE "hi"
+        0, 0,                          // NONE
+    ]);
+
+    let atags: Vec<Atag> = unsafe { Atags::from_ptr(TAGS.0.as_ptr()) }.collect();
+    assert_eq!(atags, vec![
+        Atag::Core(Core { flags: 0, page_size: 0, root_dev: 0 }),
+        Atag::Unknown(0x54410007),
+        Atag::Unknown(0x54410002),
+        Atag::Cmd("hi"),
+    ]);
+}
+
+#[test]
+fn test_fdt_atags() {
+    let fdt = Fdt::new(&PI3_DTB.0).expect("valid device tree");
+    let atags: Vec<Atag> = fdt.atags().collect();
+    assert_eq!(atags, vec![
+        Atag::Cmd("coherent_pool=1M 8250.nr_uarts=1 console=ttyS0,115200 log=debug prompt=$"),
+        Atag::Mem(Mem { start: 0, size: 0x3b400000 }),
+        Atag::Mem(Mem { start: 0x40000000, size: 0x100000 }),
+    ]);
+}
+
+#[test]
+fn test_fdt_from_ptr() {
+    let fdt = unsafe { Fdt::from_ptr(PI3_DTB.0.as_ptr()) }.expect("valid device tree");
+    assert_eq!(fdt.atags().filter_map(Atag::mem).count(), 2);
+
+    assert!(unsafe { Fdt::from_ptr(core::ptr::null()) }.is_none());
+    assert!(unsafe { Fdt::from_ptr(PI3_ATAGS.0.as_ptr()) }.is_none());
+}
+
+/// Returns a copy of the captured device tree, modified by `patch`.
+fn patched_dtb(patch: impl FnOnce(&mut [u8])) -> &'static [u8] {
+    let blob = Box::leak(PI3_DTB.0.to_vec().into_boxed_slice());
+    patch(blob);
+    blob
+}
+
+#[test]
+fn test_fdt_rejects_bad_blobs() {
+    assert!(Fdt::new(patched_dtb(|blob| blob[0] = 0)).is_none());
+    assert!(Fdt::new(&PI3_DTB.0[..39]).is_none());
+
+    // A structure block reaching past the blob.
+    assert!(Fdt::new(patched_dtb(|blob| blob[36..40].copy_from_slice(&1000u32.to_be_bytes()))).is_none());
+
+    // A malformed structure block just ends the iterator.
+    let fdt = Fdt::new(patched_dtb(|blob| {
+        let structs = u32::from_be_bytes([blob[8], blob[9], blob[10], blob[11]]) as usize;
+        blob[structs + 8] = 0xFF;
+    }));
+    assert_eq!(fdt.expect("valid header").atags().count(), 0);
+}
+
+#[test]
+fn test_fdt_bad_cell_counts() {
+    // Patches the values of the root's `#address-cells` and `#size-cells`.
+    let set_cells = |address: u32, size: u32| patched_dtb(|blob| {
+        blob[76..80].copy_from_slice(&address.to_be_bytes());
+        blob[92..96].copy_from_slice(&size.to_be_bytes());
+    });
+
+    // Regions of zero cells and of cells that don't fit a `u64` are skipped.
+    for (address, size) in [(0, 0), (3, 1), (1, 3)] {
+        let fdt = Fdt::new(set_cells(address, size)).expect("valid device tree");
+        assert_eq!(fdt.atags().filter_map(Atag::mem).count(), 0, "{} {}", address, size);
+        assert_eq!(fdt.atags().filter_map(Atag::cmd).count(), 1);
+    }
+
+    let fdt = Fdt::new(set_cells(2, 0)).expect("valid device tree");
+    let mems: Vec<Mem> = fdt.atags().filter_map(Atag::mem).collect();
+    assert_eq!(mems, vec![
+        Mem { start: 0x3b400000, size: 0 },
+        Mem { start: 0x100000, size: 0 },
+    ]);
+}
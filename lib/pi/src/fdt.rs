@@ -0,0 +1,231 @@
+//! A minimal reader for the flattened device tree (DTB) the firmware passes
+//! in `x0` when it's configured to do so instead of leaving ATAGS. Only what
+//! ATAGS would tell is extracted: the memory regions and the command line.
+
+use core::{slice, str};
+
+use crate::atags::{Atag, Mem};
+
+/// The magic number every device tree blob starts with.
+const MAGIC: u32 = 0xd00dfeed;
+/// The size of the blob's header.
+const HEADER_SIZE: usize = 40;
+/// The oldest version of the format this reader understands.
+const MIN_VERSION: u32 = 16;
+
+// Structure block tokens (ref: Devicetree Specification, 5.4.1)
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+/// Reads the big-endian `u32` at `offset` in `bytes`, if it's in bounds.
+fn be32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let word = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes([word[0], word[1], word[2], word[3]]))
+}
+
+/// Reads a big-endian number of `cells` 32-bit cells from the front of
+/// `bytes`, advancing it. Returns `None` if `bytes` is too short or if more
+/// than two cells wouldn't fit a `u64`.
+fn read_cells(bytes: &mut &[u8], cells: u32) -> Option<u64> {
+    if cells > 2 {
+        return None;
+    }
+
+    let mut value = 0u64;
+    for _ in 0..cells {
+        value = (value << 32) | be32(bytes, 0)? as u64;
+        *bytes = &bytes[4..];
+    }
+
+    Some(value)
+}
+
+/// Returns the bytes of `bytes` up to its first `NUL`.
+fn until_nul(bytes: &[u8]) -> &[u8] {
+    &bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())]
+}
+
+/// A validated flattened device tree blob.
+#[derive(Copy, Clone)]
+pub struct Fdt {
+    structs: &'static [u8],
+    strings: &'static [u8],
+}
+
+impl Fdt {
+    /// Returns the device tree at `ptr`, or `None` if `ptr` is null, not
+    /// 8-byte aligned or doesn't point to a valid device tree.
+    ///
+    /// # Safety
+    ///
+    /// If `ptr` is non-null and aligned, it must be readable for the header
+    /// and, if the header is valid, for the blob's total size, forever.
+    pub unsafe fn from_ptr(ptr: *const u8) -> Option<Fdt> {
+        if ptr.is_null() || ptr as usize % 8 != 0 {
+            return None;
+        }
+
+        let header = slice::from_raw_parts(ptr, HEADER_SIZE);
+        if be32(header, 0)? != MAGIC {
+            return None;
+        }
+
+        Fdt::new(slice::from_raw_parts(ptr, be32(header, 4)? as usize))
+    }
+
+    /// Returns the device tree in `blob`, or `None` if it isn't a valid
+    /// device tree of a version this reader understands.
+    pub fn new(blob: &'static [u8]) -> Option<Fdt> {
+        let field = |n: usize| be32(blob, 4 * n);
+        if field(0)? != MAGIC || field(6)? > MIN_VERSION + 1 || field(5)? < MIN_VERSION {
+            return None;
+        }
+
+        let total = (field(1)? as usize).min(blob.len());
+        let section = |offset: u32, size: u32| {
+            let start = offset as usize;
+            blob[..total].get(start..start.checked_add(size as usize)?)
+        };
+
+        Some(Fdt {
+            structs: section(field(2)?, field(9)?)?,
+            strings: section(field(3)?, field(8)?)?,
+        })
+    }
+
+    /// Returns an iterator over the memory regions and the command line in
+    /// this device tree, as ATAGS would describe them.
+    pub fn atags(&self) -> Atags {
+        Atags { fdt: *self, offset: 0, depth: 0, node: Node::Other, address_cells: 2, size_cells: 1, reg: &[] }
+    }
+
+    /// Returns the `NUL`-terminated string at `offset` in the strings block.
+    fn string(&self, offset: u32) -> &'static [u8] {
+        until_nul(self.strings.get(offset as usize..).unwrap_or(&[]))
+    }
+}
+
+/// The kind of node a property belongs to.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Node {
+    Root,
+    Memory,
+    Chosen,
+    Other,
+}
+
+/// An iterator over the `Mem` and `Cmd` ATAGS a device tree amounts to: one
+/// `Mem` per region of the `memory` nodes' `reg` properties and a `Cmd` with
+/// `/chosen/bootargs`. Regions beyond 4 GiB are truncated.
+pub struct Atags {
+    fdt: Fdt,
+    offset: usize,
+    depth: usize,
+    node: Node,
+    address_cells: u32,
+    size_cells: u32,
+    reg: &'static [u8],
+}
+
+impl Atags {
+    /// Reads the next token, returning its payload for properties.
+    fn token(&mut self) -> Option<Token> {
+        let structs = self.fdt.structs;
+        let token = be32(structs, self.offset)?;
+        self.offset += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = until_nul(structs.get(self.offset..)?);
+                self.offset += (name.len() + 1 + 3) & !3;
+                Some(Token::Begin(name))
+            }
+            FDT_PROP => {
+                let len = be32(structs, self.offset)? as usize;
+                let name = self.fdt.string(be32(structs, self.offset + 4)?);
+                let start = self.offset + 8;
+                let value = structs.get(start..start.checked_add(len)?)?;
+                self.offset = start + ((len + 3) & !3);
+                Some(Token::Prop(name, value))
+            }
+            FDT_END_NODE => Some(Token::End),
+            FDT_NOP => Some(Token::Nop),
+            // `FDT_END` or garbage
+            _ => None,
+        }
+    }
+}
+
+enum Token {
+    Begin(&'static [u8]),
+    Prop(&'static [u8], &'static [u8]),
+    End,
+    Nop,
+}
+
+impl Iterator for Atags {
+    type Item = Atag;
+
+    fn next(&mut self) -> Option<Atag> {
+        loop {
+            // Hand out the regions of the current `reg` property first.
+            if !self.reg.is_empty() {
+                let mut reg = self.reg;
+                let start = read_cells(&mut reg, self.address_cells);
+                let size = read_cells(&mut reg, self.size_cells);
+                self.reg = reg;
+                match (start, size) {
+                    // A region without any cells would never use `reg` up.
+                    (Some(start), Some(size)) if self.address_cells + self.size_cells > 0 => {
+                        return Some(Atag::Mem(Mem { start: start as u32, size: size as u32 }));
+                    }
+                    _ => self.reg = &[],
+                }
+            }
+
+            let token = match self.token() {
+                Some(token) => token,
+                None => {
+                    // The end of the tree or a malformed blob: stop for good.
+                    self.offset = self.fdt.structs.len();
+                    return None;
+                }
+            };
+
+            match token {
+                Token::Begin(name) => {
+                    self.depth += 1;
+                    self.node = match (self.depth, name) {
+                        (1, _) => Node::Root,
+                        (2, b"chosen") => Node::Chosen,
+                        (2, name) if name == b"memory" || name.starts_with(b"memory@") => Node::Memory,
+                        _ => Node::Other,
+                    };
+                }
+                Token::End => {
+                    self.depth = self.depth.saturating_sub(1);
+                    self.node = match self.depth {
+                        1 => Node::Root,
+                        _ => Node::Other,
+                    };
+                }
+                Token::Prop(name, value) => match (self.node, name) {
+                    (Node::Root, b"#address-cells") => self.address_cells = be32(value, 0).unwrap_or(2),
+                    (Node::Root, b"#size-cells") => self.size_cells = be32(value, 0).unwrap_or(1),
+                    (Node::Memory, b"reg") => self.reg = value,
+                    (Node::Chosen, b"bootargs") => {
+                        let bytes = until_nul(value);
+                        let cmd = match str::from_utf8(bytes) {
+                            Ok(cmd) => cmd,
+                            Err(e) => unsafe { str::from_utf8_unchecked(&bytes[..e.valid_up_to()]) },
+                        };
+                        return Some(Atag::Cmd(cmd));
+                    }
+                    _ => {}
+                },
+                Token::Nop => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use crate::common::IO_BASE;
+
+use volatile::prelude::*;
+use volatile::{ReadVolatile, Reserved, Volatile};
+
+/// The base address of the ARM interrupt registers, at offset 0x200 of the
+/// interrupt controller block at `IO_BASE + 0xB000`.
+const INT_BASE: usize = IO_BASE + 0xB000 + 0x200;
+
+/// The base address of the per-core local interrupt controller (QA7).
+const LOCAL_INT_BASE: usize = 0x4000_0000;
+
+/// The number of cores with a local interrupt controller.
+pub const NUM_CORES: usize = 4;
+
+/// The interrupt enable bit of `AXI_OUTSTANDING_IRQ`.
+const AXI_IRQ_ENABLE: u32 = 1 << 20;
+
+/// The interrupt enable bit of `LOCAL_TIMER_CONTROL`.
+const LOCAL_TIMER_IRQ_ENABLE: u32 = 1 << 29;
+
+/// An interrupt routed through the ARM interrupt controller. Numbers below 64
+/// are GPU interrupts (banks 1 and 2); 64 and above are the basic bank's own
+/// ARM interrupts.
+#[repr(u8)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Interrupt {
+    Timer1 = 1,
+    Timer3 = 3,
+    Usb = 9,
+    Aux = 29,
+    Gpio0 = 49,
+    Gpio1 = 50,
+    Gpio2 = 51,
+    Gpio3 = 52,
+    I2c = 53,
+    Spi = 54,
+    Pcm = 55,
+    Uart = 57,
+    ArmTimer = 64,
+    ArmMailbox = 65,
+    ArmDoorbell0 = 66,
+    ArmDoorbell1 = 67,
+    GpuHalted0 = 68,
+    GpuHalted1 = 69,
+    IllegalAccess1 = 70,
+    IllegalAccess0 = 71,
+}
+
+impl Interrupt {
+    /// One more than the largest interrupt number.
+    pub const MAX: usize = 72;
+
+    /// Every interrupt, in increasing order.
+    pub const ALL: [Interrupt; 20] = [
+        Interrupt::Timer1,
+        Interrupt::Timer3,
+        Interrupt::Usb,
+        Interrupt::Aux,
+        Interrupt::Gpio0,
+        Interrupt::Gpio1,
+        Interrupt::Gpio2,
+        Interrupt::Gpio3,
+        Interrupt::I2c,
+        Interrupt::Spi,
+        Interrupt::Pcm,
+        Interrupt::Uart,
+        Interrupt::ArmTimer,
+        Interrupt::ArmMailbox,
+        Interrupt::ArmDoorbell0,
+        Interrupt::ArmDoorbell1,
+        Interrupt::GpuHalted0,
+        Interrupt::GpuHalted1,
+        Interrupt::IllegalAccess1,
+        Interrupt::IllegalAccess0,
+    ];
+
+    /// Returns this interrupt's number, an index less than `MAX`.
+    pub fn to_index(self) -> usize {
+        self as usize
+    }
+
+    /// Returns the interrupt numbered `index`, if there is one.
+    pub fn from_index(index: usize) -> Option<Interrupt> {
+        Interrupt::ALL.iter().copied().find(|int| int.to_index() == index)
+    }
+
+    /// Returns an iterator over every interrupt.
+    pub fn iter() -> impl Iterator<Item = Interrupt> {
+        Interrupt::ALL.iter().copied()
+    }
+
+    /// Returns which of the three register banks this interrupt is in (0 for
+    /// the basic bank) and its bit in that bank's registers.
+    fn bank_and_bit(self) -> (usize, u32) {
+        match self.to_index() {
+            i @ 0..=31 => (1, 1 << i),
+            i @ 32..=63 => (2, 1 << (i - 32)),
+            i => (0, 1 << (i - 64)),
+        }
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    IRQ_BASIC_PENDING: ReadVolatile<u32>,
+    IRQ_PENDING: [ReadVolatile<u32>; 2],
+    FIQ_CONTROL: Volatile<u32>,
+    ENABLE_IRQS: [Volatile<u32>; 2],
+    ENABLE_BASIC_IRQS: Volatile<u32>,
+    DISABLE_IRQS: [Volatile<u32>; 2],
+    DISABLE_BASIC_IRQS: Volatile<u32>,
+}
+
+/// An interrupt controller. Used to enable and disable interrupts as well as
+/// to detect which interrupts are pending.
+pub struct Controller {
+    registers: &'static mut Registers,
+}
+
+impl Controller {
+    /// Returns a new handle to the interrupt controller.
+    pub fn new() -> Controller {
+        Controller {
+            registers: unsafe { &mut *(INT_BASE as *mut Registers) },
+        }
+    }
+
+    /// Enables the interrupt `int`.
+    pub fn enable(&mut self, int: Interrupt) {
+        match int.bank_and_bit() {
+            (0, bit) => self.registers.ENABLE_BASIC_IRQS.write(bit),
+            (bank, bit) => self.registers.ENABLE_IRQS[bank - 1].write(bit),
+        }
+    }
+
+    /// Disables the interrupt `int`.
+    pub fn disable(&mut self, int: Interrupt) {
+        match int.bank_and_bit() {
+            (0, bit) => self.registers.DISABLE_BASIC_IRQS.write(bit),
+            (bank, bit) => self.registers.DISABLE_IRQS[bank - 1].write(bit),
+        }
+    }
+
+    /// Disables every interrupt.
+    pub fn disable_all(&mut self) {
+        self.registers.DISABLE_BASIC_IRQS.write(!0);
+        for bank in self.registers.DISABLE_IRQS.iter_mut() {
+            bank.write(!0);
+        }
+    }
+
+    /// Returns `true` if `int` is pending. `false` otherwise.
+    pub fn is_pending(&self, int: Interrupt) -> bool {
+        let pending = match int.bank_and_bit() {
+            (0, bit) => self.registers.IRQ_BASIC_PENDING.read() & bit,
+            (bank, bit) => self.registers.IRQ_PENDING[bank - 1].read() & bit,
+        };
+        pending != 0
+    }
+
+    /// Returns an iterator over the pending interrupts.
+    pub fn pending(&self) -> impl Iterator<Item = Interrupt> + '_ {
+        Interrupt::iter().filter(move |&int| self.is_pending(int))
+    }
+}
+
+impl Default for Controller {
+    fn default() -> Controller {
+        Controller::new()
+    }
+}
+
+/// An interrupt raised by a core's local interrupt controller. The
+/// discriminant is the interrupt's bit in the core's IRQ source register.
+#[repr(u8)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LocalInterrupt {
+    /// The generic timer's secure physical timer (`CNTPS`).
+    CntPs = 0,
+    /// The generic timer's non-secure physical timer (`CNTP`).
+    CntPns = 1,
+    /// The generic timer's hypervisor timer (`CNTHP`).
+    CntHp = 2,
+    /// The generic timer's virtual timer (`CNTV`).
+    CntV = 3,
+    Mailbox0 = 4,
+    Mailbox1 = 5,
+    Mailbox2 = 6,
+    Mailbox3 = 7,
+    /// One of the ARM interrupt controller's interrupts; see `Controller`.
+    Gpu = 8,
+    Pmu = 9,
+    AxiOutstanding = 10,
+    LocalTimer = 11,
+}
+
+impl LocalInterrupt {
+    /// One more than the largest interrupt number.
+    pub const MAX: usize = 12;
+
+    /// Every local interrupt, in increasing order.
+    pub const ALL: [LocalInterrupt; 12] = [
+        LocalInterrupt::CntPs,
+        LocalInterrupt::CntPns,
+        LocalInterrupt::CntHp,
+        LocalInterrupt::CntV,
+        LocalInterrupt::Mailbox0,
+        LocalInterrupt::Mailbox1,
+        LocalInterrupt::Mailbox2,
+        LocalInterrupt::Mailbox3,
+        LocalInterrupt::Gpu,
+        LocalInterrupt::Pmu,
+        LocalInterrupt::AxiOutstanding,
+        LocalInterrupt::LocalTimer,
+    ];
+
+    /// Returns this interrupt's number, an index less than `MAX`.
+    pub fn to_index(self) -> usize {
+        self as usize
+    }
+
+    /// Returns an iterator over every local interrupt.
+    pub fn iter() -> impl Iterator<Item = LocalInterrupt> {
+        LocalInterrupt::ALL.iter().copied()
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct LocalRegisters {
+    CONTROL: Volatile<u32>,
+    __r0: Reserved<u32>,
+    CORE_TIMER_PRESCALER: Volatile<u32>,
+    GPU_INT_ROUTING: Volatile<u32>,
+    PMU_INT_ROUTING_SET: Volatile<u32>,
+    PMU_INT_ROUTING_CLR: Volatile<u32>,
+    __r1: Reserved<u32>,
+    CORE_TIMER_LS: Volatile<u32>,
+    CORE_TIMER_MS: Volatile<u32>,
+    LOCAL_INT_ROUTING: Volatile<u32>,
+    __r2: Reserved<u32>,
+    AXI_OUTSTANDING_COUNTERS: Volatile<u32>,
+    AXI_OUTSTANDING_IRQ: Volatile<u32>,
+    LOCAL_TIMER_CONTROL: Volatile<u32>,
+    LOCAL_TIMER_WRITE_FLAGS: Volatile<u32>,
+    __r3: Reserved<u32>,
+    CORE_TIMER_INT_CONTROL: [Volatile<u32>; NUM_CORES],
+    CORE_MAILBOX_INT_CONTROL: [Volatile<u32>; NUM_CORES],
+    CORE_IRQ_SOURCE: [ReadVolatile<u32>; NUM_CORES],
+    CORE_FIQ_SOURCE: [ReadVolatile<u32>; NUM_CORES],
+}
+
+/// A core's local interrupt controller, which routes the core's generic
+/// timers and mailboxes, and the ARM interrupt controller's interrupts, to it.
+pub struct LocalController {
+    core: usize,
+    registers: &'static mut LocalRegisters,
+}
+
+impl LocalController {
+    /// Returns a new handle to the local interrupt controller of `core`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `core` isn't less than `NUM_CORES`.
+    pub fn new(core: usize) -> LocalController {
+        assert!(core < NUM_CORES, "no such core: {}", core);
+        LocalController {
+            core,
+            registers: unsafe { &mut *(LOCAL_INT_BASE as *mut LocalRegisters) },
+        }
+    }
+
+    /// Routes the generic timer interrupt `int` to this core as an IRQ.
+    /// Interrupts other than the four timers are ignored.
+    pub fn enable_timer(&mut self, int: LocalInterrupt) {
+        if int.to_index() < 4 {
+            let control = &mut self.registers.CORE_TIMER_INT_CONTROL[self.core];
+            control.write(control.read() | (1 << int.to_index()));
+        }
+    }
+
+    /// Stops routing the generic timer interrupt `int` to this core.
+    /// Interrupts other than the four timers are ignored.
+    pub fn disable_timer(&mut self, int: LocalInterrupt) {
+        if int.to_index() < 4 {
+            let control = &mut self.registers.CORE_TIMER_INT_CONTROL[self.core];
+            control.write(control.read() & !(1 << int.to_index()));
+        }
+    }
+
+    /// Stops `int` from interrupting this core. The local timer and the AXI
+    /// outstanding interrupt are only ever routed to one core, so they're
+    /// disabled for every core. `Gpu` is ignored: the ARM interrupt
+    /// controller's interrupts are disabled in `Controller`.
+    pub fn disable(&mut self, int: LocalInterrupt) {
+        use LocalInterrupt::*;
+
+        let registers = &mut *self.registers;
+        match int {
+            CntPs | CntPns | CntHp | CntV => self.disable_timer(int),
+            Mailbox0 | Mailbox1 | Mailbox2 | Mailbox3 => {
+                let control = &mut registers.CORE_MAILBOX_INT_CONTROL[self.core];
+                control.write(control.read() & !(1 << (int.to_index() - Mailbox0.to_index())));
+            }
+            Pmu => registers.PMU_INT_ROUTING_CLR.write(1 << self.core),
+            AxiOutstanding => {
+                let irq = &mut registers.AXI_OUTSTANDING_IRQ;
+                irq.write(irq.read() & !AXI_IRQ_ENABLE);
+            }
+            LocalTimer => {
+                let control = &mut registers.LOCAL_TIMER_CONTROL;
+                control.write(control.read() & !LOCAL_TIMER_IRQ_ENABLE);
+            }
+            Gpu => {}
+        }
+    }
+
+    /// Routes the ARM interrupt controller's IRQs to this core.
+    pub fn route_gpu_irqs(&mut self) {
+        let routing = self.registers.GPU_INT_ROUTING.read() & !0b11;
+        self.registers.GPU_INT_ROUTING.write(routing | self.core as u32);
+    }
+
+    /// Returns `true` if `int` is pending on this core. `false` otherwise.
+    pub fn is_pending(&self, int: LocalInterrupt) -> bool {
+        self.registers.CORE_IRQ_SOURCE[self.core].read() & (1 << int.to_index()) != 0
+    }
+
+    /// Returns an iterator over the interrupts pending on this core.
+    pub fn pending(&self) -> impl Iterator<Item = LocalInterrupt> + '_ {
+        LocalInterrupt::iter().filter(move |&int| self.is_pending(int))
+    }
+}
@@ -1,9 +1,13 @@
 #![feature(core_intrinsics)]
 #![feature(decl_macro)]
 #![feature(never_type)]
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
+pub mod atags;
 pub mod common;
+pub mod fdt;
 pub mod gpio;
+pub mod interrupt;
+pub mod pm;
 pub mod timer;
 pub mod uart;
@@ -0,0 +1,69 @@
+use crate::common::IO_BASE;
+
+use volatile::prelude::*;
+use volatile::{Reserved, Volatile};
+
+/// The base address for the power management (`PM`) registers.
+const PM_REG_BASE: usize = IO_BASE + 0x100000;
+
+/// Every write to a `PM` register has to carry this password in bits 24-31.
+const PASSWORD: u32 = 0x5A00_0000;
+
+/// `RSTC` bits 4-5 select what the watchdog resets when it expires.
+const RSTC_WRCFG_MASK: u32 = 0x30;
+const RSTC_WRCFG_FULL_RESET: u32 = 0x20;
+
+/// The watchdog counts down in ticks of ~16 microseconds.
+const WDOG_TICKS_MASK: u32 = 0xFFFFF;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    __r0: [Reserved<u32>; 7],
+    RSTC: Volatile<u32>,
+    RSTS: Volatile<u32>,
+    WDOG: Volatile<u32>,
+}
+
+/// The power management block's watchdog, which can reset the whole board.
+pub struct Watchdog {
+    registers: &'static mut Registers,
+}
+
+impl Watchdog {
+    /// Returns a new instance of `Watchdog`.
+    pub fn new() -> Watchdog {
+        Watchdog {
+            registers: unsafe { &mut *(PM_REG_BASE as *mut Registers) },
+        }
+    }
+
+    /// Starts the watchdog so that it resets the board after `ticks` ticks of
+    /// ~16 microseconds each, unless it's started again or stopped first.
+    pub fn start(&mut self, ticks: u32) {
+        let rstc = self.registers.RSTC.read() & !RSTC_WRCFG_MASK;
+        self.registers.WDOG.write(PASSWORD | (ticks & WDOG_TICKS_MASK));
+        self.registers.RSTC.write(PASSWORD | rstc | RSTC_WRCFG_FULL_RESET);
+    }
+
+    /// Stops the watchdog if it's running.
+    pub fn stop(&mut self) {
+        let rstc = self.registers.RSTC.read() & !RSTC_WRCFG_MASK;
+        self.registers.RSTC.write(PASSWORD | rstc);
+    }
+}
+
+impl Default for Watchdog {
+    fn default() -> Watchdog {
+        Watchdog::new()
+    }
+}
+
+/// Resets the board through the watchdog. The firmware then boots again from
+/// the SD card.
+pub fn reset() -> ! {
+    Watchdog::new().start(10);
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -5,6 +5,8 @@ use volatile::prelude::*;
 
 use volatile::{ReadVolatile, Volatile};
 
+#[cfg(target_arch = "aarch64")]
+pub mod generic;
 
 /// The base address for the ARM system timer registers.
 const TIMER_REG_BASE: usize = IO_BASE + 0x3000;
@@ -20,6 +22,15 @@ struct Registers {
 
 }
 
+/// A compare channel of the system timer. Channels 0 and 2 are used by the
+/// GPU, so only 1 and 3 are available to the ARM.
+#[repr(u8)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Channel {
+    One = 1,
+    Three = 3,
+}
+
 /// The Raspberry Pi ARM system timer.
 pub struct Timer {
 
@@ -38,11 +49,58 @@ impl Timer {
     /// Reads the system timer's counter and returns Duration.
     /// `CLO` and `CHI` together can represent the number of elapsed microseconds.
     pub fn read(&self) -> Duration {
+        Duration::from_micros(self.read_micros())
+    }
+
+    /// Reads the 64-bit counter. `CHI` is read on both sides of `CLO` so that
+    /// `CLO` wrapping around in between isn't mistaken for a jump backwards
+    /// (or ~71 minutes ahead).
+    fn read_micros(&self) -> u64 {
+        loop {
+            let hi = self.registers.CHI.read();
+            let lo = self.registers.CLO.read();
+            if self.registers.CHI.read() == hi {
+                return ((hi as u64) << 32) | lo as u64;
+            }
+        }
+    }
+
+    /// Sets up a match in `channel` in `t` duration from now. If the match
+    /// interrupt is enabled in the interrupt controller, it fires then, until
+    /// it's cleared with `clear_match`.
+    ///
+    /// A channel only compares the counter's low 32 bits, so `t` can be at
+    /// most `u32::MAX` microseconds (~71 minutes); longer durations are
+    /// shortened to that. Durations too short to program in time match as soon
+    /// as possible instead of after the counter wraps around.
+    pub fn tick_in(&mut self, channel: Channel, t: Duration) {
+        let delta = t.as_micros().clamp(1, u32::MAX as u128) as u64;
+        let mut target = self.read_micros() + delta;
+        loop {
+            self.registers.COMPARE[channel as usize].write(target as u32);
+            if self.is_match(channel) || self.read_micros() < target {
+                return;
+            }
+
+            // The counter passed `target` before the compare was written.
+            target = self.read_micros() + 1;
+        }
+    }
+
+    /// Returns `true` if `channel` has matched since it was last cleared.
+    pub fn is_match(&self, channel: Channel) -> bool {
+        self.registers.CS.read() & (1 << channel as u32) != 0
+    }
 
-        let hi = self.registers.CHI.read() as u64;
-        let lo = self.registers.CLO.read() as u64;
-        Duration::from_micros((hi << 32) + lo)
+    /// Clears the match in `channel`, acknowledging its interrupt.
+    pub fn clear_match(&mut self, channel: Channel) {
+        self.registers.CS.write(1 << channel as u32);
+    }
+}
 
+impl Default for Timer {
+    fn default() -> Timer {
+        Timer::new()
     }
 }
 
@@ -55,11 +113,14 @@ pub fn current_time() -> Duration {
 
 /// Spins until `t` duration have passed.
 pub fn spin_sleep(t: Duration) {
-
-    let timer = Timer::new();
-    let target = timer.read().checked_add(t).unwrap().as_micros() as u32;
-    timer.registers.COMPARE[0].write(target);
-    while 0 == (timer.registers.CS.read() & 1) {}
-    timer.registers.CS.write(0);
+    let target = current_time() + t;
+    while current_time() < target {
+        core::hint::spin_loop();
+    }
 }
 
+/// Sets up a match in timer channel 1 in `t` duration from now. See
+/// `Timer::tick_in`.
+pub fn tick_in(t: Duration) {
+    Timer::new().tick_in(Channel::One, t);
+}
@@ -0,0 +1,54 @@
+//! The ARM generic timer's EL1 physical timer (`CNTP`), an alternative to the
+//! system timer that's private to each core. Its interrupt is routed through
+//! the core's local interrupt controller as `LocalInterrupt::CntPns`.
+
+use core::arch::asm;
+use core::time::Duration;
+
+/// `CNTP_CTL_EL0` bits.
+const CTL_ENABLE: u64 = 1 << 0;
+const CTL_ISTATUS: u64 = 1 << 2;
+
+/// Returns the counter's frequency in Hz, as set up by the firmware.
+pub fn frequency() -> u64 {
+    let freq: u64;
+    unsafe { asm!("mrs {}, CNTFRQ_EL0", out(reg) freq) };
+    freq
+}
+
+/// Returns the counter's current value.
+pub fn counter() -> u64 {
+    let count: u64;
+    unsafe { asm!("isb", "mrs {}, CNTPCT_EL0", out(reg) count) };
+    count
+}
+
+/// Returns the time since the counter started.
+pub fn current_time() -> Duration {
+    let (count, freq) = (counter() as u128, frequency() as u128);
+    Duration::from_nanos((count * 1_000_000_000 / freq) as u64)
+}
+
+/// Sets up a match in `t` duration from now and enables the timer. The match
+/// condition holds, and its interrupt stays asserted, until the timer is set
+/// up again or cleared with `clear_match`.
+pub fn tick_in(t: Duration) {
+    let ticks = (t.as_nanos() * frequency() as u128 / 1_000_000_000) as u64;
+    let target = counter().saturating_add(ticks);
+    unsafe {
+        asm!("msr CNTP_CVAL_EL0, {}", in(reg) target);
+        asm!("msr CNTP_CTL_EL0, {}", in(reg) CTL_ENABLE);
+    }
+}
+
+/// Returns `true` if the timer is enabled and has matched.
+pub fn is_match() -> bool {
+    let ctl: u64;
+    unsafe { asm!("mrs {}, CNTP_CTL_EL0", out(reg) ctl) };
+    ctl & CTL_ISTATUS != 0
+}
+
+/// Disables the timer, deasserting its interrupt.
+pub fn clear_match() {
+    unsafe { asm!("msr CNTP_CTL_EL0, {}", in(reg) 0u64) };
+}
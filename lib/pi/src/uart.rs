@@ -2,6 +2,7 @@ use core::fmt;
 use core::time::Duration;
 
 use shim::io;
+use shim::ioerr;
 use shim::const_assert_size;
 
 use volatile::prelude::*;
@@ -16,11 +17,22 @@ const MU_REG_BASE: usize = IO_BASE + 0x215040;
 /// The `AUXENB` register from page 9 of the BCM2837 documentation.
 const AUX_ENABLES: *mut Volatile<u8> = (IO_BASE + 0x215004) as *mut Volatile<u8>;
 
+/// The system (VPU core) clock the mini UART's baud rate is derived from.
+const SYSTEM_CLOCK_HZ: u32 = 250_000_000;
+
+/// The baud rate the mini UART starts with.
+pub const DEFAULT_BAUD_RATE: u32 = 115200;
+
+/// How far, in percent, the achievable baud rate may be off the requested one
+/// before the other end fails to keep up.
+const MAX_BAUD_ERROR_PERCENT: u32 = 2;
+
 /// Enum representing bit fields of the mini UART Line Status Register.
 #[repr(u8)]
 enum LsrStatus {
     DataReady   = 1,      // At least one byte in the receive FIFO.
     TxAvailable = 1 << 5, // There is space in the transmit FIFO.
+    TxIdle      = 1 << 6, // The transmit FIFO is empty and the line is idle.
 }
 
 #[repr(C)]
@@ -83,7 +95,7 @@ impl MiniUart {
         // Set data size to 8 bits (writing 3 to LCR configures 8-bit mode).
         uart.registers.lcr.write(3);
         // Set baud rate to 115200 (divider = 270).
-        uart.registers.baud.write(270);
+        uart.registers.baud.write(baud_divider(DEFAULT_BAUD_RATE));
         // Enable transmitter and receiver.
         uart.registers.cntl.write(3);
 
@@ -102,6 +114,46 @@ impl MiniUart {
         self.timeout = Some(t);
     }
 
+    /// Returns the current baud rate, which may differ slightly from the one
+    /// requested since only whole dividers are possible.
+    pub fn baud_rate(&self) -> u32 {
+        SYSTEM_CLOCK_HZ / (8 * (self.registers.baud.read() + 1))
+    }
+
+    /// Switches to the baud rate `baud`. Bytes still in the transmit FIFO are
+    /// sent at the old rate first. The other end has to switch as well, e.g.
+    /// with `ttywrite --baud`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `baud` can't be derived from
+    /// the system clock to within `MAX_BAUD_ERROR_PERCENT` percent, e.g. if
+    /// it's zero or faster than 31.25 Mbaud. The baud rate is left unchanged.
+    pub fn set_baud_rate(&mut self, baud: u32) -> io::Result<()> {
+        if baud == 0 || baud > SYSTEM_CLOCK_HZ / 8 {
+            return ioerr!(InvalidInput, "unsupported baud rate");
+        }
+
+        let divider = baud_divider(baud);
+        if divider > 0xFFFF {
+            return ioerr!(InvalidInput, "unsupported baud rate");
+        }
+
+        let actual = SYSTEM_CLOCK_HZ / (8 * (divider + 1));
+        if actual.abs_diff(baud) * 100 > baud * MAX_BAUD_ERROR_PERCENT {
+            return ioerr!(InvalidInput, "baud rate can't be derived from the system clock");
+        }
+
+        self.wait_for_idle();
+        self.registers.baud.write(divider);
+        Ok(())
+    }
+
+    /// Blocks until every byte written so far has left the transmitter.
+    pub fn wait_for_idle(&self) {
+        while (self.registers.lsr.read() & (LsrStatus::TxIdle as u32)) == 0 {}
+    }
+
     /// Write the byte `byte`. This method blocks until there is space available
     /// in the transmit FIFO.
     pub fn write_byte(&mut self, byte: u8) {
@@ -141,6 +193,12 @@ impl MiniUart {
     }
 }
 
+/// Returns the `baud` register value closest to `baud`, which must not be
+/// zero: `baudrate = system_clock_freq / (8 * (divider + 1))`.
+fn baud_divider(baud: u32) -> u32 {
+    ((SYSTEM_CLOCK_HZ + 4 * baud) / (8 * baud)).saturating_sub(1)
+}
+
 impl fmt::Write for MiniUart {
     /// Writes a string to the UART. Inserts a carriage return (`\r`)
     /// before every newline (`\n`).
@@ -193,6 +251,7 @@ mod uart_io {
         }
 
         fn flush(&mut self) -> io::Result<()> {
+            self.wait_for_idle();
             Ok(())
         }
     }
@@ -57,7 +57,6 @@ fn from_utf8_lossy<F>(mut input: &[u8], mut push: F) -> fmt::Result where F: FnM
         }
     }
     Ok(())
->>>>>>> lab4
 }
 
 // FIXME:
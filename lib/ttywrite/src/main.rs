@@ -3,14 +3,23 @@ extern crate structopt;
 #[macro_use]
 extern crate structopt_derive;
 extern crate xmodem;
+extern crate libc;
+extern crate termios;
 
-use std::path::PathBuf;
+mod parsers;
+mod progress;
+mod terminal;
+
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use structopt::StructOpt;
 use serial::core::{BaudRate, CharSize, FlowControl, SerialDevice, SerialPortSettings, StopBits};
-use xmodem::{Progress, Xmodem};
+use xmodem::{FileHeader, Mode, Xmodem, Ymodem, Zmodem};
+use parsers::{parse_baud_rate, parse_flow_control, parse_stop_bits, parse_width};
+use progress::ProgressBar;
 use std::fs::File;
-use std::io::{self, BufReader};
+use std::io::{self, BufReader, Cursor, Read, Seek, Write};
+use std::time::UNIX_EPOCH;
 
 #[derive(StructOpt, Debug)]
 #[structopt(about = "Write to TTY using the XMODEM protocol by default.")]
@@ -18,68 +27,211 @@ struct Opt {
     #[structopt(short = "i", help = "Input file (defaults to stdin if not set)")]
     input: Option<PathBuf>,
 
-    #[structopt(short = "b", long = "baud", help = "Set baud rate", default_value = "115200")]
-    baud_rate: String,  
+    #[structopt(short = "b", long = "baud", parse(try_from_str = "parse_baud_rate"),
+                help = "Set baud rate", default_value = "115200")]
+    baud_rate: BaudRate,
 
     #[structopt(short = "t", long = "timeout", help = "Set timeout in seconds", default_value = "10")]
     timeout: u64,
 
-    #[structopt(short = "w", long = "width", help = "Set data character width in bits", default_value = "8")]
-    char_width: String, 
+    #[structopt(short = "w", long = "width", parse(try_from_str = "parse_width"),
+                help = "Set data character width in bits", default_value = "8")]
+    char_width: CharSize,
 
     #[structopt(help = "Path to TTY device")]
     tty_path: PathBuf,
 
-    #[structopt(short = "f", long = "flow-control", help = "Enable flow control", default_value = "none")]
-    flow_control: String,  
+    #[structopt(short = "f", long = "flow-control", parse(try_from_str = "parse_flow_control"),
+                help = "Enable flow control", default_value = "none")]
+    flow_control: FlowControl,
 
-    #[structopt(short = "s", long = "stop-bits", help = "Set number of stop bits", default_value = "1")]
-    stop_bits: String,  
+    #[structopt(short = "s", long = "stop-bits", parse(try_from_str = "parse_stop_bits"),
+                help = "Set number of stop bits", default_value = "1")]
+    stop_bits: StopBits,
 
 
     #[structopt(short = "r", long = "raw", help = "Disable XMODEM")]
     raw: bool,
+
+    #[structopt(short = "y", long = "ymodem", help = "Use YMODEM to also send the file's name and size")]
+    ymodem: bool,
+
+    #[structopt(short = "z", long = "zmodem", help = "Use ZMODEM to stream the file with its name and size")]
+    zmodem: bool,
+
+    #[structopt(short = "R", long = "receive", help = "Receive (download) instead of sending")]
+    receive: bool,
+
+    #[structopt(short = "o", help = "Output file when receiving (defaults to stdout), or directory for YMODEM/ZMODEM files (defaults to the current one)")]
+    output: Option<PathBuf>,
+
+    #[structopt(short = "T", long = "then-terminal", help = "Open a serial terminal after the transfer")]
+    then_terminal: bool,
+
+    #[structopt(long = "escape", help = "Key that quits the terminal, e.g. '^]' or 'q'", default_value = "^]")]
+    escape: String,
+
+    #[structopt(long = "log", help = "Log everything the terminal receives to this file")]
+    log: Option<PathBuf>,
+
+    #[structopt(long = "newline", help = "Line ending the terminal sends for Enter: cr, lf or crlf", default_value = "cr")]
+    newline: String,
+
+    #[structopt(long = "crlf", help = "Show LF received by the terminal as CR LF")]
+    crlf: bool,
+}
+
+/// Returns the YMODEM/ZMODEM header for `file`, opened from `path`.
+fn file_header(path: &Path, file: &File) -> Result<FileHeader, Box<dyn std::error::Error>> {
+    let metadata = file.metadata()?;
+    let name = path.file_name()
+        .ok_or_else(|| format!("Invalid file name: {}", path.display()))?;
+    let mtime = metadata.modified().ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    Ok(FileHeader::new(name.to_string_lossy().as_bytes(), Some(metadata.len()), mtime)?)
+}
+
+/// Sends `input` as the only file of a YMODEM batch.
+fn send_ymodem<R: Read>(port: &mut serial::SystemPort, header: FileHeader, input: R) -> io::Result<usize> {
+    let mut bar = ProgressBar::new();
+    let mut ymodem = Ymodem::new_with_progress(port, |p| bar.update(p));
+    let written = ymodem.send_file(&header, input)?;
+    ymodem.finish()?;
+    bar.finish();
+    Ok(written)
+}
+
+/// How long a read of the serial port waits while sending with ZMODEM. The
+/// sender checks for a reply between subpackets, which mustn't hold up the
+/// stream; ZMODEM's own timeouts still apply.
+const ZMODEM_POLL_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Sends `input` as the only file of a ZMODEM session.
+fn send_zmodem<R: Read + Seek>(port: &mut serial::SystemPort, header: FileHeader, input: R) -> io::Result<u64> {
+    let timeout = port.timeout();
+    port.set_timeout(ZMODEM_POLL_TIMEOUT)?;
+
+    let mut bar = ProgressBar::new();
+    let mut zmodem = Zmodem::new_with_progress(&mut *port, |p| bar.update(p));
+    let result = zmodem.send_file(&header, input).and_then(|written| {
+        zmodem.finish()?;
+        Ok(written)
+    });
+    bar.finish();
+
+    port.set_timeout(timeout)?;
+    result
+}
+
+/// Receives a single file with XMODEM into `output`, showing a progress bar.
+/// Returns the number of bytes received, padding included.
+fn receive_xmodem<W: Write>(port: &mut serial::SystemPort, output: W) -> io::Result<usize> {
+    let mut bar = ProgressBar::new();
+    let mut xmodem = Xmodem::new_with_progress(port, |p| bar.update(p));
+    xmodem.set_mode(Mode::Crc);
+    let received = xmodem.receive_data(output)?;
+    bar.finish();
+    Ok(received)
+}
+
+/// Returns the name to save a received file under: the last component of the
+/// name the sender gave it.
+fn local_name(header: &FileHeader) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let name = String::from_utf8_lossy(header.name()).into_owned();
+    match Path::new(&name).file_name() {
+        Some(file_name) => Ok(PathBuf::from(file_name)),
+        None => Err(format!("Refusing to save a file named {:?}", name).into()),
+    }
+}
+
+/// Saves every file `next_file` receives until it returns `None`. Files go
+/// into the directory `output` under their own names or, if `output` is not a
+/// directory, to `output` itself, in which case only one file is allowed.
+fn receive_batch<F>(output: Option<&Path>, mut next_file: F) -> Result<(), Box<dyn std::error::Error>>
+    where F: FnMut(&mut Vec<u8>) -> io::Result<Option<FileHeader>>
+{
+    let mut saved = 0;
+    loop {
+        let mut data = vec![];
+        let header = match next_file(&mut data)? {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+
+        let path = match output {
+            Some(path) if !path.is_dir() => {
+                if saved > 0 {
+                    return Err(format!("{} can only hold one file, but more were sent", path.display()).into());
+                }
+                path.to_path_buf()
+            }
+            dir => dir.unwrap_or_else(|| Path::new(".")).join(local_name(&header)?),
+        };
+
+        File::create(&path)
+            .and_then(|mut file| file.write_all(&data))
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+        eprintln!("Received {} ({} bytes)", path.display(), data.len());
+        saved += 1;
+    }
+}
+
+/// Receives a YMODEM batch, saving its files as [`receive_batch()`] does.
+fn receive_ymodem(port: &mut serial::SystemPort, output: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut bar = ProgressBar::new();
+    let mut ymodem = Ymodem::new_with_progress(port, |p| bar.update(p));
+    receive_batch(output, |data| Ok(ymodem.receive_file(data)?.map(|(header, _)| header)))?;
+    bar.finish();
+    Ok(())
 }
 
-fn progress_fn(progress: Progress) {
-    println!("Progress: {:?}", progress);
+/// Receives a ZMODEM session, saving its files as [`receive_batch()`] does.
+fn receive_zmodem(port: &mut serial::SystemPort, output: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut bar = ProgressBar::new();
+    let mut zmodem = Zmodem::new_with_progress(port, |p| bar.update(p));
+    receive_batch(output, |data| Ok(zmodem.receive_file(data)?.map(|(header, _)| header)))?;
+    bar.finish();
+    Ok(())
+}
+
+/// Sends `input` with XMODEM, showing a progress bar. `total` is the size of
+/// `input`, if known.
+fn send_xmodem<R: Read>(port: &mut serial::SystemPort, total: Option<u64>, input: R) -> io::Result<usize> {
+    let mut bar = ProgressBar::new();
+    let mut xmodem = Xmodem::new_with_progress(port, |p| bar.update(p));
+    xmodem.set_total(total);
+    let written = xmodem.send_data(input)?;
+    bar.finish();
+    Ok(written)
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let opt = Opt::from_args();
 
-    // Convert read arguments to respective objects
-    let baud_rate = match opt.baud_rate.as_str() {
-        "1200" => BaudRate::Baud1200,
-        "2400" => BaudRate::Baud2400,
-        "4800" => BaudRate::Baud4800,
-        "9600" => BaudRate::Baud9600,
-        "19200" => BaudRate::Baud19200,
-        "38400" => BaudRate::Baud38400,
-        "57600" => BaudRate::Baud57600,
-        "115200" => BaudRate::Baud115200,
-        _ => return Err(format!("Invalid baud rate: {}", opt.baud_rate).into()),
-    };
-
-    let char_width = match opt.char_width.as_str() {
-        "5" => CharSize::Bits5,
-        "6" => CharSize::Bits6,
-        "7" => CharSize::Bits7,
-        "8" => CharSize::Bits8,
-        _ => return Err(format!("Invalid character width: {}", opt.char_width).into()),
-    };
+    if [opt.raw, opt.ymodem, opt.zmodem].iter().filter(|&&mode| mode).count() > 1 {
+        return Err("only one of --raw, --ymodem and --zmodem can be used".into());
+    }
 
-    let flow_control = match opt.flow_control.as_str() {
-        "none" => FlowControl::FlowNone,
-        "software" => FlowControl::FlowSoftware,
-        "hardware" => FlowControl::FlowHardware,
-        _ => return Err(format!("Invalid flow control: {}", opt.flow_control).into()),
-    };
+    if opt.receive && (opt.raw || opt.input.is_some()) {
+        return Err("--receive can't be used with --raw or -i".into());
+    }
 
-    let stop_bits = match opt.stop_bits.as_str() {
-        "1" => StopBits::Stop1,
-        "2" => StopBits::Stop2,
-        _ => return Err(format!("Invalid stop bits: {}", opt.stop_bits).into()),
+    // Check the terminal's settings before transferring anything.
+    let terminal = match opt.then_terminal {
+        true => Some(terminal::Options {
+            escape: terminal::parse_escape(&opt.escape)
+                .map_err(|e| format!("Invalid escape key {}: {}", opt.escape, e))?,
+            newline: terminal::parse_newline(&opt.newline)
+                .map_err(|e| format!("Invalid newline {}: {}", opt.newline, e))?,
+            crlf: opt.crlf,
+            log: match opt.log {
+                Some(ref path) => Some(File::create(path)
+                    .map_err(|e| format!("Error creating log file {}: {}", path.display(), e))?),
+                None => None,
+            },
+        }),
+        false => None,
     };
 
     // Open serial port
@@ -90,15 +242,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Read & Apply serial settings
     let mut settings = port.read_settings()?;
-    settings.set_baud_rate(baud_rate)?;
-    settings.set_stop_bits(stop_bits);
-    settings.set_flow_control(flow_control);
-    settings.set_char_size(char_width);
+    settings.set_baud_rate(opt.baud_rate)
+        .map_err(|_| format!("Baud rate {} isn't supported by {}", opt.baud_rate.speed(), opt.tty_path.display()))?;
+    settings.set_stop_bits(opt.stop_bits);
+    settings.set_flow_control(opt.flow_control);
+    settings.set_char_size(opt.char_width);
     port.write_settings(&settings)?;
     port.set_timeout(Duration::from_secs(opt.timeout))?;
 
+    // Receive Mode
+    if opt.receive {
+        if opt.ymodem {
+            receive_ymodem(&mut port, opt.output.as_deref())?;
+        } else if opt.zmodem {
+            receive_zmodem(&mut port, opt.output.as_deref())?;
+        } else {
+            match opt.output {
+                Some(ref path) => {
+                    let file = File::create(path)
+                        .map_err(|e| format!("Error creating file {}: {}", path.display(), e))?;
+                    receive_xmodem(&mut port, io::BufWriter::new(file))?;
+                }
+                None => {
+                    receive_xmodem(&mut port, io::stdout())?;
+                }
+            }
+        }
+    }
     // Raw Mode
-    if opt.raw {
+    else if opt.raw {
         match opt.input {
             Some(ref path) => {
                 let file = File::open(path)
@@ -112,20 +284,60 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         };
     } 
+    // YMODEM Mode
+    else if opt.ymodem {
+        match opt.input {
+            Some(ref path) => {
+                let file = File::open(path)
+                    .map_err(|e| format!("Error opening file {}: {}", path.display(), e))?;
+                let header = file_header(path, &file)?;
+                send_ymodem(&mut port, header, BufReader::new(file))?;
+            }
+            None => {
+                // The header needs the size up front, so buffer all of stdin.
+                let mut input = vec![];
+                io::stdin().read_to_end(&mut input)?;
+                let header = FileHeader::new(b"stdin", Some(input.len() as u64), None)?;
+                send_ymodem(&mut port, header, &input[..])?;
+            }
+        }
+    }
+    // ZMODEM Mode
+    else if opt.zmodem {
+        match opt.input {
+            Some(ref path) => {
+                let file = File::open(path)
+                    .map_err(|e| format!("Error opening file {}: {}", path.display(), e))?;
+                let header = file_header(path, &file)?;
+                send_zmodem(&mut port, header, BufReader::new(file))?;
+            }
+            None => {
+                // The receiver may ask to resume anywhere, so buffer all of stdin.
+                let mut input = vec![];
+                io::stdin().read_to_end(&mut input)?;
+                let header = FileHeader::new(b"stdin", Some(input.len() as u64), None)?;
+                send_zmodem(&mut port, header, Cursor::new(input))?;
+            }
+        }
+    }
     // XMODEM Mode
     else {
         match opt.input {
             Some(ref path) => {
                 let file = File::open(path)
                     .map_err(|e| format!("Error opening file {}: {}", path.display(), e))?;
-                let mut input = BufReader::new(file);
-                Xmodem::transmit_with_progress(input, &mut port, progress_fn)?;
+                let total = file.metadata()?.len();
+                send_xmodem(&mut port, Some(total), BufReader::new(file))?;
             }
             None => {
-                let mut input = io::stdin();
-                Xmodem::transmit_with_progress(input, &mut port, progress_fn)?;
+                send_xmodem(&mut port, None, io::stdin())?;
             }
         }
     }
+
+    if let Some(options) = terminal {
+        terminal::run(&mut port, options)?;
+    }
     
     Ok(())
+}
@@ -27,6 +27,11 @@ pub fn parse_flow_control(s: &str) -> Result<FlowControl, &str> {
     }
 }
 
-pub fn parse_baud_rate(s: &str) -> Result<BaudRate, ::std::num::ParseIntError> {
-    Ok(BaudRate::from_speed(s.parse()?))
+/// Parses any positive speed. Whether the TTY supports it is only known once
+/// it's applied to the device.
+pub fn parse_baud_rate(s: &str) -> Result<BaudRate, &str> {
+    match s.parse() {
+        Ok(0) | Err(_) => Err("value must be a positive integer, e.g. 115200 or 921600"),
+        Ok(speed) => Ok(BaudRate::from_speed(speed)),
+    }
 }
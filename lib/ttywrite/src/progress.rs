@@ -0,0 +1,110 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use xmodem::Progress;
+
+/// Width of the bar itself, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// A progress bar on stderr, driven by `xmodem::Progress` events.
+pub struct ProgressBar {
+    start: Instant,
+    bytes: u64,
+    total: Option<u64>,
+    drawn: bool,
+}
+
+impl ProgressBar {
+    pub fn new() -> ProgressBar {
+        ProgressBar { start: Instant::now(), bytes: 0, total: None, drawn: false }
+    }
+
+    /// Updates the bar with the progress event `progress`.
+    pub fn update(&mut self, progress: Progress) {
+        match progress {
+            Progress::Waiting => eprintln!("Waiting for receiver..."),
+            Progress::Started { total } => {
+                // Keep a finished file's bar. YMODEM headers have no total and
+                // are simply drawn over.
+                if self.drawn && self.total.is_some() {
+                    eprintln!();
+                }
+
+                self.start = Instant::now();
+                self.bytes = 0;
+                self.total = total;
+                self.draw();
+            }
+            Progress::Packet { bytes, total, .. } => {
+                self.bytes = bytes;
+                self.total = total;
+                self.draw();
+            }
+            Progress::Retry { number, attempt, cause } => {
+                eprintln!("\nPacket {} failed (attempt {}): {:?}", number, attempt, cause);
+            }
+            Progress::Failed(cause) => eprintln!("\nTransfer failed: {:?}", cause),
+        }
+    }
+
+    /// Ends the bar's line once the transfer is done.
+    pub fn finish(&self) {
+        if self.drawn {
+            eprintln!();
+        }
+    }
+
+    fn draw(&mut self) {
+        self.drawn = true;
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 { self.bytes as f64 / elapsed } else { 0.0 };
+
+        let mut line = String::new();
+        if let Some(total) = self.total {
+            // The last packet is padded, so the count can overshoot the total.
+            let bytes = self.bytes.min(total);
+            let fraction = if total == 0 { 1.0 } else { bytes as f64 / total as f64 };
+            let filled = (fraction * BAR_WIDTH as f64) as usize;
+            line.push('[');
+            line.extend((0..BAR_WIDTH).map(|i| match i {
+                i if i < filled => '=',
+                i if i == filled => '>',
+                _ => ' ',
+            }));
+            line.push_str(&format!("] {:>3}% ", (fraction * 100.0) as u32));
+            line.push_str(&format!("{} / {}", format_size(bytes as f64), format_size(total as f64)));
+            if rate > 0.0 {
+                let remaining = (total - bytes) as f64 / rate;
+                line.push_str(&format!("  {}/s  ETA {}", format_size(rate), format_eta(Duration::from_secs_f64(remaining))));
+            }
+        } else {
+            line.push_str(&format!("{}  {}/s", format_size(self.bytes as f64), format_size(rate)));
+        }
+
+        // Clear the rest of the line in case the previous one was longer.
+        eprint!("\r{}\x1B[K", line);
+        let _ = io::stderr().flush();
+    }
+}
+
+/// Formats `bytes` with a binary unit, e.g. `12.3 KiB`.
+fn format_size(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} {}", size as u64, UNITS[unit]),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+/// Formats `eta` as `m:ss`.
+fn format_eta(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
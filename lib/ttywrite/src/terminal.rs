@@ -0,0 +1,169 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use termios::{cfmakeraw, tcsetattr, Termios, TCSANOW};
+
+/// What to send to the device when Enter is pressed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Newline {
+    Cr,
+    Lf,
+    CrLf,
+}
+
+impl Newline {
+    fn bytes(self) -> &'static [u8] {
+        match self {
+            Newline::Cr => b"\r",
+            Newline::Lf => b"\n",
+            Newline::CrLf => b"\r\n",
+        }
+    }
+}
+
+pub fn parse_newline(s: &str) -> Result<Newline, &str> {
+    match s {
+        "cr" => Ok(Newline::Cr),
+        "lf" => Ok(Newline::Lf),
+        "crlf" => Ok(Newline::CrLf),
+        _ => Err("value must be 'cr', 'lf' or 'crlf'")
+    }
+}
+
+/// Parses an escape key given as a single character or in caret notation,
+/// e.g. `^]` for `0x1D`.
+pub fn parse_escape(s: &str) -> Result<u8, &str> {
+    match s.as_bytes() {
+        [b'^', c] if (b'@'..=b'_').contains(&c.to_ascii_uppercase()) => Ok(c.to_ascii_uppercase() - b'@'),
+        [c] if c.is_ascii() => Ok(*c),
+        _ => Err("value must be a single ASCII character or a control key like '^]'")
+    }
+}
+
+/// Formats the escape key `key` for humans, in caret notation if needed.
+fn describe_key(key: u8) -> String {
+    match key {
+        0..=0x1F => format!("^{}", (key + b'@') as char),
+        _ => format!("'{}'", key as char),
+    }
+}
+
+/// Settings for the interactive terminal.
+pub struct Options {
+    /// Key that quits the terminal.
+    pub escape: u8,
+    /// Sent to the device for Enter (a `CR` from the keyboard).
+    pub newline: Newline,
+    /// Print a `LF` received from the device as `CR LF`.
+    pub crlf: bool,
+    /// File receiving a copy of everything the device sends.
+    pub log: Option<File>,
+}
+
+/// Puts the terminal on `fd` into raw mode until dropped.
+struct RawMode {
+    fd: RawFd,
+    original: Termios,
+}
+
+impl RawMode {
+    fn enable(fd: RawFd) -> io::Result<RawMode> {
+        let original = Termios::from_fd(fd)?;
+        let mut raw = original;
+        cfmakeraw(&mut raw);
+        tcsetattr(fd, TCSANOW, &raw)?;
+        Ok(RawMode { fd, original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = tcsetattr(self.fd, TCSANOW, &self.original);
+    }
+}
+
+/// Connects stdin and stdout to `port` until the escape key is pressed, stdin
+/// ends or the device goes away.
+pub fn run<P: Read + Write + AsRawFd>(port: &mut P, mut options: Options) -> io::Result<()> {
+    let stdin = io::stdin().as_raw_fd();
+    let _raw = match unsafe { libc::isatty(stdin) } {
+        1 => Some(RawMode::enable(stdin)?),
+        _ => None,
+    };
+
+    let mut stdout = io::stdout();
+    write!(stdout, "Connected. Press {} to quit.\r\n", describe_key(options.escape))?;
+    stdout.flush()?;
+
+    let mut fds = [
+        libc::pollfd { fd: stdin, events: libc::POLLIN, revents: 0 },
+        libc::pollfd { fd: port.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+    ];
+
+    let mut buf = [0u8; 1024];
+    let mut out = Vec::with_capacity(2 * buf.len());
+    loop {
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } < 0 {
+            match io::Error::last_os_error() {
+                ref e if e.kind() == io::ErrorKind::Interrupted => continue,
+                e => return Err(e),
+            }
+        }
+
+        // Device to screen
+        if fds[1].revents != 0 {
+            let n = match port.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => 0,
+                Err(e) => {
+                    write!(stdout, "\r\nDisconnected: {}\r\n", e)?;
+                    break;
+                }
+            };
+
+            if let Some(ref mut log) = options.log {
+                log.write_all(&buf[..n])?;
+            }
+
+            out.clear();
+            for &byte in &buf[..n] {
+                if options.crlf && byte == b'\n' {
+                    out.push(b'\r');
+                }
+                out.push(byte);
+            }
+
+            stdout.write_all(&out)?;
+            stdout.flush()?;
+        }
+
+        // Keyboard to device
+        if fds[0].revents != 0 {
+            // Bypass `Stdin`'s buffer so `poll` sees everything that's pending.
+            let n = unsafe { libc::read(stdin, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+            if n <= 0 {
+                break;
+            }
+
+            out.clear();
+            for &byte in &buf[..n as usize] {
+                match byte {
+                    byte if byte == options.escape => {
+                        port.write_all(&out)?;
+                        write!(stdout, "\r\n")?;
+                        return Ok(());
+                    }
+                    b'\r' => out.extend_from_slice(options.newline.bytes()),
+                    byte => out.push(byte),
+                }
+            }
+
+            port.write_all(&out)?;
+        }
+    }
+
+    write!(stdout, "\r\n")?;
+    Ok(())
+}
@@ -1,7 +1,8 @@
 #! /bin/bash
 
 function cleanup_and_exit() {
-  kill $!
+  kill $(jobs -p)
+  rm -rf "${tmp}"
   exit $1
 }
 
@@ -55,5 +56,37 @@ for i in {1..10}; do
   fi
 done
 
+echo -e "${KBLU}Opening PTYs for receiving...${KNRM}"
+socat ${PARAMS},link=sender ${PARAMS},link=receiver &
+sleep 1
+
+tmp=$(mktemp -d)
+for mode in xmodem ymodem zmodem; do
+  echo -e "${KBLU}Running receive test (${mode}).${KNRM}"
+
+  case ${mode} in
+    xmodem) flag="" ;;
+    ymodem) flag="-y" ;;
+    zmodem) flag="-z" ;;
+  esac
+
+  head -c $((1 + RANDOM % 4096)) /dev/urandom > "${tmp}/sent"
+  ./target/debug/ttywrite -R ${flag} -o "${tmp}/received" receiver &
+  receiver=$!
+  if ! ./target/debug/ttywrite ${flag} -i "${tmp}/sent" sender || ! wait ${receiver}; then
+    echo -e "${KRED}ERROR: ${mode} transfer failed${KNRM}" >&2
+    cleanup_and_exit 1
+  fi
+
+  # XMODEM pads the file to a whole packet; YMODEM and ZMODEM don't.
+  size=$(wc -c < "${tmp}/sent")
+  [[ ${mode} = xmodem ]] && limit="-n ${size}" || limit=""
+  if ! cmp ${limit} "${tmp}/sent" "${tmp}/received"; then
+    echo -e "${KRED}ERROR: sent and received files differ${KNRM}" >&2
+    cleanup_and_exit 1
+  fi
+  rm "${tmp}/received"
+done
+
 echo -e "${KGRN}SUCCESS${KNRM}"
 cleanup_and_exit 0
@@ -0,0 +1,66 @@
+#! /bin/bash
+
+function cleanup_and_exit() {
+  kill $SOCAT_PID
+  rm -rf "${WORKDIR}"
+  exit $1
+}
+
+# Use color when outputting to the terminal.
+if [ -t 1 ]; then
+  KNRM="\x1B[0m"; KRED="\x1B[31m"; KGRN="\x1B[32m"; KBLU="\x1B[34m"
+else
+  KNRM=""; KRED=""; KGRN=""; KBLU=""
+fi
+
+for cmd in socat rz; do
+  if ! command -v ${cmd} > /dev/null 2>&1; then
+    echo >&2 "error: the '${cmd}' command is required but not installed"
+    echo >&2 "help: install the '$([ ${cmd} = rz ] && echo lrzsz || echo socat)' package using your package manager"
+    exit 1
+  fi
+done
+
+echo -e "${KBLU}Compiling project with 'cargo build'...${KNRM}"
+if ! cargo build; then
+  echo -e "${KRED}ERROR: ttywrite compilation failed${KNRM}" >&2
+  exit 1
+fi
+
+echo -e "${KBLU}Opening PTYs...${KNRM}"
+# Unlike test.sh, ZMODEM needs the receiver's replies, so the PTYs are linked
+# in both directions.
+PARAMS="pty,echo=0,raw,parenb=0,cs8,cstopb=0"
+socat ${PARAMS},link=input ${PARAMS},link=output &
+SOCAT_PID=$!
+sleep 1
+
+WORKDIR=$(mktemp -d)
+OUTPUT="$(pwd)/output"
+
+for i in {1..5}; do
+  echo -e "${KBLU}Running test ${i}/5.${KNRM}"
+
+  # random binary file between 1 byte and 256 KiB
+  head -c $((1 + (RANDOM * 8) % 262144)) < /dev/urandom > "${WORKDIR}/input.bin"
+  rm -f "${WORKDIR}/received/input.bin"
+  mkdir -p "${WORKDIR}/received"
+
+  (cd "${WORKDIR}/received" && rz -b -y < "${OUTPUT}" > "${OUTPUT}") &
+  RZ_PID=$!
+
+  if ! ./target/debug/ttywrite -z -i "${WORKDIR}/input.bin" input; then
+    echo -e "${KRED}ERROR: ttywrite failed${KNRM}" >&2
+    kill ${RZ_PID}
+    cleanup_and_exit 1
+  fi
+
+  wait ${RZ_PID}
+  if ! cmp -s "${WORKDIR}/input.bin" "${WORKDIR}/received/input.bin"; then
+    echo -e "${KRED}ERROR: input and output differ${KNRM}" >&2
+    cleanup_and_exit 1
+  fi
+done
+
+echo -e "${KGRN}SUCCESS${KNRM}"
+cleanup_and_exit 0
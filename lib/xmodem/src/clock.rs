@@ -0,0 +1,28 @@
+use core::time::Duration;
+
+/// Type for time sources.
+///
+/// A clock returns the time elapsed since some fixed point in the past. Only
+/// differences between two readings are ever used, so the point of reference
+/// doesn't matter. On the Raspberry Pi, `pi::timer::current_time` is a clock.
+pub type ClockFn = fn() -> Duration;
+
+/// Clock backed by `std::time::Instant`.
+#[cfg(not(feature = "no_std"))]
+pub fn std_clock() -> Duration {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed()
+}
+
+/// The clock used unless told otherwise: `std_clock` with `std` and none
+/// without it.
+#[cfg(not(feature = "no_std"))]
+pub const DEFAULT_CLOCK: Option<ClockFn> = Some(std_clock);
+
+/// The clock used unless told otherwise: `std_clock` with `std` and none
+/// without it.
+#[cfg(feature = "no_std")]
+pub const DEFAULT_CLOCK: Option<ClockFn> = None;
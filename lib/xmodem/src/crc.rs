@@ -0,0 +1,41 @@
+/// Polynomial used by CRC-16/XMODEM (x^16 + x^12 + x^5 + 1).
+const POLY: u16 = 0x1021;
+
+/// Computes the CRC-16/XMODEM of `buf`: polynomial `0x1021`, initial value
+/// `0`, no reflection and no final XOR. This is the check value XMODEM-CRC
+/// appends, high byte first, to every packet.
+pub fn crc16(buf: &[u8]) -> u16 {
+    buf.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ POLY
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Reflected polynomial used by CRC-32 (IEEE 802.3).
+const POLY32: u32 = 0xEDB8_8320;
+
+/// Computes the CRC-32 of `buf` as used by ZMODEM, Ethernet and zlib:
+/// reflected polynomial `0xEDB88320`, initial value and final XOR `!0`.
+pub fn crc32(buf: &[u8]) -> u32 {
+    !crc32_update(!0, buf)
+}
+
+/// Feeds `buf` into the CRC-32 register `crc`, without the final XOR. Start
+/// with `!0` and invert the result to checksum data spread over several
+/// buffers.
+pub fn crc32_update(crc: u32, buf: &[u8]) -> u32 {
+    buf.iter().fold(crc, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ POLY32
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
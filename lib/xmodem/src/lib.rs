@@ -2,35 +2,100 @@
 //refrence:chatgpt
 #![feature(decl_macro)]
 
+use core::time::Duration;
+
 use shim::io;
 use shim::ioerr;
 
 #[cfg(test)] mod tests;
 mod read_ext;
 mod progress;
+mod crc;
+mod clock;
+mod ymodem;
+mod zmodem;
 
 pub use progress::{Progress, ProgressFn};
+pub use clock::ClockFn;
+#[cfg(not(feature = "no_std"))]
+pub use clock::std_clock;
+pub use ymodem::{FileHeader, Ymodem};
+pub use zmodem::Zmodem;
 
 use read_ext::ReadExt;
+use crc::crc16;
 
 const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
 const EOT: u8 = 0x04;
 const ACK: u8 = 0x06;
 const NAK: u8 = 0x15;
 const CAN: u8 = 0x18;
+const CRC: u8 = b'C';
+
+/// Number of times a CRC receiver sends `C` before falling back to checksums.
+const CRC_ATTEMPTS: usize = 3;
+
+/// Payload size of a `SOH` packet.
+const PACKET_SIZE: usize = 128;
+/// Payload size of an XMODEM-1K `STX` packet.
+const PACKET_SIZE_1K: usize = 1024;
+
+/// Default time to wait for each byte of a packet after its first.
+const BYTE_TIMEOUT: Duration = Duration::from_secs(1);
+/// Default time to wait for the start of a packet or for the reply to one.
+const PACKET_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default number of times a packet is retried before cancelling the transfer.
+const MAX_RETRIES: usize = 10;
+
+/// The integrity check appended to each packet.
+///
+/// The receiver picks the mode by how it starts the transfer: `NAK` asks for
+/// checksums while `C` asks for CRCs. A transmitter always uses whichever mode
+/// the receiver asked for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// The original 8-bit additive checksum.
+    #[default]
+    Checksum,
+    /// CRC-16/XMODEM, sent high byte first.
+    Crc,
+}
 
 /// Implementation of the XMODEM protocol.
-pub struct Xmodem<R> {
+///
+/// Timeouts are measured with a [`ClockFn`] and require reads from the inner
+/// stream to return regularly with `TimedOut` or `WouldBlock` when no data is
+/// available, as a serial port with a read timeout does. A read that blocks
+/// forever can't time out. Without a clock, such an error fails the current
+/// operation immediately.
+///
+/// Progress is reported to a callback of type `P`, which can be any
+/// `FnMut(Progress)`. See [`Xmodem::new_with_progress()`].
+pub struct Xmodem<R, P = ProgressFn> {
     packet: u8,
     started: bool,
+    /// The mode in use for the current transfer.
+    mode: Mode,
+    /// The mode each transfer is requested in when receiving.
+    requested: Mode,
+    /// Whether a CRC request nobody answers falls back to checksums.
+    fallback: bool,
     inner: R,
-    progress: ProgressFn
+    progress: P,
+    bytes: u64,
+    total: Option<u64>,
+    clock: Option<ClockFn>,
+    byte_timeout: Duration,
+    packet_timeout: Duration,
+    max_retries: usize,
 }
 
 impl Xmodem<()> {
     /// Transmits `data` to the receiver `to` using the XMODEM protocol. If the
     /// length of the total data yielded by `data` is not a multiple of 128
-    /// bytes, the data is padded with zeroes and sent to the receiver.
+    /// bytes, the data is padded with zeroes and sent to the receiver. The
+    /// checksum or CRC mode is chosen by the receiver.
     ///
     /// Returns the number of bytes written to `to`, excluding padding zeroes.
     #[inline]
@@ -44,38 +109,18 @@ impl Xmodem<()> {
     /// length of the total data yielded by `data` is not a multiple of 128
     /// bytes, the data is padded with zeroes and sent to the receiver.
     ///
+    /// When the receiver asks for CRCs, full 1024-byte blocks are sent as
+    /// XMODEM-1K `STX` packets. The remaining tail is sent in 128-byte packets
+    /// to keep padding small.
+    ///
     /// The function `f` is used as a callback to indicate progress throughout
     /// the transmission. See the [`Progress`] enum for more information.
     ///
     /// Returns the number of bytes written to `to`, excluding padding zeroes.
-    pub fn transmit_with_progress<R, W>(mut data: R, to: W, f: ProgressFn) -> io::Result<usize>
-        where W: io::Read + io::Write, R: io::Read
+    pub fn transmit_with_progress<R, W, P>(data: R, to: W, f: P) -> io::Result<usize>
+        where W: io::Read + io::Write, R: io::Read, P: FnMut(Progress)
     {
-        let mut transmitter = Xmodem::new_with_progress(to, f);
-        let mut packet = [0u8; 128];
-        let mut written = 0;
-        'next_packet: loop {
-            let n = data.read_max(&mut packet)?;
-            packet[n..].iter_mut().for_each(|b| *b = 0);
-
-            if n == 0 {
-                transmitter.write_packet(&[])?;
-                return Ok(written);
-            }
-
-            for _ in 0..10 {
-                match transmitter.write_packet(&packet) {
-                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                    Err(e) => return Err(e),
-                    Ok(_) => {
-                        written += n;
-                        continue 'next_packet;
-                    }
-                }
-            }
-
-            return ioerr!(BrokenPipe, "bad transmit");
-        }
+        Xmodem::new_with_progress(to, f).send_data(data)
     }
 
     /// Receives `data` from `from` using the XMODEM protocol and writes it into
@@ -87,35 +132,31 @@ impl Xmodem<()> {
         Xmodem::receive_with_progress(from, into, progress::noop)
     }
 
+    /// Receives `data` from `from` using the XMODEM protocol in mode `mode` and
+    /// writes it into `into`. Returns the number of bytes read from `from`, a
+    /// multiple of 128.
+    ///
+    /// In `Mode::Crc`, the transfer falls back to `Mode::Checksum` if the
+    /// sender doesn't answer the `C` requests before reads from `from` time
+    /// out. See [`Xmodem::read_packet()`] for details.
+    #[inline]
+    pub fn receive_with_mode<R, W>(from: R, into: W, mode: Mode) -> io::Result<usize>
+       where R: io::Read + io::Write, W: io::Write
+    {
+        Xmodem::new_with_mode(from, mode).receive_data(into)
+    }
+
     /// Receives `data` from `from` using the XMODEM protocol and writes it into
     /// `into`. Returns the number of bytes read from `from`, a multiple of 128.
+    /// If writing to `into` fails, the transfer is cancelled with `CAN` and the
+    /// write error is returned.
     ///
     /// The function `f` is used as a callback to indicate progress throughout
     /// the reception. See the [`Progress`] enum for more information.
-    pub fn receive_with_progress<R, W>(from: R, mut into: W, f: ProgressFn) -> io::Result<usize>
-       where R: io::Read + io::Write, W: io::Write
+    pub fn receive_with_progress<R, W, P>(from: R, into: W, f: P) -> io::Result<usize>
+       where R: io::Read + io::Write, W: io::Write, P: FnMut(Progress)
     {
-        let mut receiver = Xmodem::new_with_progress(from, f);
-        let mut packet = [0u8; 128];
-        let mut received = 0;
-        'next_packet: loop {
-            for _ in 0..10 {
-                match receiver.read_packet(&mut packet) {
-                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                    Err(e) => return Err(e),
-                    Ok(0) => break 'next_packet,
-                    Ok(n) => {
-                        received += n;
-                        into.write_all(&packet)?;
-                        continue 'next_packet;
-                    }
-                }
-            }
-
-            return ioerr!(BrokenPipe, "bad receive");
-        }
-
-        Ok(received)
+        Xmodem::new_with_progress(from, f).receive_data(into)
     }
 }
 
@@ -123,21 +164,197 @@ fn get_checksum(buf: &[u8]) -> u8 {
     return buf.iter().fold(0, |a, b| a.wrapping_add(*b));
 }
 
+/// Returns `true` if `e` means no data was available in time.
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock)
+}
+
 impl<T: io::Read + io::Write> Xmodem<T> {
     /// Returns a new `Xmodem` instance with the internal reader/writer set to
     /// `inner`. The returned instance can be used for both receiving
     /// (downloading) and sending (uploading).
     pub fn new(inner: T) -> Self {
-        Xmodem { packet: 1, started: false, inner, progress: progress::noop}
+        Xmodem::new_with_progress(inner, progress::noop as ProgressFn)
+    }
+
+    /// Returns a new `Xmodem` instance with the internal reader/writer set to
+    /// `inner` that requests `mode` when receiving. When sending, the mode is
+    /// always the one requested by the receiver.
+    pub fn new_with_mode(inner: T, mode: Mode) -> Self {
+        Xmodem { mode, requested: mode, ..Xmodem::new(inner) }
     }
+}
 
+impl<T: io::Read + io::Write, P: FnMut(Progress)> Xmodem<T, P> {
     /// Returns a new `Xmodem` instance with the internal reader/writer set to
     /// `inner`. The returned instance can be used for both receiving
     /// (downloading) and sending (uploading). The function `f` is used as a
     /// callback to indicate progress throughout the transfer. See the
     /// [`Progress`] enum for more information.
-    pub fn new_with_progress(inner: T, f: ProgressFn) -> Self {
-        Xmodem { packet: 1, started: false, inner, progress: f }
+    ///
+    /// Unlike a plain `fn`, a closure passed as `f` can keep state between
+    /// calls, such as the time the transfer started.
+    pub fn new_with_progress(inner: T, f: P) -> Self {
+        Xmodem {
+            packet: 1,
+            started: false,
+            mode: Mode::Checksum,
+            requested: Mode::Checksum,
+            fallback: true,
+            inner,
+            progress: f,
+            bytes: 0,
+            total: None,
+            clock: clock::DEFAULT_CLOCK,
+            byte_timeout: BYTE_TIMEOUT,
+            packet_timeout: PACKET_TIMEOUT,
+            max_retries: MAX_RETRIES,
+        }
+    }
+
+    /// Sets the time source used to measure timeouts. Defaults to
+    /// [`std_clock`] when built with `std` and to `None` otherwise.
+    pub fn set_clock(&mut self, clock: Option<ClockFn>) {
+        self.clock = clock;
+    }
+
+    /// Sets how long to wait for each byte of a packet after its first
+    /// (`byte`, 1 second by default) and how long to wait for the start of a
+    /// packet or for the reply to one (`packet`, 10 seconds by default).
+    pub fn set_timeouts(&mut self, byte: Duration, packet: Duration) {
+        self.byte_timeout = byte;
+        self.packet_timeout = packet;
+    }
+
+    /// Sets how many times a packet is retried after being rejected or timing
+    /// out before the transfer is cancelled with `CAN CAN`. Defaults to 10.
+    pub fn set_max_retries(&mut self, retries: usize) {
+        self.max_retries = retries;
+    }
+
+    /// Sets the number of bytes the next transfer is expected to carry, which
+    /// is passed on to the progress callback. XMODEM itself doesn't transmit
+    /// it, so it's unknown by default.
+    pub fn set_total(&mut self, total: Option<u64>) {
+        self.total = total;
+    }
+
+    /// Transmits everything `data` yields as a sequence of packets followed by
+    /// the end of transmission. See [`Xmodem::transmit_with_progress()`] for
+    /// details. Returns the number of bytes read from `data`.
+    pub fn send_data<R: io::Read>(&mut self, data: R) -> io::Result<usize> {
+        let result = self.send_blocks(data);
+        self.report_failure(result)
+    }
+
+    /// Sends the blocks making up `data`. See [`Xmodem::send_data()`].
+    fn send_blocks<R: io::Read>(&mut self, mut data: R) -> io::Result<usize> {
+        let mut block = [0u8; PACKET_SIZE_1K];
+        let mut written = 0;
+        loop {
+            let n = data.read_max(&mut block)?;
+            if n == 0 {
+                self.write_packet(&[])?;
+                return Ok(written);
+            }
+
+            // The receiver's first byte tells us whether 1K packets are okay.
+            self.wait_for_start()?;
+            let size = match self.mode {
+                Mode::Crc if n == PACKET_SIZE_1K => PACKET_SIZE_1K,
+                _ => PACKET_SIZE,
+            };
+
+            let padded = n.div_ceil(PACKET_SIZE) * PACKET_SIZE;
+            block[n..padded].iter_mut().for_each(|b| *b = 0);
+            for packet in block[..padded].chunks(size) {
+                self.write_packet_retrying(packet)?;
+            }
+
+            written += n;
+        }
+    }
+
+    /// Receives packets until the sender ends the transmission, writing them
+    /// into `into`. See [`Xmodem::receive_with_progress()`] for details.
+    /// Returns the number of bytes received, padding included.
+    pub fn receive_data<W: io::Write>(&mut self, into: W) -> io::Result<usize> {
+        let result = self.receive_blocks(into);
+        self.report_failure(result)
+    }
+
+    /// Receives packets into `into`. See [`Xmodem::receive_data()`].
+    fn receive_blocks<W: io::Write>(&mut self, mut into: W) -> io::Result<usize> {
+        let mut packet = [0u8; PACKET_SIZE_1K];
+        let mut received = 0;
+        loop {
+            let n = self.read_packet_retrying(&mut packet)?;
+            if n == 0 {
+                return Ok(received);
+            }
+
+            received += n;
+            if let Err(e) = into.write_all(&packet[..n]) {
+                // There's nowhere to put the data: tell the sender to stop
+                // instead of letting it keep going.
+                self.write_byte(CAN)?;
+                return Err(e);
+            }
+        }
+    }
+
+    /// Reads a packet into `buf` with [`Xmodem::read_packet()`], retrying while
+    /// the packet fails with `Interrupted`. Cancels the transfer with `CAN CAN`
+    /// once the retry limit is exceeded.
+    fn read_packet_retrying(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        for attempt in 1..=self.max_retries + 1 {
+            match self.read_packet(buf) {
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => self.report_retry(attempt, e),
+                result => return result,
+            }
+        }
+
+        self.cancel()?;
+        ioerr!(BrokenPipe, "bad receive")
+    }
+
+    /// Reports a failed attempt at transferring the current packet.
+    fn report_retry(&mut self, attempt: usize, cause: &io::Error) {
+        let number = self.packet;
+        (self.progress)(Progress::Retry { number, attempt, cause: cause.kind() });
+    }
+
+    /// Reports the transfer as failed if `result` is an error and returns it.
+    fn report_failure<X>(&mut self, result: io::Result<X>) -> io::Result<X> {
+        if let Err(ref e) = result {
+            (self.progress)(Progress::Failed(e.kind()));
+        }
+
+        result
+    }
+
+    /// Reports the start of a transfer and resets the byte count.
+    fn report_start(&mut self) {
+        self.bytes = 0;
+        (self.progress)(Progress::Started { total: self.total });
+    }
+
+    /// Reports packet `number` of `size` bytes as transferred.
+    fn report_packet(&mut self, number: u8, size: usize) {
+        self.bytes += size as u64;
+        (self.progress)(Progress::Packet { number, bytes: self.bytes, total: self.total });
+    }
+
+    /// Returns the integrity check mode in use. Once a transfer has started,
+    /// this is the mode both sides agreed on.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Sets the mode requested when receiving. See [`Xmodem::new_with_mode()`].
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.requested = mode;
     }
 
     /// Reads a single byte from the inner I/O stream. If `abort_on_can` is
@@ -149,10 +366,7 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// Returns an error if reading from the inner stream fails or if
     /// `abort_on_can` is `true` and the read byte is `CAN`.
     fn read_byte(&mut self, abort_on_can: bool) -> io::Result<u8> {
-        let mut buf = [0u8; 1];
-        self.inner.read_exact(&mut buf)?;
-
-        let byte = buf[0];
+        let byte = self.read_byte_within(self.byte_timeout)?;
         if abort_on_can && byte == CAN {
             return ioerr!(ConnectionAborted, "received CAN");
         }
@@ -160,6 +374,67 @@ impl<T: io::Read + io::Write> Xmodem<T> {
         Ok(byte)
     }
 
+    /// Reads a single byte from the inner I/O stream, waiting at most
+    /// `timeout` for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from the inner stream fails. Returns the
+    /// inner stream's `TimedOut` or `WouldBlock` error once `timeout` expires.
+    fn read_byte_within(&mut self, timeout: Duration) -> io::Result<u8> {
+        let mut buf = [0u8; 1];
+        self.read_exact_within(&mut buf, timeout)?;
+        Ok(buf[0])
+    }
+
+    /// Fills `buf` from the inner I/O stream, waiting at most `timeout` for
+    /// each read to make progress. Reads that time out or would block are
+    /// retried until then.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `UnexpectedEof` if the inner stream ends early.
+    /// Otherwise, returns the inner stream's error. For reads that time out or
+    /// would block, that only happens once `timeout` has expired or if there's
+    /// no clock to tell.
+    fn read_exact_within(&mut self, mut buf: &mut [u8], timeout: Duration) -> io::Result<()> {
+        let mut start = self.now();
+        while !buf.is_empty() {
+            match self.inner.read(buf) {
+                Ok(0) => return ioerr!(UnexpectedEof, "failed to fill whole buffer"),
+                Ok(n) => {
+                    let tmp = buf;
+                    buf = &mut tmp[n..];
+                    start = self.now();
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref e) if is_timeout(e) && !self.expired(start, timeout) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the current time if there's a clock.
+    fn now(&self) -> Option<Duration> {
+        self.clock.map(|now| now())
+    }
+
+    /// Returns `true` if `timeout` has passed since `start`. Without a clock,
+    /// every timeout counts as expired.
+    fn expired(&self, start: Option<Duration>, timeout: Duration) -> bool {
+        match (self.now(), start) {
+            (Some(now), Some(start)) => now.saturating_sub(start) >= timeout,
+            _ => true,
+        }
+    }
+
+    /// Cancels the transfer by writing `CAN CAN` to the inner stream.
+    fn cancel(&mut self) -> io::Result<()> {
+        self.inner.write_all(&[CAN, CAN])
+    }
+
     /// Writes a single byte to the inner I/O stream.
     ///
     /// # Errors
@@ -181,17 +456,23 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// Returns an error if reading from the inner stream fails, if the read
     /// byte was not `byte`, if the read byte was `CAN` and `byte` is not `CAN`,
     /// or if writing the `CAN` byte failed on byte mismatch.
-    
     fn expect_byte_or_cancel(&mut self, byte: u8, expected: &'static str) -> io::Result<u8> {
         let received = self.read_byte(false)?;
         if received == byte {
             Ok(received)
         } else {
-            self.write_byte(CAN)?;
-            match received {
-                CAN => ioerr!(ConnectionAborted, "Received CAN"),
-                _ => ioerr!(InvalidData, expected),
-            }
+            self.cancel_unexpected(received, expected)
+        }
+    }
+
+    /// Writes a `CAN` byte in reply to the unexpected byte `received` and
+    /// returns the matching error: `ConnectionAborted` if `received` is `CAN`
+    /// and `InvalidData` with the message `expected` otherwise.
+    fn cancel_unexpected<X>(&mut self, received: u8, expected: &'static str) -> io::Result<X> {
+        self.write_byte(CAN)?;
+        match received {
+            CAN => ioerr!(ConnectionAborted, "Received CAN"),
+            _ => ioerr!(InvalidData, expected),
         }
     }
 
@@ -206,7 +487,6 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// byte was not `byte`. If the read byte differed and was `CAN`, an error
     /// of `ConnectionAborted` is returned. Otherwise, the error kind is
     /// `InvalidData`.
-    
     fn expect_byte(&mut self, byte: u8, expected: &'static str) -> io::Result<u8> {
         let received = self.read_byte(false)?;
         match received {
@@ -215,10 +495,40 @@ impl<T: io::Read + io::Write> Xmodem<T> {
             _ => ioerr!(InvalidData, expected),
         }
     }
-    
+
+
+    /// Asks the sender to start transmitting, using `C` in CRC mode and `NAK`
+    /// in checksum mode, and returns the first byte the sender replies with.
+    /// Falls back to checksum mode for this transfer if every `C` request
+    /// times out, unless the fallback is disabled; the next one is requested
+    /// in the configured mode again.
+    fn request_start(&mut self) -> io::Result<u8> {
+        self.mode = self.requested;
+        if self.mode == Mode::Crc {
+            for _ in 0..CRC_ATTEMPTS {
+                self.write_byte(CRC)?;
+                match self.read_byte(false) {
+                    Err(ref e) if is_timeout(e) => continue,
+                    result => return result,
+                }
+            }
+
+            if !self.fallback {
+                return ioerr!(TimedOut, "no reply to CRC request");
+            }
+
+            self.mode = Mode::Checksum;
+        }
+
+        self.write_byte(NAK)?;
+        self.read_byte_within(self.packet_timeout)
+    }
 
     /// Reads (downloads) a single packet from the inner stream using the XMODEM
-    /// protocol. On success, returns the number of bytes read (always 128).
+    /// protocol. On success, returns the number of bytes read: 128 for a `SOH`
+    /// packet or 1024 for an XMODEM-1K `STX` packet. Reading into a buffer
+    /// shorter than 1024 bytes works as long as the sender only sends `SOH`
+    /// packets.
     ///
     /// The progress callback is called with `Progress::Started` when reception
     /// for the first packet has started and subsequently with
@@ -230,64 +540,188 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// point. Also returns an error if the XMODEM protocol indicates an error.
     /// In particular, an `InvalidData` error is returned when:
     ///
-    ///   * The sender's first byte for a packet isn't `EOT` or `SOH`.
+    ///   * The sender's first byte for a packet isn't `EOT`, `SOH` or `STX`.
     ///   * The sender doesn't send a second `EOT` after the first.
     ///   * The received packet numbers don't match the expected values.
     ///
-    /// An error of kind `Interrupted` is returned if a packet checksum fails.
+    /// An error of kind `Interrupted` is returned if a packet checksum fails or
+    /// if the sender stops sending in the middle of a transfer. In both cases
+    /// the packet is rejected with a `NAK`, asking for it to be resent.
     ///
     /// An error of kind `ConnectionAborted` is returned if a `CAN` byte is
     /// received when not expected.
     ///
-    /// An error of kind `UnexpectedEof` is returned if `buf.len() < 128`.
+    /// An error of kind `UnexpectedEof` is returned if `buf.len() < 128`, or if
+    /// an `STX` packet arrives and `buf.len() < 1024`. In the latter case the
+    /// transfer is cancelled.
+    ///
+    /// In `Mode::Crc`, the first packet is requested with `C` up to three
+    /// times. If every request times out, the receiver switches to
+    /// `Mode::Checksum` for the rest of the transfer and requests the packet
+    /// with `NAK` instead. If that times out too, the inner stream's timeout
+    /// error is returned.
+    ///
+    /// A resent copy of the previous packet, sent because our `ACK` got lost,
+    /// is acknowledged and skipped.
     pub fn read_packet(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if buf.len() < 128 {
             return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Buffer size too small"));
         }
-        if !self.started {
-            self.write_byte(NAK)?;
-            self.started = true;
+
+        loop {
+            let start_byte = if !self.started {
+                self.started = true;
+                self.report_start();
+                self.request_start()?
+            } else {
+                match self.read_byte_within(self.packet_timeout) {
+                    Err(ref e) if is_timeout(e) => return self.reject("Timed out waiting for packet"),
+                    result => result?,
+                }
+            };
+
+            match self.read_packet_from(start_byte, buf) {
+                Ok(Some(n)) => return Ok(n),
+                Ok(None) => continue,
+                Err(ref e) if is_timeout(e) => return self.reject("Timed out reading packet"),
+                Err(e) => return Err(e),
+            }
         }
-    
-        let start_byte = self.read_byte(false)?;
+    }
+
+    /// Rejects the packet being received with a `NAK` and returns an error of
+    /// kind `Interrupted` with the message `msg`.
+    fn reject<X>(&mut self, msg: &'static str) -> io::Result<X> {
+        self.write_byte(NAK)?;
+        Err(io::Error::new(io::ErrorKind::Interrupted, msg))
+    }
+
+    /// Reads the rest of the packet starting with `start_byte` into `buf`. See
+    /// [`Xmodem::read_packet()`]. Returns `None` for a duplicate packet.
+    fn read_packet_from(&mut self, start_byte: u8, buf: &mut [u8]) -> io::Result<Option<usize>> {
         match start_byte {
             CAN => {
                 self.write_byte(CAN)?;
-                return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "Received CAN at start"));
+                Err(io::Error::new(io::ErrorKind::ConnectionAborted, "Received CAN at start"))
             }
             EOT => {
                 self.write_byte(NAK)?;
                 self.expect_byte(EOT, "Expected EOT")?;
                 self.write_byte(ACK)?;
                 self.started = false;
-                return Ok(0);
+                self.packet = 1;
+                Ok(Some(0))
             }
-            SOH => {
+            SOH | STX => {
                 self.started = true;
-    
+                let size = if start_byte == STX { PACKET_SIZE_1K } else { PACKET_SIZE };
+                if buf.len() < size {
+                    self.write_byte(CAN)?;
+                    return ioerr!(UnexpectedEof, "Buffer too small for 1K packet");
+                }
+
                 let packet_num = self.read_byte(false)?;
                 let packet_num_complement = self.read_byte(false)?;
                 if packet_num != 255 - packet_num_complement {
                     return Err(io::Error::new(io::ErrorKind::InvalidData, "Packet numbers do not match"));
                 }
-    
+
                 // Ensure full packet is read
-                self.inner.read_exact(buf).map_err(|_| io::Error::new(io::ErrorKind::UnexpectedEof, "Failed to read full packet"))?;
-    
-                let checksum = self.read_byte(false)?;
-                if get_checksum(buf) != checksum {
-                    self.write_byte(NAK)?;
-                    return Err(io::Error::new(io::ErrorKind::Interrupted, "Checksum mismatch"));
+                match self.read_exact_within(&mut buf[..size], self.byte_timeout) {
+                    Err(ref e) if is_timeout(e) => return Err(io::Error::new(e.kind(), "Timed out reading packet")),
+                    Err(_) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Failed to read full packet")),
+                    Ok(()) => {}
                 }
-    
+
+                let valid = match self.mode {
+                    Mode::Checksum => self.read_byte(false)? == get_checksum(&buf[..size]),
+                    Mode::Crc => {
+                        let hi = self.read_byte(false)?;
+                        let lo = self.read_byte(false)?;
+                        u16::from_be_bytes([hi, lo]) == crc16(&buf[..size])
+                    }
+                };
+
+                if !valid {
+                    return self.reject("Checksum mismatch");
+                }
+
+                if packet_num == self.packet.wrapping_sub(1) {
+                    // The sender didn't get our ACK and sent the packet again.
+                    self.write_byte(ACK)?;
+                    return Ok(None);
+                } else if packet_num != self.packet {
+                    self.cancel()?;
+                    return ioerr!(InvalidData, "Unexpected packet number");
+                }
+
                 self.write_byte(ACK)?;
+                self.report_packet(packet_num, size);
                 self.packet = self.packet.wrapping_add(1);
-                return Ok(128);
+                Ok(Some(size))
             }
-            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Expected SOH")),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Expected SOH or STX")),
         }
     }
-    
+
+
+    /// Waits for the receiver to start the transfer, if it hasn't already, and
+    /// adopts the mode it asks for: `NAK` for checksums or `C` for CRCs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `ConnectionAborted` if the receiver sends `CAN`
+    /// and `InvalidData` if it sends any other byte. In both cases `CAN` is
+    /// written back to the receiver. If the receiver stays silent for as many
+    /// packet timeouts as there are retries, the transfer is cancelled with
+    /// `CAN CAN` and the timeout error is returned.
+    fn wait_for_start(&mut self) -> io::Result<()> {
+        if self.started {
+            return Ok(());
+        }
+
+        (self.progress)(Progress::Waiting);
+        let mut attempts = 0;
+        let first = loop {
+            match self.read_byte_within(self.packet_timeout) {
+                Err(ref e) if is_timeout(e) && attempts < self.max_retries => attempts += 1,
+                Err(e) => {
+                    if is_timeout(&e) {
+                        self.cancel()?;
+                    }
+                    return Err(e);
+                }
+                Ok(byte) => break byte,
+            }
+        };
+
+        self.mode = match first {
+            NAK => Mode::Checksum,
+            CRC => Mode::Crc,
+            byte => return self.cancel_unexpected(byte, "no start"),
+        };
+
+        self.started = true;
+        self.report_start();
+        Ok(())
+    }
+
+    /// Sends `packet` with [`Xmodem::write_packet()`], resending it while the
+    /// receiver rejects it or doesn't reply in time. Cancels the transfer with
+    /// `CAN CAN` once the retry limit is exceeded.
+    fn write_packet_retrying(&mut self, packet: &[u8]) -> io::Result<usize> {
+        for attempt in 1..=self.max_retries + 1 {
+            match self.write_packet(packet) {
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted || is_timeout(e) => {
+                    self.report_retry(attempt, e)
+                }
+                result => return result,
+            }
+        }
+
+        self.cancel()?;
+        ioerr!(BrokenPipe, "bad transmit")
+    }
 
     /// Sends (uploads) a single packet to the inner stream using the XMODEM
     /// protocol. If `buf` is empty, end of transmissions is sent. Users of this
@@ -295,6 +729,10 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// transmission is complete. On success, returns the number of bytes
     /// written.
     ///
+    /// If `buf` holds at least 1024 bytes, its first 1024 bytes are sent as an
+    /// XMODEM-1K `STX` packet. Otherwise its first 128 bytes are sent as a
+    /// `SOH` packet.
+    ///
     /// The progress callback is called with `Progress::Waiting` before waiting
     /// for the receiver's `NAK`, `Progress::Started` when transmission of the
     /// first packet has started and subsequently with `Progress::Packet` when a
@@ -306,11 +744,11 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// point. Also returns an error if the XMODEM protocol indicates an error.
     /// In particular, an `InvalidData` error is returned when:
     ///
-    ///   * The receiver's first byte isn't a `NAK`.
+    ///   * The receiver's first byte isn't a `NAK` or `C`.
     ///   * The receiver doesn't respond with a `NAK` to the first `EOT`.
     ///   * The receiver doesn't respond with an `ACK` to the second `EOT`.
     ///   * The receiver responds to a complete packet with something besides
-    ///     `ACK` or `NAK`.
+    ///     `ACK`, `NAK` or `C`.
     ///
     /// An error of kind `UnexpectedEof` is returned if `buf.len() < 128 &&
     /// buf.len() != 0`.
@@ -318,37 +756,47 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// An error of kind `ConnectionAborted` is returned if a `CAN` byte is
     /// received when not expected.
     ///
-    /// An error of kind `Interrupted` is returned if a packet checksum fails.
+    /// An error of kind `Interrupted` is returned if the receiver rejects the
+    /// packet with `NAK` (or a repeated `C`), usually because its checksum
+    /// failed. The inner stream's timeout error is returned if the receiver
+    /// doesn't reply within the packet timeout. Either way, the packet should
+    /// be sent again.
     pub fn write_packet(&mut self, buf: &[u8]) -> io::Result<usize> {
-        if (!self.started){
-            self.expect_byte_or_cancel(NAK,"no start")?;
-            self.started=true;
-
-        }
+        self.wait_for_start()?;
         match buf.len() {
             0 => {
                 self.write_byte(EOT)?;
                 self.expect_byte(NAK, "Expected NAK after EOT")?;
                 self.write_byte(EOT)?;
-                self.expect_byte(ACK, "Expected ACK after final EOT")?;
+                self.expect_byte_or_cancel(ACK, "Expected ACK after final EOT")?;
                 Ok(0)
             }
-            
-            
-            len if len < 128 => ioerr!(UnexpectedEof, "Incomplete packet"),
-            _ => {
-                self.write_byte(SOH)?;
+
+
+            len if len < PACKET_SIZE => ioerr!(UnexpectedEof, "Incomplete packet"),
+            len => {
+                let (start, size) = match len {
+                    len if len >= PACKET_SIZE_1K => (STX, PACKET_SIZE_1K),
+                    _ => (SOH, PACKET_SIZE),
+                };
+
+                self.write_byte(start)?;
                 self.write_byte(self.packet)?;
                 self.write_byte(255 - self.packet)?;
-                self.inner.write_all(buf)?;
-                self.write_byte(get_checksum(buf))?;
-                
-                match self.expect_byte_or_cancel(ACK, "Expected ACK") {
-                    Ok(_) => {
+                self.inner.write_all(&buf[..size])?;
+                match self.mode {
+                    Mode::Checksum => self.write_byte(get_checksum(&buf[..size]))?,
+                    Mode::Crc => self.inner.write_all(&crc16(&buf[..size]).to_be_bytes())?,
+                }
+
+                match self.read_byte_within(self.packet_timeout)? {
+                    ACK => {
+                        self.report_packet(self.packet, size);
                         self.packet = self.packet.wrapping_add(1);
-                        Ok(128)
+                        Ok(size)
                     }
-                    Err(e) => Err(e),
+                    NAK | CRC => ioerr!(Interrupted, "Receiver rejected packet"),
+                    received => self.cancel_unexpected(received, "Expected ACK"),
                 }
             }
         }
@@ -1,22 +1,36 @@
+use shim::io;
+
 /// Enum representing how much progress has been made transmitting/receiving.
 ///
 /// A value of this type is passed in to the progress callback supplied to
 /// methods like [`Xmodem::transmit_with_progress()`],
 /// [`Xmodem::receive_with_progress()`], and [`Xmodem::new_with_progress()`]. It
 /// is intended to be used by progress indicators or for debugging purposes.
-#[derive(Debug, Copy, Clone)]
+///
+/// [`Xmodem::transmit_with_progress()`]: crate::Xmodem::transmit_with_progress
+/// [`Xmodem::receive_with_progress()`]: crate::Xmodem::receive_with_progress
+/// [`Xmodem::new_with_progress()`]: crate::Xmodem::new_with_progress
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Progress {
-    /// Waiting for receiver to send NAK.
+    /// Waiting for the receiver to send `NAK` or `C`.
     Waiting,
-    /// Download/upload has started.
-    Started,
-    /// Packet `.0` was transmitted/received.
-    Packet(u8),
-    NAK,
-    Unknown,
+    /// Download/upload has started. `total` is the number of bytes expected to
+    /// be transferred, if known.
+    Started { total: Option<u64> },
+    /// Packet `number` was transmitted/received, bringing the number of bytes
+    /// transferred so far, padding included, to `bytes` of an expected
+    /// `total`, if known.
+    Packet { number: u8, bytes: u64, total: Option<u64> },
+    /// Attempt `attempt`, counting from 1, at transferring packet `number`
+    /// failed because of `cause`. The packet is retried unless the retry limit
+    /// has been reached.
+    Retry { number: u8, attempt: usize, cause: io::ErrorKind },
+    /// The transfer failed because of `cause`.
+    Failed(io::ErrorKind),
 }
 
-/// Type for progress callbacks.
+/// Type for plain progress callbacks. Any `FnMut(Progress)` works as a
+/// progress callback; this is the type used when none is given.
 pub type ProgressFn = fn(Progress);
 
 /// Noop progress callback.
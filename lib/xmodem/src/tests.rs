@@ -1,6 +1,8 @@
 use super::*;
-use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel};
 use std::io::Cursor;
+use std::cell::Cell;
+use std::time::Duration;
 
 struct Pipe(Sender<u8>, Receiver<u8>, Vec<u8>);
 
@@ -12,8 +14,9 @@ fn pipe() -> (Pipe, Pipe) {
 impl io::Read for Pipe {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         for i in 0..buf.len() {
-            match self.1.recv() {
+            match self.1.recv_timeout(Duration::from_millis(10)) {
                 Ok(byte) => buf[i] = byte,
+                Err(RecvTimeoutError::Timeout) if i == 0 => return ioerr!(TimedOut, "pipe timeout"),
                 Err(_) => return Ok(i)
             }
         }
@@ -227,3 +230,682 @@ fn test_eot() {
 
     assert_eq!(&buffer[..], &[NAK, EOT, NAK, EOT, ACK]);
 }
+
+thread_local! {
+    static FAKE_TIME: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+/// A clock that only moves when a `Script` times out.
+fn fake_clock() -> Duration {
+    FAKE_TIME.with(|t| t.get())
+}
+
+/// A stream that replays scripted reads, which may be timeouts, and records
+/// everything written to it. Each timeout advances `fake_clock` by an hour.
+struct Script(std::collections::VecDeque<Option<u8>>, Vec<u8>);
+
+impl Script {
+    fn new(reads: Vec<Option<u8>>) -> Script {
+        Script(reads.into_iter().collect(), vec![])
+    }
+}
+
+impl io::Read for Script {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match self.0.pop_front() {
+            Some(Some(byte)) => { buf[0] = byte; Ok(1) }
+            Some(None) => {
+                FAKE_TIME.with(|t| t.set(t.get() + Duration::from_secs(3600)));
+                ioerr!(TimedOut, "scripted timeout")
+            }
+            None => Ok(0),
+        }
+    }
+}
+
+impl io::Write for Script {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.1.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_crc16() {
+    assert_eq!(crc16(b"123456789"), 0x31C3);
+    assert_eq!(crc16(&[]), 0);
+    assert_eq!(crc16(&[0; 128]), 0);
+}
+
+#[test]
+fn test_crc_loop() {
+    let mut input = [0u8; 384];
+    (0..384usize).for_each(|i| input[i] = (i * 7) as u8);
+
+    let (mut tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        let n = Xmodem::transmit(&input[..], &mut rx).expect("transmit okay");
+        (n, rx.2)
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = [0u8; 384];
+        Xmodem::receive_with_mode(&mut tx, &mut output[..], Mode::Crc).expect("receive okay");
+        (output, tx.2)
+    });
+
+    let (n, rx_buf) = tx_thread.join().expect("tx join okay");
+    let (output, tx_buf) = rx_thread.join().expect("rx join okay");
+    assert_eq!(n, 384);
+    assert_eq!(&input[..], &output[..]);
+
+    // each packet carries a 16-bit CRC, high byte first
+    for (i, chunk) in input.chunks(128).enumerate() {
+        let packet = &rx_buf[(i * 133)..((i + 1) * 133)];
+        assert_eq!(&packet[..3], &[SOH, i as u8 + 1, 255 - (i as u8 + 1)]);
+        assert_eq!(&packet[3..131], chunk);
+        assert_eq!(&packet[131..], &crc16(chunk).to_be_bytes());
+    }
+
+    assert_eq!(&rx_buf[399..], &[EOT, EOT]);
+    assert_eq!(&tx_buf, &[CRC, ACK, ACK, ACK, NAK, ACK]);
+}
+
+#[test]
+fn test_crc_bad_packet() {
+    let data = [0xAAu8; 128];
+    let crc = crc16(&data).wrapping_add(1).to_be_bytes();
+    let mut reads = vec![Some(SOH), Some(1), Some(254)];
+    reads.extend(data.iter().map(|&b| Some(b)));
+    reads.extend(crc.iter().map(|&b| Some(b)));
+
+    let mut xmodem = Xmodem::new_with_mode(Script::new(reads), Mode::Crc);
+    let mut packet = [0u8; 128];
+    let e = xmodem.read_packet(&mut packet[..]).expect_err("bad CRC");
+    assert_eq!(e.kind(), io::ErrorKind::Interrupted);
+    assert_eq!(&xmodem.inner.1, &[CRC, NAK]);
+}
+
+#[test]
+fn test_crc_fallback_to_checksum() {
+    // A checksum-only sender ignores `C` until the receiver falls back to NAK.
+    let data = [7u8; 128];
+    let mut reads = vec![None; CRC_ATTEMPTS];
+    reads.extend(vec![Some(SOH), Some(1), Some(254)]);
+    reads.extend(data.iter().map(|&b| Some(b)));
+    reads.push(Some(get_checksum(&data)));
+    reads.extend(vec![Some(EOT), Some(EOT)]);
+
+    let mut xmodem = Xmodem::new_with_mode(Script::new(reads), Mode::Crc);
+    xmodem.set_clock(Some(fake_clock));
+    let mut output = [0u8; 128];
+    let n = xmodem.receive_data(&mut output[..]).expect("receive okay");
+
+    assert_eq!(n, 128);
+    assert_eq!(&output[..], &data[..]);
+    assert_eq!(&xmodem.inner.1, &[CRC, CRC, CRC, NAK, ACK, NAK, ACK]);
+
+    // The fallback only lasts for that transfer: the next one asks for CRCs.
+    xmodem.inner.0.extend([Some(SOH), Some(1), Some(254)]);
+    xmodem.inner.0.extend(data.iter().map(|&b| Some(b)));
+    xmodem.inner.0.extend(crc16(&data).to_be_bytes().map(Some));
+    xmodem.inner.0.extend([Some(EOT), Some(EOT)]);
+    xmodem.inner.1.clear();
+    let n = xmodem.receive_data(&mut output[..]).expect("second receive okay");
+
+    assert_eq!(n, 128);
+    assert_eq!(xmodem.mode(), Mode::Crc);
+    assert_eq!(&xmodem.inner.1, &[CRC, ACK, NAK, ACK]);
+}
+
+#[test]
+fn test_crc_sender_follows_receiver() {
+    let mut buffer = vec![0u8; 1 + 3 + 128 + 2 + 1];
+    buffer[0] = CRC;
+    buffer[134] = ACK;
+
+    let mut xmodem = Xmodem::new(Cursor::new(buffer.as_mut_slice()));
+    xmodem.write_packet(&[9u8; 128]).expect("write packet");
+    assert_eq!(xmodem.mode(), Mode::Crc);
+
+    assert_eq!(&buffer[1..4], &[SOH, 1, 254]);
+    assert_eq!(&buffer[132..134], &crc16(&[9u8; 128]).to_be_bytes());
+}
+
+#[test]
+fn test_1k_loop() {
+    let mut input = vec![0u8; 2500];
+    input.iter_mut().enumerate().for_each(|(i, b)| *b = (i % 251) as u8);
+    let expected = input.clone();
+
+    let (mut tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        let n = Xmodem::transmit(&input[..], &mut rx).expect("transmit okay");
+        (n, rx.2)
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = vec![];
+        let n = Xmodem::receive_with_mode(&mut tx, &mut output, Mode::Crc).expect("receive okay");
+        (n, output)
+    });
+
+    let (n, rx_buf) = tx_thread.join().expect("tx join okay");
+    let (received, output) = rx_thread.join().expect("rx join okay");
+    assert_eq!(n, 2500);
+    assert_eq!(received, 2 * 1024 + 4 * 128);
+    assert_eq!(&output[..2500], &expected[..]);
+    assert!(output[2500..].iter().all(|&b| b == 0));
+
+    // two 1K packets, then the tail in 128-byte packets
+    let (full, small) = (3 + 1024 + 2, 3 + 128 + 2);
+    assert_eq!(&rx_buf[..3], &[STX, 1, 254]);
+    assert_eq!(&rx_buf[full..(full + 3)], &[STX, 2, 253]);
+    for i in 0..4 {
+        let start = 2 * full + i * small;
+        assert_eq!(&rx_buf[start..(start + 3)], &[SOH, 3 + i as u8, 252 - i as u8]);
+    }
+    assert_eq!(&rx_buf[(2 * full + 4 * small)..], &[EOT, EOT]);
+}
+
+#[test]
+fn test_no_1k_in_checksum_mode() {
+    let input = [0x5Au8; 2048];
+    let (mut tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        Xmodem::transmit(&input[..], &mut rx).expect("transmit okay");
+        rx.2
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = [0u8; 2048];
+        Xmodem::receive(&mut tx, &mut output[..]).expect("receive okay");
+        output
+    });
+
+    let rx_buf = tx_thread.join().expect("tx join okay");
+    let output = rx_thread.join().expect("rx join okay");
+    assert_eq!(&input[..], &output[..]);
+    assert_eq!(rx_buf.len(), 16 * (3 + 128 + 1) + 2);
+    assert!(rx_buf.chunks(3 + 128 + 1).take(16).all(|p| p[0] == SOH));
+}
+
+#[test]
+fn test_1k_packet_too_small_buffer() {
+    let mut xmodem = Xmodem::new(Script::new(vec![Some(STX), Some(1), Some(254)]));
+    let mut packet = [0u8; 128];
+    let e = xmodem.read_packet(&mut packet[..]).expect_err("1K into 128");
+    assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(&xmodem.inner.1, &[NAK, CAN]);
+}
+
+#[test]
+fn test_ymodem_header() {
+    let header = FileHeader::new(b"kernel.bin", Some(1000), Some(0o14_000_000_000)).expect("header");
+    let mut block = [0u8; 1024];
+    assert_eq!(header.encode(&mut block), 128);
+    assert_eq!(&block[..10], b"kernel.bin");
+    assert_eq!(block[10], 0);
+    assert_eq!(&block[11..27], b"1000 14000000000");
+    assert!(block[27..].iter().all(|&b| b == 0));
+
+    let decoded = FileHeader::decode(&block[..128]).expect("valid").expect("not empty");
+    assert_eq!(decoded.name(), b"kernel.bin");
+    assert_eq!(decoded.size, Some(1000));
+    assert_eq!(decoded.mtime, Some(0o14_000_000_000));
+
+    assert!(FileHeader::decode(&[0; 128]).expect("valid").is_none());
+    let e = FileHeader::decode(b"a\0ten\0").expect_err("bad size");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+    let e = FileHeader::new(b"", None, None).expect_err("empty name");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    let e = FileHeader::new(b"a\0b", None, None).expect_err("NUL in name");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_ymodem_long_name_uses_1k_header() {
+    let name = [b'x'; 200];
+    let header = FileHeader::new(&name, Some(1), None).expect("header");
+    let mut block = [0u8; 1024];
+    assert_eq!(header.encode(&mut block), 1024);
+
+    let decoded = FileHeader::decode(&block[..]).expect("valid").expect("not empty");
+    assert_eq!(decoded.name(), &name[..]);
+    assert_eq!(decoded.size, Some(1));
+    assert_eq!(decoded.mtime, None);
+}
+
+#[test]
+fn test_ymodem_batch() {
+    let first: Vec<u8> = (0..1300u32).map(|i| (i % 256) as u8).collect();
+    let second = b"hello, world!\n".to_vec();
+    let files = vec![
+        (FileHeader::new(b"first.bin", Some(first.len() as u64), Some(1234)).unwrap(), first),
+        (FileHeader::new(b"second.txt", Some(second.len() as u64), None).unwrap(), second),
+    ];
+    let expected = files.clone();
+
+    let (mut tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        let mut ymodem = Ymodem::new(&mut rx);
+        for (header, data) in files.iter() {
+            let n = ymodem.send_file(header, &data[..]).expect("send okay");
+            assert_eq!(n, data.len());
+        }
+        ymodem.finish().expect("finish okay");
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut ymodem = Ymodem::new(&mut tx);
+        let mut received = vec![];
+        loop {
+            let mut data = vec![];
+            match ymodem.receive_file(&mut data).expect("receive okay") {
+                Some((header, n)) => {
+                    assert_eq!(n, data.len());
+                    received.push((header, data));
+                }
+                None => return received,
+            }
+        }
+    });
+
+    tx_thread.join().expect("tx join okay");
+    let received = rx_thread.join().expect("rx join okay");
+    assert_eq!(received.len(), expected.len());
+    for ((header, data), (expected_header, expected_data)) in received.iter().zip(expected.iter()) {
+        assert_eq!(header.name(), expected_header.name());
+        assert_eq!(header.size, expected_header.size);
+        assert_eq!(header.mtime, expected_header.mtime);
+        assert_eq!(data, expected_data);
+    }
+}
+
+#[test]
+fn test_ymodem_empty_batch() {
+    let (mut tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        Ymodem::new(&mut rx).finish().expect("finish okay");
+        rx.2
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut data = vec![];
+        let file = Ymodem::new(&mut tx).receive_file(&mut data).expect("receive okay");
+        assert!(file.is_none());
+        tx.2
+    });
+
+    let rx_buf = tx_thread.join().expect("tx join okay");
+    let tx_buf = rx_thread.join().expect("rx join okay");
+    assert_eq!(&rx_buf[..3], &[SOH, 0, 255]);
+    assert!(rx_buf[3..131].iter().all(|&b| b == 0));
+    assert_eq!(&tx_buf, &[CRC, ACK]);
+}
+
+/// A pipe end that drops or corrupts the bytes it writes at chosen offsets.
+struct Faulty {
+    pipe: Pipe,
+    written: usize,
+    drop: Vec<usize>,
+    corrupt: Vec<usize>,
+}
+
+impl Faulty {
+    fn new(pipe: Pipe, drop: Vec<usize>, corrupt: Vec<usize>) -> Faulty {
+        Faulty { pipe, written: 0, drop, corrupt }
+    }
+}
+
+impl io::Read for Faulty {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.pipe.read(buf)
+    }
+}
+
+impl io::Write for Faulty {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            let offset = self.written;
+            self.written += 1;
+            if self.drop.contains(&offset) {
+                continue;
+            }
+
+            let byte = if self.corrupt.contains(&offset) { !byte } else { byte };
+            io::Write::write_all(&mut self.pipe, &[byte])?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_nak_retransmission() {
+    let mut input = [0u8; 256];
+    input.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+
+    // Flip a data byte of the first packet on its way to the receiver.
+    let (mut tx, rx) = pipe();
+    let mut rx = Faulty::new(rx, vec![], vec![10]);
+    let tx_thread = std::thread::spawn(move || {
+        let n = Xmodem::transmit(&input[..], &mut rx).expect("transmit okay");
+        (n, rx.pipe.2)
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = [0u8; 256];
+        Xmodem::receive(&mut tx, &mut output[..]).expect("receive okay");
+        (output, tx.2)
+    });
+
+    let (n, rx_buf) = tx_thread.join().expect("tx join okay");
+    let (output, tx_buf) = rx_thread.join().expect("rx join okay");
+    assert_eq!(n, 256);
+    assert_eq!(&input[..], &output[..]);
+
+    // packet 1 arrives corrupted, is resent intact and followed by packet 2
+    assert_eq!(rx_buf[10], !input[7]);
+    assert_eq!(&rx_buf[132..135], &[SOH, 1, 254]);
+    assert_eq!(&rx_buf[135..263], &input[..128]);
+    assert_eq!(&rx_buf[264..267], &[SOH, 2, 253]);
+    assert_eq!(&tx_buf, &[NAK, NAK, ACK, ACK, NAK, ACK]);
+}
+
+#[test]
+fn test_lost_ack_retransmission() {
+    let mut input = [0u8; 256];
+    input.iter_mut().enumerate().for_each(|(i, b)| *b = !(i as u8));
+
+    let (tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        let mut xmodem = Xmodem::new(&mut rx);
+        xmodem.set_timeouts(Duration::from_millis(100), Duration::from_millis(100));
+        let n = xmodem.send_data(&input[..]).expect("transmit okay");
+        (n, rx.2)
+    });
+
+    // Drop the receiver's ACK for the first packet.
+    let mut tx = Faulty::new(tx, vec![1], vec![]);
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = [0u8; 256];
+        let n = Xmodem::receive(&mut tx, &mut output[..]).expect("receive okay");
+        (n, output, tx.pipe.2)
+    });
+
+    let (n, rx_buf) = tx_thread.join().expect("tx join okay");
+    let (received, output, tx_buf) = rx_thread.join().expect("rx join okay");
+    assert_eq!(n, 256);
+    assert_eq!(received, 256);
+    assert_eq!(&input[..], &output[..]);
+
+    // the sender timed out and resent packet 1, which was acknowledged again
+    // but not written twice
+    assert_eq!(&rx_buf[0..132], &rx_buf[132..264]);
+    assert_eq!(&tx_buf, &[NAK, ACK, ACK, NAK, ACK]);
+}
+
+#[test]
+fn test_cancel_after_too_many_naks() {
+    let mut xmodem = Xmodem::new(Script::new(vec![Some(NAK); 5]));
+    xmodem.set_max_retries(3);
+    let e = xmodem.send_data(&[1u8; 128][..]).expect_err("too many NAKs");
+    assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+
+    // one attempt and three retries, then CAN CAN
+    let written = &xmodem.inner.1;
+    assert_eq!(written.len(), 4 * 132 + 2);
+    assert_eq!(&written[..3], &[SOH, 1, 254]);
+    assert_eq!(&written[396..399], &[SOH, 1, 254]);
+    assert_eq!(&written[528..], &[CAN, CAN]);
+}
+
+#[test]
+fn test_cancel_after_too_many_timeouts() {
+    let mut xmodem = Xmodem::new(Script::new(vec![Some(NAK), None, None, None]));
+    xmodem.set_clock(Some(fake_clock));
+    xmodem.set_max_retries(2);
+    let e = xmodem.send_data(&[2u8; 128][..]).expect_err("no reply");
+    assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+
+    let written = &xmodem.inner.1;
+    assert_eq!(written.len(), 3 * 132 + 2);
+    assert_eq!(&written[396..], &[CAN, CAN]);
+}
+
+#[test]
+fn test_receiver_cancels_after_too_many_timeouts() {
+    let mut xmodem = Xmodem::new(Script::new(vec![Some(EOT), None, None]));
+    xmodem.set_clock(Some(fake_clock));
+    xmodem.set_max_retries(1);
+
+    // the first EOT is NAKed, after which the sender goes quiet
+    let mut packet = [0u8; 128];
+    let e = xmodem.read_packet_retrying(&mut packet[..]).expect_err("no packet");
+    assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(&xmodem.inner.1, &[NAK, NAK, NAK, NAK, CAN, CAN]);
+}
+
+#[test]
+fn test_progress_events() {
+    let mut events = vec![];
+    let script = Script::new(vec![Some(NAK), Some(NAK), Some(ACK), Some(NAK), Some(ACK)]);
+    let mut xmodem = Xmodem::new_with_progress(script, |p| events.push(p));
+    xmodem.set_total(Some(100));
+    xmodem.send_data(&[3u8; 100][..]).expect("transmit okay");
+
+    assert_eq!(&events, &[
+        Progress::Waiting,
+        Progress::Started { total: Some(100) },
+        Progress::Retry { number: 1, attempt: 1, cause: io::ErrorKind::Interrupted },
+        Progress::Packet { number: 1, bytes: 128, total: Some(100) },
+    ]);
+}
+
+#[test]
+fn test_progress_reports_failure() {
+    let mut events = vec![];
+    let mut packet = [0u8; 128];
+    let script = Script::new(vec![Some(CAN)]);
+    let e = Xmodem::receive_with_progress(script, &mut packet[..], |p| events.push(p))
+        .expect_err("cancelled");
+
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+    assert_eq!(&events, &[
+        Progress::Started { total: None },
+        Progress::Failed(io::ErrorKind::ConnectionAborted),
+    ]);
+}
+
+#[test]
+fn test_ymodem_progress_total() {
+    let mut input = vec![0u8; 300];
+    input.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+    let expected = input.clone();
+
+    let (tx, rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        let mut events = vec![];
+        let mut ymodem = Ymodem::new_with_progress(rx, |p| events.push(p));
+        let header = FileHeader::new(b"data", Some(300), None).expect("valid header");
+        ymodem.send_file(&header, &input[..]).expect("send okay");
+        ymodem.finish().expect("finish okay");
+        drop(ymodem);
+        events
+    });
+
+    let mut ymodem = Ymodem::new(tx);
+    let mut output = vec![];
+    ymodem.receive_file(&mut output).expect("receive okay").expect("a file");
+    assert!(ymodem.receive_file(&mut output).expect("receive okay").is_none());
+    assert_eq!(output, expected);
+
+    let events = tx_thread.join().expect("tx join okay");
+    // the header packets don't count towards the file's total
+    let data = events.iter()
+        .filter(|p| matches!(p, Progress::Packet { total: Some(_), .. }))
+        .collect::<Vec<_>>();
+
+    assert_eq!(data, [
+        &Progress::Packet { number: 1, bytes: 128, total: Some(300) },
+        &Progress::Packet { number: 2, bytes: 256, total: Some(300) },
+        &Progress::Packet { number: 3, bytes: 384, total: Some(300) },
+    ]);
+}
+
+#[test]
+fn test_ymodem_waits_for_sender() {
+    // the sender starts late and only ends the batch; YMODEM never falls back
+    // to checksums, so every request is a `C`
+    let crc = crc::crc16(&[0; 128]);
+    let mut reads = vec![None; 4];
+    reads.extend([SOH, 0, 255].map(Some));
+    reads.extend([Some(0); 128]);
+    reads.extend([Some((crc >> 8) as u8), Some(crc as u8)]);
+
+    let mut script = Script::new(reads);
+    let mut ymodem = Ymodem::new(&mut script);
+    ymodem.set_clock(Some(fake_clock));
+    assert!(ymodem.receive_file(vec![]).expect("receive okay").is_none());
+    assert_eq!(&script.1, &[CRC, CRC, CRC, CRC, CRC, ACK]);
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc::crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc::crc32(&[]), 0);
+}
+
+/// Sends `files` over a pipe with ZMODEM, the sender's end wrapped by
+/// `faulty`, and returns what the receiver got along with its progress events.
+fn zmodem_transfer(files: Vec<(&'static [u8], Vec<u8>)>, faulty: fn(Pipe) -> Faulty)
+    -> (Vec<(FileHeader, Vec<u8>)>, Vec<Progress>)
+{
+    let (tx, rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        let mut zmodem = Zmodem::new(faulty(rx));
+        for (name, data) in files.iter() {
+            let header = FileHeader::new(name, Some(data.len() as u64), None).expect("valid header");
+            let n = zmodem.send_file(&header, Cursor::new(&data[..])).expect("send okay");
+            assert_eq!(n, data.len() as u64);
+        }
+
+        zmodem.finish().expect("finish okay");
+        // Hand the pipe back: dropping it now could fail the receiver's
+        // writes that are still in flight, which no real serial line does.
+        zmodem
+    });
+
+    let mut events = vec![];
+    let mut zmodem = Zmodem::new_with_progress(tx, |p| events.push(p));
+    let mut received = vec![];
+    loop {
+        let mut output = vec![];
+        match zmodem.receive_file(&mut output).expect("receive okay") {
+            Some((header, n)) => {
+                assert_eq!(n, output.len() as u64);
+                received.push((header, output));
+            }
+            None => break,
+        }
+    }
+
+    let _sender = tx_thread.join().expect("tx join okay");
+    (received, events)
+}
+
+/// `ZDLE` doubles as `CAN`, so it has to be escaped in file data.
+const ZDLE_BYTE: u8 = CAN;
+
+#[test]
+fn test_zmodem_loop() {
+    let big: Vec<u8> = (0..40000usize).map(|i| (i * 31 % 256) as u8).collect();
+    let files: Vec<(&'static [u8], Vec<u8>)> = vec![
+        (b"big.bin", big),
+        (b"exact.bin", vec![ZDLE_BYTE; 1024]),
+        (b"empty.bin", vec![]),
+    ];
+
+    let expected = files.clone();
+    let (received, events) = zmodem_transfer(files, |p| Faulty::new(p, vec![], vec![]));
+    assert_eq!(received.len(), expected.len());
+    for ((header, data), (name, input)) in received.iter().zip(expected.iter()) {
+        assert_eq!(header.name(), *name);
+        assert_eq!(header.size, Some(input.len() as u64));
+        assert_eq!(data, input);
+    }
+
+    assert!(!events.iter().any(|p| matches!(p, Progress::Retry { .. })));
+    assert!(events.contains(&Progress::Started { total: Some(40000) }));
+}
+
+#[test]
+fn test_zmodem_recovers_from_corruption() {
+    let input: Vec<u8> = (0..20000usize).map(|i| (i % 251) as u8).collect();
+    let files: Vec<(&'static [u8], Vec<u8>)> = vec![(b"data.bin", input.clone())];
+
+    // Corrupt a byte in the middle of the first data frame.
+    let (received, events) = zmodem_transfer(files, |p| Faulty::new(p, vec![], vec![3000]));
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].1, input);
+    assert!(events.iter().any(|p| matches!(p, Progress::Retry { .. })));
+}
+
+#[test]
+fn test_zmodem_receiver_cancels() {
+    let mut zmodem = Zmodem::new(Script::new(vec![Some(CAN); 5]));
+    let header = FileHeader::new(b"f", Some(1), None).expect("valid header");
+    let e = zmodem.send_file(&header, Cursor::new(&[1u8][..])).expect_err("cancelled");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+}
+
+/// Returns the reads of a hex ZMODEM header of type `kind` carrying `data`.
+fn zmodem_hex_header(kind: u8, data: [u8; 4]) -> Vec<Option<u8>> {
+    let mut bytes = vec![kind];
+    bytes.extend_from_slice(&data);
+    let crc = crc::crc16(&bytes);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+
+    let mut header = b"**\x18B".to_vec();
+    bytes.iter().for_each(|b| header.extend_from_slice(format!("{:02x}", b).as_bytes()));
+    header.into_iter().map(Some).collect()
+}
+
+#[test]
+fn test_zmodem_sender_notices_zrpos_mid_window() {
+    const ZRINIT: u8 = 1;
+    const ZRPOS: u8 = 9;
+
+    // The receiver asks to go back to 1024 while the second subpacket of the
+    // first window is on its way, and takes the rest once it's resent.
+    let mut reads = zmodem_hex_header(ZRINIT, [0, 0, 0, 0x20]);
+    reads.extend(zmodem_hex_header(ZRPOS, 0u32.to_le_bytes()));
+    reads.push(None);
+    reads.extend(zmodem_hex_header(ZRPOS, 1024u32.to_le_bytes()));
+    reads.extend([None, None]);
+    reads.extend(zmodem_hex_header(ZRINIT, [0, 0, 0, 0x20]));
+
+    let mut events = vec![];
+    let mut zmodem = Zmodem::new_with_progress(Script::new(reads), |p| events.push(p));
+    let input = vec![7u8; 3 * 1024 + 10];
+    let header = FileHeader::new(b"f", Some(input.len() as u64), None).expect("valid header");
+    assert_eq!(zmodem.send_file(&header, Cursor::new(&input[..])).expect("send okay"), 3082);
+    drop(zmodem);
+
+    let positions: Vec<_> = events.iter()
+        .filter_map(|p| match *p {
+            Progress::Packet { bytes, .. } => Some(Some(bytes)),
+            Progress::Retry { .. } => Some(None),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(positions, [Some(1024), Some(2048), None, Some(2048), Some(3072), Some(3082)]);
+}
@@ -0,0 +1,305 @@
+use core::fmt::{self, Write};
+
+use shim::io;
+use shim::ioerr;
+
+use crate::{is_timeout, ClockFn, Mode, Progress, ProgressFn, Xmodem, PACKET_SIZE, PACKET_SIZE_1K};
+
+/// Maximum length, in bytes, of a file name in a YMODEM header.
+pub const MAX_NAME_LEN: usize = 255;
+
+/// The contents of a YMODEM header (block 0): a file's name and, if known,
+/// its exact size and modification time.
+///
+/// A header with an empty name marks the end of a batch and is never returned
+/// to users of this type.
+#[derive(Clone)]
+pub struct FileHeader {
+    name: [u8; MAX_NAME_LEN],
+    name_len: usize,
+    /// The exact size of the file in bytes.
+    pub size: Option<u64>,
+    /// The file's modification time in seconds since the Unix epoch. Only sent
+    /// when `size` is known since the header's fields are positional.
+    pub mtime: Option<u64>,
+}
+
+impl FileHeader {
+    /// Returns a header for a file named `name` of `size` bytes last modified
+    /// at `mtime`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `name` is empty, contains a
+    /// `NUL` byte, or is longer than `MAX_NAME_LEN` bytes.
+    pub fn new(name: &[u8], size: Option<u64>, mtime: Option<u64>) -> io::Result<FileHeader> {
+        if name.is_empty() || name.len() > MAX_NAME_LEN || name.contains(&0) {
+            return ioerr!(InvalidInput, "invalid YMODEM file name");
+        }
+
+        let mut header = FileHeader { name: [0; MAX_NAME_LEN], name_len: name.len(), size, mtime };
+        header.name[..name.len()].copy_from_slice(name);
+        Ok(header)
+    }
+
+    /// Returns the file's name as sent by the transmitter.
+    pub fn name(&self) -> &[u8] {
+        &self.name[..self.name_len]
+    }
+
+    /// Writes the header into `block` as `name NUL size SP mtime NUL`, with
+    /// `size` in decimal and `mtime` in octal. `block` must be zeroed and at
+    /// least 1024 bytes long. Returns the packet size needed to send it.
+    pub(crate) fn encode(&self, block: &mut [u8]) -> usize {
+        match self.encode_info(block) {
+            len if len <= PACKET_SIZE => PACKET_SIZE,
+            _ => PACKET_SIZE_1K,
+        }
+    }
+
+    /// Writes the header into `block` like [`FileHeader::encode()`]. Returns
+    /// the length of the encoded header, terminating `NUL` included. ZMODEM
+    /// sends its file information in exactly this format.
+    pub(crate) fn encode_info(&self, block: &mut [u8]) -> usize {
+        block[..self.name_len].copy_from_slice(self.name());
+
+        let mut fields = SliceWriter { buf: &mut block[(self.name_len + 1)..], pos: 0 };
+        if let Some(size) = self.size {
+            // The buffer fits a maximal name and both fields, so this can't fail.
+            let _ = write!(fields, "{}", size);
+            if let Some(mtime) = self.mtime {
+                let _ = write!(fields, " {:o}", mtime);
+            }
+        }
+
+        // name, NUL, fields and the terminating NUL
+        self.name_len + fields.pos + 2
+    }
+
+    /// Parses a header from the header block `block`. Returns `None` for the
+    /// empty header that ends a batch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the block isn't a valid header.
+    pub(crate) fn decode(block: &[u8]) -> io::Result<Option<FileHeader>> {
+        let name_len = block.iter().position(|&b| b == 0).unwrap_or(block.len());
+        if name_len == 0 {
+            return Ok(None);
+        }
+
+        let rest = block.get((name_len + 1)..).unwrap_or(&[]);
+        let rest = &rest[..rest.iter().position(|&b| b == 0).unwrap_or(rest.len())];
+        let rest = match core::str::from_utf8(rest) {
+            Ok(rest) => rest,
+            Err(_) => return ioerr!(InvalidData, "invalid YMODEM header"),
+        };
+
+        let mut fields = rest.split(' ').filter(|f| !f.is_empty());
+        let size = parse_field(fields.next(), 10)?;
+        let mtime = parse_field(fields.next(), 8)?;
+        match FileHeader::new(&block[..name_len], size, mtime) {
+            Ok(header) => Ok(Some(header)),
+            Err(_) => ioerr!(InvalidData, "invalid YMODEM file name"),
+        }
+    }
+}
+
+impl fmt::Debug for FileHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FileHeader")
+            .field("name", &core::str::from_utf8(self.name()).unwrap_or("<binary>"))
+            .field("size", &self.size)
+            .field("mtime", &self.mtime)
+            .finish()
+    }
+}
+
+/// Parses an optional header field in base `radix`.
+fn parse_field(field: Option<&str>, radix: u32) -> io::Result<Option<u64>> {
+    match field.map(|f| u64::from_str_radix(f, radix)) {
+        None => Ok(None),
+        Some(Ok(value)) => Ok(Some(value)),
+        Some(Err(_)) => ioerr!(InvalidData, "invalid YMODEM header field"),
+    }
+}
+
+/// A `fmt::Write` sink over a fixed buffer.
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.pos + s.len();
+        if end > self.buf.len() {
+            return Err(fmt::Error);
+        }
+
+        self.buf[self.pos..end].copy_from_slice(s.as_bytes());
+        self.pos = end;
+        Ok(())
+    }
+}
+
+/// Passes the first `remaining` bytes through to `inner` and silently drops
+/// the rest, i.e., the padding of the last packet.
+struct Truncate<W> {
+    inner: W,
+    remaining: u64,
+}
+
+impl<W: io::Write> io::Write for Truncate<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = core::cmp::min(buf.len() as u64, self.remaining) as usize;
+        self.inner.write_all(&buf[..n])?;
+        self.remaining -= n as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Implementation of the YMODEM batch protocol.
+///
+/// YMODEM sends each file as a header block numbered `0`, carrying the file's
+/// name, size and modification time, followed by a regular XMODEM-1K transfer
+/// of its contents. A batch ends with an empty header. The receiver requests
+/// every header and every file's data with `C`, so CRCs are used throughout:
+/// unlike XMODEM, there's no fallback to checksums.
+pub struct Ymodem<T, P = ProgressFn> {
+    xmodem: Xmodem<T, P>,
+}
+
+impl<T: io::Read + io::Write> Ymodem<T> {
+    /// Returns a new `Ymodem` instance with the internal reader/writer set to
+    /// `inner`. The returned instance can be used for both receiving
+    /// (downloading) and sending (uploading).
+    pub fn new(inner: T) -> Self {
+        Ymodem::new_with_progress(inner, crate::progress::noop as ProgressFn)
+    }
+}
+
+impl<T: io::Read + io::Write, P: FnMut(Progress)> Ymodem<T, P> {
+    /// Returns a new `Ymodem` instance with the internal reader/writer set to
+    /// `inner`. The function `f` is used as a callback to indicate progress
+    /// throughout the transfer. See the [`Progress`](crate::Progress) enum for
+    /// more information. The expected total passed to `f` is the size of the
+    /// file being transferred, if its header carries one.
+    pub fn new_with_progress(inner: T, f: P) -> Self {
+        let mut xmodem = Xmodem::new_with_progress(inner, f);
+        xmodem.set_mode(Mode::Crc);
+        xmodem.fallback = false;
+        Ymodem { xmodem }
+    }
+
+    /// Sets the time source used to measure timeouts. See
+    /// [`Xmodem::set_clock()`].
+    pub fn set_clock(&mut self, clock: Option<ClockFn>) {
+        self.xmodem.set_clock(clock);
+    }
+
+    /// Sends (uploads) the file described by `header` with contents `data`.
+    /// Call [`Ymodem::finish()`] once every file of the batch has been sent.
+    ///
+    /// Returns the number of bytes read from `data`. If `data` yields more than
+    /// `header.size` bytes, the receiver drops the excess.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from `data` or the XMODEM transfer fails.
+    /// See [`Xmodem::write_packet()`] for details.
+    pub fn send_file<R: io::Read>(&mut self, header: &FileHeader, data: R) -> io::Result<usize> {
+        let mut block = [0u8; PACKET_SIZE_1K];
+        let size = header.encode(&mut block);
+        self.send_header(&block[..size])?;
+        self.xmodem.set_total(header.size);
+        let result = self.xmodem.send_data(data);
+        self.xmodem.set_total(None);
+        result
+    }
+
+    /// Ends the batch by sending an empty header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the receiver doesn't accept the header. See
+    /// [`Xmodem::write_packet()`] for details.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.send_header(&[0; PACKET_SIZE])
+    }
+
+    /// Sends the header block `block` as packet `0` once the receiver asks for
+    /// it. Afterwards, the receiver has to ask for the file's data.
+    fn send_header(&mut self, block: &[u8]) -> io::Result<()> {
+        self.xmodem.started = false;
+        self.xmodem.packet = 0;
+        self.xmodem.write_packet_retrying(block)?;
+        self.xmodem.started = false;
+        Ok(())
+    }
+
+    /// Receives (downloads) the next file of the batch into `into`. Returns
+    /// the file's header and the number of bytes written to `into`, or `None`
+    /// once the sender has ended the batch.
+    ///
+    /// If the header carries the file's size, exactly that many bytes are
+    /// written to `into` and the padding is dropped. Otherwise every received
+    /// byte, padding included, is written. The header is asked for until the
+    /// sender starts, however long that takes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the header is malformed or if
+    /// the sender ends a transmission instead of sending a header. Otherwise,
+    /// see [`Xmodem::read_packet()`] for details.
+    pub fn receive_file<W: io::Write>(&mut self, into: W) -> io::Result<Option<(FileHeader, usize)>> {
+        let mut block = [0u8; PACKET_SIZE_1K];
+        self.xmodem.started = false;
+        self.xmodem.packet = 0;
+        self.xmodem.set_total(None);
+        let n = loop {
+            // Nobody is sending yet: keep asking, as the bootloader does.
+            match self.xmodem.read_packet_retrying(&mut block) {
+                Err(ref e) if is_timeout(e) => self.xmodem.started = false,
+                result => break result?,
+            }
+        };
+        if n == 0 {
+            return ioerr!(InvalidData, "expected YMODEM header");
+        }
+
+        let header = match FileHeader::decode(&block[..n])? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        // The file's data has to be requested separately.
+        self.xmodem.started = false;
+        self.xmodem.set_total(header.size);
+        let written = match header.size {
+            Some(size) => {
+                let mut truncated = Truncate { inner: into, remaining: size };
+                self.xmodem.receive_data(&mut truncated)?;
+                (size - truncated.remaining) as usize
+            }
+            None => self.xmodem.receive_data(into)?,
+        };
+
+        Ok(Some((header, written)))
+    }
+
+    /// Flush this output stream, ensuring that all intermediately buffered
+    /// contents reach their destination.
+    ///
+    /// # Errors
+    ///
+    /// It is considered an error if not all bytes could be written due to I/O
+    /// errors or EOF being reached.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.xmodem.flush()
+    }
+}
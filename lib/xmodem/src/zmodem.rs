@@ -0,0 +1,772 @@
+use core::time::Duration;
+
+use shim::io;
+use shim::ioerr;
+
+use crate::crc::{crc16, crc32, crc32_update};
+use crate::read_ext::ReadExt;
+use crate::{is_timeout, ClockFn, FileHeader, Progress, ProgressFn, Xmodem, CAN, PACKET_SIZE_1K};
+
+const ZPAD: u8 = b'*';
+const ZDLE: u8 = CAN;
+const ZHEX: u8 = b'B';
+const ZBIN32: u8 = b'C';
+const XON: u8 = 0x11;
+const BS: u8 = 0x08;
+
+// Frame types.
+const ZRQINIT: u8 = 0;
+const ZRINIT: u8 = 1;
+const ZACK: u8 = 3;
+const ZFILE: u8 = 4;
+const ZSKIP: u8 = 5;
+const ZABORT: u8 = 7;
+const ZFIN: u8 = 8;
+const ZRPOS: u8 = 9;
+const ZDATA: u8 = 10;
+const ZEOF: u8 = 11;
+const ZFERR: u8 = 12;
+const ZCAN: u8 = 16;
+
+// Data subpacket terminators, sent after a `ZDLE`.
+const ZCRCE: u8 = b'h';
+const ZCRCG: u8 = b'i';
+const ZCRCQ: u8 = b'j';
+const ZCRCW: u8 = b'k';
+const ZRUB0: u8 = b'l';
+const ZRUB1: u8 = b'm';
+
+// `ZRINIT` capabilities, sent in `ZF0`.
+const CANFDX: u8 = 0x01;
+const CANOVIO: u8 = 0x02;
+const CANFC32: u8 = 0x20;
+
+/// `ZFILE` conversion option for binary transfers, sent in `ZF0`.
+const ZCBIN: u8 = 1;
+
+/// Maximum payload of a data subpacket.
+const SUBPACKET_SIZE: usize = PACKET_SIZE_1K;
+
+/// Bytes streamed before the sender waits for the receiver to acknowledge
+/// them, unless the receiver asks for less.
+const WINDOW_SIZE: u32 = 16 * 1024;
+
+/// A ZMODEM frame header: the frame type and four bytes of flags or a file
+/// position.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Header {
+    kind: u8,
+    data: [u8; 4],
+}
+
+impl Header {
+    /// Returns a header of type `kind` carrying the file position `pos`,
+    /// least significant byte first.
+    fn with_position(kind: u8, pos: u32) -> Header {
+        Header { kind, data: pos.to_le_bytes() }
+    }
+
+    /// Returns a header of type `kind` carrying the flags `ZF0` = `zf0`.
+    fn with_flags(kind: u8, zf0: u8) -> Header {
+        Header { kind, data: [0, 0, 0, zf0] }
+    }
+
+    fn position(&self) -> u32 {
+        u32::from_le_bytes(self.data)
+    }
+
+    fn flags(&self) -> u8 {
+        self.data[3]
+    }
+
+    /// The type followed by the data bytes, as covered by the header's CRC.
+    fn bytes(&self) -> [u8; 5] {
+        let d = self.data;
+        [self.kind, d[0], d[1], d[2], d[3]]
+    }
+}
+
+/// A byte read from a ZDLE-escaped stream.
+enum Escaped {
+    Byte(u8),
+    /// The end of a data subpacket, i.e., one of `ZCRCE`, `ZCRCG`, `ZCRCQ`
+    /// or `ZCRCW`.
+    End(u8),
+}
+
+/// Returns `true` if `byte` has to be ZDLE-escaped on the wire.
+fn needs_escape(byte: u8) -> bool {
+    matches!(byte & 0x7F, ZDLE | 0x10 | XON | 0x13)
+}
+
+/// Returns `true` if `e` is worth retrying: a timeout or a corrupted frame.
+fn is_retryable(e: &io::Error) -> bool {
+    is_timeout(e) || e.kind() == io::ErrorKind::InvalidData
+}
+
+/// Converts the ASCII hex digit `digit` to its value.
+fn from_hex(digit: u8) -> io::Result<u8> {
+    match digit {
+        b'0'..=b'9' => Ok(digit - b'0'),
+        b'a'..=b'f' => Ok(digit - b'a' + 10),
+        b'A'..=b'F' => Ok(digit - b'A' + 10),
+        _ => ioerr!(InvalidData, "invalid hex digit in ZMODEM header"),
+    }
+}
+
+/// Implementation of the ZMODEM streaming protocol.
+///
+/// Unlike XMODEM, the sender doesn't wait for every packet to be
+/// acknowledged. File data is streamed in 1024-byte subpackets protected by a
+/// CRC-32 and only acknowledged once every 16 KiB, or as often as the receiver
+/// asks. A receiver that detects an error replies with `ZRPOS`, naming the
+/// file position to resume from, and the sender seeks back there as soon as it
+/// notices: it checks for a reply between subpackets without waiting for one,
+/// so the inner stream's reads should time out or fail with `WouldBlock`
+/// quickly when there's nothing to read. Frames are always sent with CRC-32s,
+/// so the receiver has to support them.
+///
+/// Files are described with a [`FileHeader`], as in YMODEM. Timeouts, the
+/// clock and the retry limit work as they do for [`Xmodem`]; progress is
+/// reported with file positions as byte counts.
+pub struct Zmodem<T, P = ProgressFn> {
+    xmodem: Xmodem<T, P>,
+    started: bool,
+    window: u32,
+}
+
+impl<T: io::Read + io::Write> Zmodem<T> {
+    /// Returns a new `Zmodem` instance with the internal reader/writer set to
+    /// `inner`. The returned instance can be used for both receiving
+    /// (downloading) and sending (uploading).
+    pub fn new(inner: T) -> Self {
+        Zmodem::new_with_progress(inner, crate::progress::noop as ProgressFn)
+    }
+}
+
+impl<T: io::Read + io::Write, P: FnMut(Progress)> Zmodem<T, P> {
+    /// Returns a new `Zmodem` instance with the internal reader/writer set to
+    /// `inner`. The function `f` is used as a callback to indicate progress
+    /// throughout the transfer. See the [`Progress`](crate::Progress) enum for
+    /// more information. Packet numbers count data subpackets.
+    pub fn new_with_progress(inner: T, f: P) -> Self {
+        Zmodem { xmodem: Xmodem::new_with_progress(inner, f), started: false, window: WINDOW_SIZE }
+    }
+
+    /// Sets the time source used to measure timeouts. See
+    /// [`Xmodem::set_clock()`].
+    pub fn set_clock(&mut self, clock: Option<ClockFn>) {
+        self.xmodem.set_clock(clock);
+    }
+
+    /// Sets how long to wait for each byte of a frame after its first and how
+    /// long to wait for a frame or for the reply to one. See
+    /// [`Xmodem::set_timeouts()`].
+    pub fn set_timeouts(&mut self, byte: Duration, frame: Duration) {
+        self.xmodem.set_timeouts(byte, frame);
+    }
+
+    /// Sets how many times a frame is retried before the transfer is
+    /// cancelled. See [`Xmodem::set_max_retries()`].
+    pub fn set_max_retries(&mut self, retries: usize) {
+        self.xmodem.set_max_retries(retries);
+    }
+
+    /// Sends (uploads) the file described by `header` with contents `data`.
+    /// Call [`Zmodem::finish()`] once every file of the session has been sent.
+    ///
+    /// `data` is read from the position the receiver asks for, which is where
+    /// `data` is seeked to whenever the receiver reports an error. Returns the
+    /// position of the end of the file, i.e., the number of bytes the receiver
+    /// has, or `0` if the receiver skipped the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from or seeking `data` fails. Returns an
+    /// error of kind `ConnectionAborted` if the receiver aborts the transfer
+    /// and `Other` if it can't check CRC-32s. If the receiver keeps
+    /// rejecting frames or stays silent for more than the retry limit allows,
+    /// the transfer is cancelled and an error of kind `BrokenPipe` is
+    /// returned.
+    pub fn send_file<R>(&mut self, header: &FileHeader, data: R) -> io::Result<u64>
+        where R: io::Read + io::Seek
+    {
+        let result = self.send_file_inner(header, data);
+        self.xmodem.report_failure(result)
+    }
+
+    /// Ends the session by sending `ZFIN` and waiting for the receiver to
+    /// answer in kind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the receiver doesn't answer within the retry limit.
+    /// See [`Zmodem::send_file()`] for details.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.wait_for_receiver()?;
+        for attempt in 1..=self.xmodem.max_retries + 1 {
+            self.write_hex_header(Header::with_position(ZFIN, 0))?;
+            match self.read_header() {
+                Ok(h) if h.kind == ZFIN => {
+                    // "Over and out"
+                    self.xmodem.inner.write_all(b"OO")?;
+                    self.started = false;
+                    return Ok(());
+                }
+                Ok(_) => continue,
+                Err(ref e) if is_retryable(e) => self.xmodem.report_retry(attempt, e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.cancel()?;
+        ioerr!(BrokenPipe, "bad transmit")
+    }
+
+    /// Receives (downloads) the next file of the session into `into`. Returns
+    /// the file's header and the number of bytes written to `into`, or `None`
+    /// once the sender has ended the session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the file information is
+    /// malformed. Returns an error of kind `ConnectionAborted` if the sender
+    /// aborts the transfer. If writing to `into` fails, the transfer is
+    /// cancelled and the write error is returned. If the sender keeps sending
+    /// corrupted frames or stays silent for more than the retry limit allows,
+    /// the transfer is cancelled and an error of kind `BrokenPipe` is
+    /// returned.
+    pub fn receive_file<W: io::Write>(&mut self, into: W) -> io::Result<Option<(FileHeader, u64)>> {
+        let result = self.receive_file_inner(into);
+        self.xmodem.report_failure(result)
+    }
+
+    /// Flush this output stream, ensuring that all intermediately buffered
+    /// contents reach their destination.
+    ///
+    /// # Errors
+    ///
+    /// It is considered an error if not all bytes could be written due to I/O
+    /// errors or EOF being reached.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.xmodem.flush()
+    }
+
+    /// Announces a session with `ZRQINIT`, if that hasn't happened yet, and
+    /// waits for the receiver's `ZRINIT` to learn its buffer size.
+    fn wait_for_receiver(&mut self) -> io::Result<()> {
+        if self.started {
+            return Ok(());
+        }
+
+        (self.xmodem.progress)(Progress::Waiting);
+        // Starts `rz` if the other end happens to be a shell.
+        self.xmodem.inner.write_all(b"rz\r")?;
+        for attempt in 1..=self.xmodem.max_retries + 1 {
+            self.write_hex_header(Header::with_position(ZRQINIT, 0))?;
+            match self.read_header() {
+                Ok(h) if h.kind == ZRINIT => {
+                    if h.flags() & CANFC32 == 0 {
+                        self.cancel()?;
+                        return ioerr!(Other, "ZMODEM receiver doesn't support CRC-32");
+                    }
+
+                    // A buffer size of zero means the receiver can take a
+                    // nonstop stream.
+                    self.window = match u16::from_le_bytes([h.data[0], h.data[1]]) as u32 {
+                        0 => WINDOW_SIZE,
+                        size => core::cmp::min(size, WINDOW_SIZE),
+                    };
+                    self.started = true;
+                    return Ok(());
+                }
+                Ok(h) if matches!(h.kind, ZABORT | ZFERR | ZCAN) => {
+                    return ioerr!(ConnectionAborted, "ZMODEM receiver aborted");
+                }
+                Ok(_) => continue,
+                Err(ref e) if is_retryable(e) => self.xmodem.report_retry(attempt, e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.cancel()?;
+        ioerr!(BrokenPipe, "bad transmit")
+    }
+
+    fn send_file_inner<R: io::Read + io::Seek>(&mut self, header: &FileHeader, mut data: R) -> io::Result<u64> {
+        self.wait_for_receiver()?;
+
+        let mut info = [0u8; SUBPACKET_SIZE];
+        let len = header.encode_info(&mut info);
+        let mut attempt = 0;
+        let mut resend = true;
+        let start = loop {
+            if resend {
+                self.write_bin32_header(Header::with_flags(ZFILE, ZCBIN))?;
+                self.write_subpacket(&info[..len], ZCRCW)?;
+            }
+
+            resend = true;
+            match self.read_header() {
+                Ok(h) if h.kind == ZRPOS => break h.position(),
+                Ok(h) if h.kind == ZSKIP => return Ok(0),
+                Ok(h) if matches!(h.kind, ZABORT | ZFERR | ZFIN | ZCAN) => {
+                    return ioerr!(ConnectionAborted, "ZMODEM receiver aborted");
+                }
+                // A late answer to our `ZRQINIT`: the `ZFILE` is still coming.
+                Ok(h) if h.kind == ZRINIT => resend = false,
+                Ok(_) => {}
+                Err(ref e) if is_retryable(e) => {}
+                Err(e) => return Err(e),
+            }
+
+            attempt += 1;
+            if attempt > self.xmodem.max_retries {
+                self.cancel()?;
+                return ioerr!(BrokenPipe, "bad transmit");
+            }
+        };
+
+        self.xmodem.set_total(header.size);
+        self.xmodem.report_start();
+        let result = self.stream(&mut data, start);
+        self.xmodem.set_total(None);
+        result.map(u64::from)
+    }
+
+    /// Streams `data` from position `pos` until the receiver has all of it,
+    /// going back to wherever the receiver asks. Returns the end position.
+    fn stream<R: io::Read + io::Seek>(&mut self, data: &mut R, mut pos: u32) -> io::Result<u32> {
+        let mut block = [0u8; SUBPACKET_SIZE];
+        let size = core::cmp::min(SUBPACKET_SIZE, self.window as usize);
+        let mut acked = pos;
+        let mut attempt = 0;
+        loop {
+            data.seek(io::SeekFrom::Start(pos as u64))?;
+            self.write_bin32_header(Header::with_position(ZDATA, pos))?;
+
+            // Stream a window's worth of subpackets. The last one of a window
+            // asks for a `ZACK`; the last one of the file ends the frame. A
+            // reply that starts before then, such as a `ZRPOS` after a bad
+            // subpacket, cuts the frame short.
+            let window_end = pos.saturating_add(self.window);
+            let (eof, reply) = loop {
+                let n = data.read_max(&mut block[..size])?;
+                let next = pos + n as u32;
+                let (end, eof) = match n {
+                    n if n < size => (ZCRCE, true),
+                    _ if next >= window_end => (ZCRCW, false),
+                    _ => (ZCRCG, false),
+                };
+
+                self.write_subpacket(&block[..n], end)?;
+                pos = next;
+                self.report_position(pos);
+                if end != ZCRCG {
+                    break (eof, None);
+                }
+
+                match self.poll_byte()? {
+                    Some(byte) if byte & 0x7F == ZPAD || byte == CAN => break (false, Some(byte)),
+                    _ => {}
+                }
+            };
+
+            if eof {
+                self.write_bin32_header(Header::with_position(ZEOF, pos))?;
+            }
+
+            let header = match reply {
+                Some(byte) => self.read_header_from(byte),
+                None => self.read_header(),
+            };
+            let cause = match header {
+                Ok(h) if h.kind == ZRINIT && eof => return Ok(pos),
+                Ok(h) if h.kind == ZACK && !eof => {
+                    acked = pos;
+                    attempt = 0;
+                    continue;
+                }
+                Ok(h) if h.kind == ZRPOS => {
+                    pos = h.position();
+                    acked = pos;
+                    io::ErrorKind::InvalidData
+                }
+                Ok(h) if matches!(h.kind, ZABORT | ZFERR | ZFIN | ZCAN) => {
+                    return ioerr!(ConnectionAborted, "ZMODEM receiver aborted");
+                }
+                Ok(_) => {
+                    pos = acked;
+                    io::ErrorKind::InvalidData
+                }
+                Err(ref e) if is_retryable(e) => {
+                    pos = acked;
+                    e.kind()
+                }
+                Err(e) => return Err(e),
+            };
+
+            attempt += 1;
+            let number = self.xmodem.packet;
+            (self.xmodem.progress)(Progress::Retry { number, attempt, cause });
+            if attempt > self.xmodem.max_retries {
+                self.cancel()?;
+                return ioerr!(BrokenPipe, "bad transmit");
+            }
+        }
+    }
+
+    fn receive_file_inner<W: io::Write>(&mut self, mut into: W) -> io::Result<Option<(FileHeader, u64)>> {
+        let mut block = [0u8; SUBPACKET_SIZE];
+        let mut attempt = 0;
+        let header = loop {
+            self.write_hex_header(Header::with_flags(ZRINIT, CANFDX | CANOVIO | CANFC32))?;
+            let result = match self.read_header() {
+                Ok(h) if h.kind == ZFILE => self.read_subpacket(&mut block).map(Some),
+                Ok(h) if h.kind == ZFIN => {
+                    self.write_hex_header(Header::with_position(ZFIN, 0))?;
+                    return Ok(None);
+                }
+                Ok(_) => Ok(None),
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(Some((n, _))) => match FileHeader::decode(&block[..n])? {
+                    Some(header) => break header,
+                    None => return ioerr!(InvalidData, "empty ZMODEM file name"),
+                },
+                Ok(None) => {}
+                Err(ref e) if is_retryable(e) => {}
+                Err(e) => return Err(e),
+            }
+
+            attempt += 1;
+            if attempt > self.xmodem.max_retries {
+                self.cancel()?;
+                return ioerr!(BrokenPipe, "bad receive");
+            }
+        };
+
+        self.xmodem.set_total(header.size);
+        self.xmodem.report_start();
+        let result = self.receive_stream(&mut into, &mut block);
+        self.xmodem.set_total(None);
+        Ok(Some((header, result? as u64)))
+    }
+
+    /// Receives file data into `into` until the sender's `ZEOF` matches the
+    /// data received. Returns the number of bytes received.
+    fn receive_stream<W: io::Write>(&mut self, into: &mut W, block: &mut [u8; SUBPACKET_SIZE]) -> io::Result<u32> {
+        let mut pos = 0;
+        let mut attempt = 0;
+        self.write_hex_header(Header::with_position(ZRPOS, pos))?;
+        loop {
+            let cause = match self.read_header() {
+                Ok(h) if h.kind == ZDATA && h.position() == pos => {
+                    match self.receive_frame(into, block, &mut pos) {
+                        Ok(()) => {
+                            attempt = 0;
+                            continue;
+                        }
+                        Err(ref e) if is_retryable(e) => e.kind(),
+                        Err(e) => return Err(e),
+                    }
+                }
+                Ok(h) if h.kind == ZEOF && h.position() == pos => return Ok(pos),
+                Ok(h) if matches!(h.kind, ZABORT | ZCAN) => {
+                    return ioerr!(ConnectionAborted, "ZMODEM sender aborted");
+                }
+                // Data from the wrong place, a stale `ZEOF` or a repeated
+                // `ZFILE` (with its file information): ask for `pos` again.
+                Ok(h) => {
+                    if h.kind == ZFILE {
+                        let _ = self.read_subpacket(block);
+                    }
+                    io::ErrorKind::InvalidData
+                }
+                Err(ref e) if is_retryable(e) => e.kind(),
+                Err(e) => return Err(e),
+            };
+
+            attempt += 1;
+            let number = self.xmodem.packet;
+            (self.xmodem.progress)(Progress::Retry { number, attempt, cause });
+            if attempt > self.xmodem.max_retries {
+                self.cancel()?;
+                return ioerr!(BrokenPipe, "bad receive");
+            }
+
+            self.write_hex_header(Header::with_position(ZRPOS, pos))?;
+        }
+    }
+
+    /// Receives the data subpackets of a `ZDATA` frame starting at `*pos` into
+    /// `into`, advancing `*pos` past every intact subpacket.
+    fn receive_frame<W: io::Write>(&mut self, into: &mut W, block: &mut [u8; SUBPACKET_SIZE], pos: &mut u32) -> io::Result<()> {
+        loop {
+            let (n, end) = self.read_subpacket(block)?;
+            if let Err(e) = into.write_all(&block[..n]) {
+                self.cancel()?;
+                return Err(e);
+            }
+
+            *pos += n as u32;
+            self.report_position(*pos);
+            match end {
+                ZCRCW => {
+                    self.write_hex_header(Header::with_position(ZACK, *pos))?;
+                    return Ok(());
+                }
+                ZCRCQ => self.write_hex_header(Header::with_position(ZACK, *pos))?,
+                ZCRCG => {}
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Reports the file position `pos` as the number of bytes transferred.
+    fn report_position(&mut self, pos: u32) {
+        let xmodem = &mut self.xmodem;
+        xmodem.bytes = pos as u64;
+        (xmodem.progress)(Progress::Packet { number: xmodem.packet, bytes: xmodem.bytes, total: xmodem.total });
+        xmodem.packet = xmodem.packet.wrapping_add(1);
+    }
+
+    /// Cancels the transfer with the standard eight `CAN`s followed by as many
+    /// backspaces to erase them from a terminal.
+    fn cancel(&mut self) -> io::Result<()> {
+        self.xmodem.inner.write_all(&[CAN; 8])?;
+        self.xmodem.inner.write_all(&[BS; 8])
+    }
+
+    /// Writes `header` in hex: `ZPAD ZPAD ZDLE ZHEX`, the type, data and
+    /// CRC-16 as lowercase hex digits, `CR LF` and, to undo a stray `XOFF`,
+    /// `XON` unless the header ends the session.
+    fn write_hex_header(&mut self, header: Header) -> io::Result<()> {
+        const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+        let bytes = header.bytes();
+        let crc = crc16(&bytes).to_be_bytes();
+        let mut buf = [0u8; 21];
+        buf[..4].copy_from_slice(&[ZPAD, ZPAD, ZDLE, ZHEX]);
+        for (i, &byte) in bytes.iter().chain(crc.iter()).enumerate() {
+            buf[4 + 2 * i] = DIGITS[(byte >> 4) as usize];
+            buf[5 + 2 * i] = DIGITS[(byte & 0xF) as usize];
+        }
+
+        buf[18] = b'\r';
+        buf[19] = b'\n' | 0x80;
+        let len = match header.kind {
+            ZACK | ZFIN => 20,
+            _ => {
+                buf[20] = XON;
+                21
+            }
+        };
+
+        self.xmodem.inner.write_all(&buf[..len])
+    }
+
+    /// Writes `header` in binary with a CRC-32: `ZPAD ZDLE ZBIN32` followed by
+    /// the escaped type, data and CRC.
+    fn write_bin32_header(&mut self, header: Header) -> io::Result<()> {
+        let bytes = header.bytes();
+        self.xmodem.inner.write_all(&[ZPAD, ZDLE, ZBIN32])?;
+        self.write_escaped(&bytes)?;
+        self.write_escaped(&crc32(&bytes).to_le_bytes())
+    }
+
+    /// Writes `data` as a data subpacket ending with `end` and its CRC-32.
+    fn write_subpacket(&mut self, data: &[u8], end: u8) -> io::Result<()> {
+        self.write_escaped(data)?;
+        self.xmodem.inner.write_all(&[ZDLE, end])?;
+        let crc = !crc32_update(crc32_update(!0, data), &[end]);
+        self.write_escaped(&crc.to_le_bytes())
+    }
+
+    /// Writes `data`, escaping the bytes that could be mistaken for `ZDLE` or
+    /// flow control.
+    fn write_escaped(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut buf = [0u8; 256];
+        let mut len = 0;
+        for &byte in data {
+            if len + 2 > buf.len() {
+                self.xmodem.inner.write_all(&buf[..len])?;
+                len = 0;
+            }
+
+            if needs_escape(byte) {
+                buf[len] = ZDLE;
+                buf[len + 1] = byte ^ 0x40;
+                len += 2;
+            } else {
+                buf[len] = byte;
+                len += 1;
+            }
+        }
+
+        self.xmodem.inner.write_all(&buf[..len])
+    }
+
+    /// Reads the next frame header, skipping anything in front of it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the header is corrupted or in
+    /// a format other than hex or binary with a CRC-32, and of kind
+    /// `ConnectionAborted` if five `CAN`s in a row arrive. Returns the inner
+    /// stream's timeout error if no header starts within the frame timeout.
+    fn read_header(&mut self) -> io::Result<Header> {
+        let byte = self.xmodem.read_byte_within(self.xmodem.packet_timeout)?;
+        self.read_header_from(byte)
+    }
+
+    /// Reads the next frame header like [`Zmodem::read_header()`], with `byte`
+    /// already read.
+    fn read_header_from(&mut self, mut byte: u8) -> io::Result<Header> {
+        let mut pad = false;
+        let mut cans = 0;
+        loop {
+            cans = if byte == CAN { cans + 1 } else { 0 };
+            if cans >= 5 {
+                return ioerr!(ConnectionAborted, "received CAN");
+            }
+
+            if pad && byte == ZDLE {
+                let format = self.xmodem.read_byte_within(self.xmodem.byte_timeout)?;
+                match format {
+                    ZHEX => return self.read_hex_header(),
+                    ZBIN32 => return self.read_bin32_header(),
+                    CAN => cans += 1,
+                    _ => return ioerr!(InvalidData, "unsupported ZMODEM header format"),
+                }
+            }
+
+            pad = byte & 0x7F == ZPAD;
+            byte = self.xmodem.read_byte_within(self.xmodem.packet_timeout)?;
+        }
+    }
+
+    /// Returns the next byte from the receiver if one has already arrived,
+    /// without waiting for one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `UnexpectedEof` if the inner stream has ended.
+    /// Otherwise, returns the inner stream's error unless it means there's
+    /// nothing to read yet.
+    fn poll_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut buf = [0u8; 1];
+        match self.xmodem.inner.read(&mut buf) {
+            Ok(0) => ioerr!(UnexpectedEof, "ZMODEM receiver hung up"),
+            Ok(_) => Ok(Some(buf[0])),
+            Err(ref e) if is_timeout(e) || e.kind() == io::ErrorKind::Interrupted => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads the rest of a hex header, after `ZHEX`.
+    fn read_hex_header(&mut self) -> io::Result<Header> {
+        let mut digits = [0u8; 14];
+        self.xmodem.read_exact_within(&mut digits, self.xmodem.byte_timeout)?;
+
+        let mut bytes = [0u8; 7];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+            *byte = (from_hex(pair[0])? << 4) | from_hex(pair[1])?;
+        }
+
+        if crc16(&bytes[..5]).to_be_bytes() != bytes[5..] {
+            return ioerr!(InvalidData, "ZMODEM header CRC mismatch");
+        }
+
+        Ok(Header { kind: bytes[0], data: [bytes[1], bytes[2], bytes[3], bytes[4]] })
+    }
+
+    /// Reads the rest of a binary header with a CRC-32, after `ZBIN32`.
+    fn read_bin32_header(&mut self) -> io::Result<Header> {
+        let mut bytes = [0u8; 9];
+        for byte in bytes.iter_mut() {
+            *byte = match self.read_escaped()? {
+                Escaped::Byte(byte) => byte,
+                Escaped::End(_) => return ioerr!(InvalidData, "unexpected ZDLE in ZMODEM header"),
+            };
+        }
+
+        if crc32(&bytes[..5]).to_le_bytes() != bytes[5..] {
+            return ioerr!(InvalidData, "ZMODEM header CRC mismatch");
+        }
+
+        Ok(Header { kind: bytes[0], data: [bytes[1], bytes[2], bytes[3], bytes[4]] })
+    }
+
+    /// Reads a data subpacket into `block`. Returns its length and how it
+    /// ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the subpacket is too long or
+    /// its CRC-32 doesn't match.
+    fn read_subpacket(&mut self, block: &mut [u8; SUBPACKET_SIZE]) -> io::Result<(usize, u8)> {
+        let mut len = 0;
+        let end = loop {
+            match self.read_escaped()? {
+                Escaped::Byte(byte) if len < block.len() => {
+                    block[len] = byte;
+                    len += 1;
+                }
+                Escaped::Byte(_) => return ioerr!(InvalidData, "ZMODEM subpacket too long"),
+                Escaped::End(end) => break end,
+            }
+        };
+
+        let mut crc = [0u8; 4];
+        for byte in crc.iter_mut() {
+            *byte = match self.read_escaped()? {
+                Escaped::Byte(byte) => byte,
+                Escaped::End(_) => return ioerr!(InvalidData, "unexpected ZDLE in ZMODEM CRC"),
+            };
+        }
+
+        if !crc32_update(crc32_update(!0, &block[..len]), &[end]) != u32::from_le_bytes(crc) {
+            return ioerr!(InvalidData, "ZMODEM subpacket CRC mismatch");
+        }
+
+        Ok((len, end))
+    }
+
+    /// Reads a single, possibly escaped, byte. Unescaped flow control bytes are
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` for an invalid escape sequence
+    /// and of kind `ConnectionAborted` if five `CAN`s in a row arrive.
+    fn read_escaped(&mut self) -> io::Result<Escaped> {
+        let timeout = self.xmodem.byte_timeout;
+        loop {
+            let byte = match self.xmodem.read_byte_within(timeout)? {
+                ZDLE => self.xmodem.read_byte_within(timeout)?,
+                XON | 0x13 | 0x91 | 0x93 => continue,
+                byte => return Ok(Escaped::Byte(byte)),
+            };
+
+            return match byte {
+                ZCRCE | ZCRCG | ZCRCQ | ZCRCW => Ok(Escaped::End(byte)),
+                ZRUB0 => Ok(Escaped::Byte(0x7F)),
+                ZRUB1 => Ok(Escaped::Byte(0xFF)),
+                CAN => {
+                    for _ in 0..3 {
+                        if self.xmodem.read_byte_within(timeout)? != CAN {
+                            return ioerr!(InvalidData, "invalid ZDLE sequence");
+                        }
+                    }
+
+                    ioerr!(ConnectionAborted, "received CAN")
+                }
+                byte if byte & 0x60 == 0x40 => Ok(Escaped::Byte(byte ^ 0x40)),
+                _ => ioerr!(InvalidData, "invalid ZDLE sequence"),
+            };
+        }
+    }
+}
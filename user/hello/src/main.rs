@@ -0,0 +1,12 @@
+//! Says hello, with its process ID and the time since boot.
+
+#![no_std]
+#![no_main]
+
+use kernel_api::println;
+use kernel_api::syscall::{getpid, time};
+
+#[no_mangle]
+fn main() {
+    println!("hello from process {}, {:?} after boot", getpid(), time());
+}
@@ -0,0 +1,20 @@
+//! Sleeps a few times, printing how long each sleep took, so that the
+//! scheduler's idle time shows in `uptime`.
+
+#![no_std]
+#![no_main]
+
+use core::time::Duration;
+
+use kernel_api::println;
+use kernel_api::syscall::{getpid, sleep};
+
+#[no_mangle]
+fn main() {
+    for i in 1..=5 {
+        match sleep(Duration::from_millis(200 * i)) {
+            Ok(slept) => println!("[process {}] slept {:?}", getpid(), slept),
+            Err(e) => println!("[process {}] sleep failed: {}", getpid(), e),
+        }
+    }
+}